@@ -0,0 +1,179 @@
+//! Separate debug file lookup
+//!
+//! Stripped binaries often ship their DWARF in a separate file, found either
+//! through the `.note.gnu.build-id` note (`.build-id/xx/yyyy.debug` under a
+//! debug directory, carrying the same note) or through the `.gnu_debuglink`
+//! section, which names the file and records its CRC32.
+//!
+//! Debug files compressed with `dwz` may in turn refer to a supplementary
+//! file through `.gnu_debugaltlink`, which names the file and records its
+//! build ID.
+
+use crate::parser::map_file;
+use object::Object;
+use std::path::{Path, PathBuf};
+
+/// System-wide debug directory searched after the user's directories
+const DEFAULT_DEBUG_DIR: &str = "/usr/lib/debug";
+
+/// Whether an object file carries DWARF of its own
+pub fn has_dwarf(object_file: &object::File) -> bool {
+    object_file.section_by_name(".debug_info").is_some()
+}
+
+/// Find the separate debug file for a binary.
+///
+/// The build ID is tried first, then the debug link. Candidates are checked
+/// against the binary's build ID or the link's CRC, so a stale debug file is
+/// not used.
+pub fn find_debug_file(
+    binary_path: &Path,
+    object_file: &object::File,
+    debug_dirs: &[PathBuf],
+) -> Option<PathBuf> {
+    let mut search_dirs = debug_dirs.to_vec();
+    search_dirs.push(PathBuf::from(DEFAULT_DEBUG_DIR));
+
+    if let Ok(Some(build_id)) = object_file.build_id() {
+        for candidate in find_by_build_id(build_id, &search_dirs) {
+            if has_build_id(&candidate, build_id) {
+                return Some(candidate);
+            }
+            eprintln!(
+                "Warning: Ignoring {}: build ID does not match the binary's",
+                candidate.display()
+            );
+        }
+    }
+
+    if let Ok(Some((file_name, crc))) = object_file.gnu_debuglink() {
+        let file_name = String::from_utf8_lossy(file_name).to_string();
+        if let Some(path) = find_by_debuglink(binary_path, &file_name, crc, &search_dirs) {
+            return Some(path);
+        }
+    }
+
+    None
+}
+
+/// Find the supplementary file named by a debug file's `.gnu_debugaltlink`.
+///
+/// A relative name is resolved against the directory of the debug file, and
+/// the debug directories are searched by the build ID the link records. The
+/// file found must carry that build ID.
+pub fn find_supplementary_file(
+    debug_file_path: &Path,
+    object_file: &object::File,
+    debug_dirs: &[PathBuf],
+) -> Option<PathBuf> {
+    let (file_name, build_id) = object_file.gnu_debugaltlink().ok()??;
+    let file_name = PathBuf::from(String::from_utf8_lossy(file_name).to_string());
+
+    let mut candidates = Vec::new();
+    if file_name.is_absolute() {
+        candidates.push(file_name);
+    } else {
+        let debug_file_dir = debug_file_path
+            .canonicalize()
+            .ok()
+            .and_then(|path| path.parent().map(Path::to_path_buf))
+            .unwrap_or_default();
+        candidates.push(debug_file_dir.join(file_name));
+    }
+    let mut search_dirs = debug_dirs.to_vec();
+    search_dirs.push(PathBuf::from(DEFAULT_DEBUG_DIR));
+    candidates.extend(find_by_build_id(build_id, &search_dirs));
+
+    for candidate in candidates {
+        if !candidate.is_file() {
+            continue;
+        }
+        if has_build_id(&candidate, build_id) {
+            return Some(candidate);
+        }
+        eprintln!(
+            "Warning: Ignoring {}: build ID does not match .gnu_debugaltlink",
+            candidate.display()
+        );
+    }
+
+    None
+}
+
+/// Look for `<dir>/.build-id/xx/yyyy.debug`, in the order of the directories
+fn find_by_build_id(build_id: &[u8], search_dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let Some((first, rest)) = build_id.split_first() else {
+        return Vec::new();
+    };
+    let hex: String = rest.iter().map(|byte| format!("{:02x}", byte)).collect();
+
+    search_dirs
+        .iter()
+        .map(|dir| {
+            dir.join(".build-id")
+                .join(format!("{:02x}", first))
+                .join(format!("{}.debug", hex))
+        })
+        .filter(|candidate| candidate.is_file())
+        .collect()
+}
+
+/// Whether a file's NT_GNU_BUILD_ID note holds `build_id`
+fn has_build_id(path: &Path, build_id: &[u8]) -> bool {
+    map_file(path).ok().is_some_and(|data| {
+        object::File::parse(&*data)
+            .ok()
+            .and_then(|object| object.build_id().ok().flatten())
+            .is_some_and(|file_id| file_id == build_id)
+    })
+}
+
+/// Look for the debug link target next to the binary, in its `.debug`
+/// subdirectory, and under each debug directory
+fn find_by_debuglink(
+    binary_path: &Path,
+    file_name: &str,
+    crc: u32,
+    search_dirs: &[PathBuf],
+) -> Option<PathBuf> {
+    let binary_dir = binary_path
+        .canonicalize()
+        .ok()
+        .and_then(|path| path.parent().map(Path::to_path_buf))
+        .unwrap_or_default();
+
+    let mut candidates = vec![
+        binary_dir.join(file_name),
+        binary_dir.join(".debug").join(file_name),
+    ];
+    for dir in search_dirs {
+        // Debug directories mirror the binary's location, as in /usr/lib/debug/usr/lib/...
+        let relative_dir = binary_dir.strip_prefix("/").unwrap_or(&binary_dir);
+        candidates.push(dir.join(relative_dir).join(file_name));
+        candidates.push(dir.join(file_name));
+    }
+
+    for candidate in candidates {
+        // The link may name the binary itself when it was not actually stripped
+        if !candidate.is_file() || same_file(&candidate, binary_path) {
+            continue;
+        }
+        match map_file(&candidate) {
+            Ok(data) if crc32fast::hash(&data) == crc => return Some(candidate),
+            Ok(_) => eprintln!(
+                "Warning: Ignoring {}: CRC does not match .gnu_debuglink",
+                candidate.display()
+            ),
+            Err(_) => {}
+        }
+    }
+
+    None
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
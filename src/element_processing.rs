@@ -4,7 +4,7 @@
 //! based on their declaration files.
 
 use crate::types::{Compound, Element, Function, Namespace};
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, HashSet};
 use std::path::Path;
 
 /// Configuration for element merging
@@ -127,7 +127,7 @@ fn anonymous_compound_key(compound: &Compound) -> Option<String> {
         for member in &compound.members {
             members_sig.push_str(&member.name);
             members_sig.push(':');
-            members_sig.push_str(&member.type_info.base_type);
+            members_sig.push_str(&member.type_info.to_string(""));
             if let Some(line) = member.line {
                 members_sig.push_str(&format!("@{}", line));
             }
@@ -160,7 +160,7 @@ fn format_compound_for_display(compound: &Compound) -> String {
     if compound.compound_type == "enum" {
         for (name, value) in &compound.enum_values {
             if let Some(v) = value {
-                output.push_str(&format!("    {} = {}, // 0x{:x}\n", name, v, v));
+                output.push_str(&format!("    {} = {}, // 0x{:x}\n", name, v, v.bits(8)));
             } else {
                 output.push_str(&format!("    {},\n", name));
             }
@@ -168,8 +168,8 @@ fn format_compound_for_display(compound: &Compound) -> String {
     } else {
         for member in &compound.members {
             output.push_str(&format!(
-                "    {} {};\n",
-                member.type_info.base_type, member.name
+                "    {};\n",
+                member.type_info.to_string(&member.name)
             ));
         }
     }
@@ -201,12 +201,12 @@ pub struct NamespaceByFile {
     /// The original namespace line
     pub line: Option<u64>,
     /// Children grouped by their decl_file
-    pub children_by_file: HashMap<Option<u64>, Vec<Element>>,
+    pub children_by_file: BTreeMap<Option<u64>, Vec<Element>>,
 }
 
 /// Split namespace children by their decl_file values
 pub fn split_namespace_by_file(ns: &Namespace) -> NamespaceByFile {
-    let mut children_by_file: HashMap<Option<u64>, Vec<Element>> = HashMap::new();
+    let mut children_by_file: BTreeMap<Option<u64>, Vec<Element>> = BTreeMap::new();
 
     for child in &ns.children {
         let decl_file = get_element_decl_file(child);
@@ -274,13 +274,18 @@ pub fn merge_namespaces_with_config(
 ) -> (Vec<Element>, MergeStats) {
     let mut stats = MergeStats::default();
 
+    // Definitions come first, so that they win over the prototypes of the
+    // same functions
+    let mut elements = elements;
+    elements.sort_by_key(is_extern_prototype);
+
     // Separate namespaces from other elements
     let mut namespaces_by_name: BTreeMap<String, (Namespace, HashSet<String>, HashSet<String>)> =
         BTreeMap::new();
     let mut other_elements: Vec<Element> = Vec::new();
     let mut seen_keys: HashSet<String> = HashSet::new();
     // Track anonymous types separately for merging
-    let mut anonymous_types: HashMap<String, Vec<Compound>> = HashMap::new();
+    let mut anonymous_types: BTreeMap<String, Vec<Compound>> = BTreeMap::new();
 
     for element in elements {
         match element {
@@ -436,8 +441,8 @@ pub fn merge_namespaces_with_config(
 
 /// Group elements by their declaration file, properly handling namespaces
 /// by splitting their children by decl_file and wrapping them in namespace elements
-pub fn group_elements_by_file(elements: &[Element]) -> HashMap<Option<u64>, Vec<Element>> {
-    let mut elements_by_file: HashMap<Option<u64>, Vec<Element>> = HashMap::new();
+pub fn group_elements_by_file(elements: &[Element]) -> BTreeMap<Option<u64>, Vec<Element>> {
+    let mut elements_by_file: BTreeMap<Option<u64>, Vec<Element>> = BTreeMap::new();
 
     for element in elements {
         match element {
@@ -473,6 +478,43 @@ pub fn group_elements_by_file(elements: &[Element]) -> HashMap<Option<u64>, Vec<
     elements_by_file
 }
 
+/// Whether an element is the prototype of a function defined elsewhere
+pub fn is_extern_prototype(element: &Element) -> bool {
+    matches!(element, Element::Function(f) if !f.has_body && !f.is_method)
+}
+
+/// Split elements into the prototypes of functions defined elsewhere and the
+/// other elements. Namespaces are split too, keeping their prototypes wrapped.
+pub fn partition_extern_prototypes(elements: Vec<Element>) -> (Vec<Element>, Vec<Element>) {
+    let mut prototypes = Vec::new();
+    let mut others = Vec::new();
+
+    for element in elements {
+        match element {
+            Element::Namespace(ns) => {
+                let (ns_prototypes, ns_others) = partition_extern_prototypes(ns.children);
+                if !ns_prototypes.is_empty() {
+                    prototypes.push(Element::Namespace(Namespace {
+                        name: ns.name.clone(),
+                        line: ns.line,
+                        children: ns_prototypes,
+                    }));
+                }
+                if !ns_others.is_empty() {
+                    others.push(Element::Namespace(Namespace {
+                        children: ns_others,
+                        ..ns
+                    }));
+                }
+            }
+            element if is_extern_prototype(&element) => prototypes.push(element),
+            element => others.push(element),
+        }
+    }
+
+    (prototypes, others)
+}
+
 /// Wrap method definitions with namespace paths into proper Element::Namespace structures.
 /// This allows merge_namespaces() to properly merge methods from the same namespace.
 pub fn wrap_method_definitions_in_namespaces(elements: Vec<Element>) -> Vec<Element> {
@@ -567,10 +609,8 @@ pub fn normalize_path(path: &str) -> String {
             std::path::Component::Normal(c) => {
                 components.push(c.to_str().unwrap_or("unknown"));
             }
-            std::path::Component::ParentDir => {
-                if !components.is_empty() {
-                    components.pop();
-                }
+            std::path::Component::ParentDir if !components.is_empty() => {
+                components.pop();
             }
             std::path::Component::CurDir => {
                 // Skip
@@ -1,7 +1,7 @@
 //! Compound type (struct/class/union/enum) code generation
 
 use crate::types::*;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use super::function_gen::FunctionGenerator;
 use super::output::OutputWriter;
@@ -16,32 +16,67 @@ pub struct CompoundGenerator<'a> {
 }
 
 impl<'a> CompoundGenerator<'a> {
-    pub fn new(
-        output: &'a mut OutputWriter,
-        config: &'a CodeGenConfig,
-        type_sizes: &'a HashMap<String, u64>,
-    ) -> Self {
+    pub fn new(output: &'a mut OutputWriter, config: &'a CodeGenConfig) -> Self {
         Self {
             output,
-            formatter: TypeFormatter::new(config, type_sizes),
+            formatter: TypeFormatter::new(config),
             config,
         }
     }
 
     /// Generate code for a compound type (dispatches to appropriate method)
-    pub fn generate(&mut self, compound: &Compound, type_sizes: &HashMap<String, u64>) {
+    pub fn generate(&mut self, compound: &Compound) {
         // Check if we should merge typedef
         let use_typedef = compound.is_typedef && compound.typedef_name.is_some();
 
+        // Instantiations of class templates are written as explicit specializations
+        if !compound.template_params.is_empty() && !use_typedef {
+            self.output.write_line(&format!(
+                "template<> // {}",
+                self.formatter
+                    .format_template_arguments(&compound.template_params)
+            ));
+        }
+
         if compound.compound_type == "enum" {
             self.generate_enum(compound, use_typedef);
         } else if compound.compound_type == "class" {
-            self.generate_class(compound, type_sizes);
+            self.generate_class(compound);
         } else {
             self.generate_struct_or_union(compound, use_typedef);
         }
     }
 
+    /// Declare the primary template of a class template, which its explicit
+    /// specializations need, from one of its instantiations
+    pub fn generate_primary_declaration(&mut self, instantiation: &Compound) {
+        let name = instantiation.name.as_deref().unwrap_or_default();
+        self.output.write_line(&format!(
+            "template<{}> {} {};",
+            self.formatter
+                .format_template_parameters(&instantiation.template_params),
+            instantiation.compound_type,
+            template_name(name)
+        ));
+    }
+
+    /// Generate the primary template of a class template, in place of its
+    /// instantiations, which are listed above it
+    pub fn generate_primary_template(&mut self, instantiations: &[&Compound]) {
+        let names: Vec<&str> = instantiations
+            .iter()
+            .filter_map(|c| c.name.as_deref())
+            .collect();
+        self.output
+            .write_line(&format!("// Instantiations: {}", names.join(", ")));
+        self.output.write_line(&format!(
+            "template<{}>",
+            self.formatter
+                .format_template_parameters(&instantiations[0].template_params)
+        ));
+        self.generate(&primary_template(instantiations));
+    }
+
     /// Generate enum definition
     pub fn generate_enum(&mut self, compound: &Compound, use_typedef: bool) {
         let mut opening = String::new();
@@ -53,11 +88,43 @@ impl<'a> CompoundGenerator<'a> {
         opening.push_str(&compound.compound_type);
         opening.push(' ');
 
+        // C++ enums keep the underlying type DWARF gives them. In C, enums
+        // whose size the compiler wouldn't otherwise pick are packed.
+        let underlying_type = compound
+            .enum_underlying_type
+            .as_ref()
+            .map(|t| self.formatter.format_type_string(t, ""));
+        let packed_size = packed_enum_size(&compound.enum_values);
+        let unusual_size = compound
+            .byte_size
+            .filter(|&size| size != packed_size.max(4));
+        let mut fixed_type = None;
+        let mut note = None;
+        if compound.is_enum_class {
+            opening.push_str("class ");
+            fixed_type = underlying_type.filter(|t| t != "int");
+        } else if self.config.code_style == "c++" && underlying_type.is_some() {
+            fixed_type = underlying_type;
+        } else if let Some(size) = unusual_size {
+            if size == packed_size {
+                opening.push_str("__attribute__((packed)) ");
+            } else {
+                note = Some(match underlying_type {
+                    Some(t) => format!("underlying type: {}", t),
+                    None => format!("{} bytes", size),
+                });
+            }
+        }
+
         if let Some(ref name) = compound.name {
             opening.push_str(name);
             opening.push(' ');
         }
 
+        if let Some(t) = fixed_type {
+            opening.push_str(&format!(": {} ", t));
+        }
+
         opening.push('{');
 
         if let Some(line) = compound.line {
@@ -66,14 +133,19 @@ impl<'a> CompoundGenerator<'a> {
             opening.push_str(" //No line number");
         }
 
+        if let Some(note) = note {
+            opening.push_str(&format!(" // {}", note));
+        }
+
         self.output.write_line(&opening);
 
         // Enum values
         self.output.indent();
         for (name, value) in &compound.enum_values {
             if let Some(v) = value {
+                let hex = v.bits(compound.byte_size.unwrap_or(8));
                 self.output
-                    .write_line(&format!("{} = {}, // 0x{:x}", name, v, v));
+                    .write_line(&format!("{} = {}, // 0x{:x}", name, v, hex));
             } else {
                 self.output.write_line(&format!("{},", name));
             }
@@ -103,7 +175,10 @@ impl<'a> CompoundGenerator<'a> {
 
     /// Generate struct or union definition
     pub fn generate_struct_or_union(&mut self, compound: &Compound, use_typedef: bool) {
-        if compound.members.is_empty() && compound.nested_types.is_empty() {
+        if compound.members.is_empty()
+            && compound.static_members.is_empty()
+            && compound.nested_types.is_empty()
+        {
             // Empty struct/union - just output typedef or declaration
             // Skip unnamed forward declarations as they generate invalid C code like "struct;"
             if compound.name.is_none() && compound.typedef_name.is_none() {
@@ -153,6 +228,7 @@ impl<'a> CompoundGenerator<'a> {
             }
 
             opening.push_str(&compound.compound_type);
+            self.push_layout_attributes(&mut opening, compound);
 
             if let Some(ref name) = compound.name {
                 opening.push(' ');
@@ -184,13 +260,15 @@ impl<'a> CompoundGenerator<'a> {
 
             // Generate nested types first (they're typically declared at the top)
             for nested in &compound.nested_types {
-                self.generate(nested, &HashMap::new());
+                self.generate(nested);
                 self.output.push_newline();
             }
 
             // Members grouped by line
             let member_refs: Vec<_> = compound.members.iter().collect();
             self.generate_members(&member_refs);
+            let static_refs: Vec<_> = compound.static_members.iter().collect();
+            self.generate_static_members(&static_refs);
             self.output.dedent();
 
             let mut closing = String::from("}");
@@ -216,9 +294,10 @@ impl<'a> CompoundGenerator<'a> {
     }
 
     /// Generate class definition
-    pub fn generate_class(&mut self, compound: &Compound, type_sizes: &HashMap<String, u64>) {
+    pub fn generate_class(&mut self, compound: &Compound) {
         // Check if this is a forward declaration (no members, no methods, no base classes, no nested types)
         let is_forward_decl = compound.members.is_empty()
+            && compound.static_members.is_empty()
             && compound.methods.is_empty()
             && compound.base_classes.is_empty()
             && compound.nested_types.is_empty();
@@ -240,10 +319,10 @@ impl<'a> CompoundGenerator<'a> {
             return;
         }
 
-        let mut opening = format!(
-            "class {}",
-            compound.name.as_ref().unwrap_or(&String::from("unnamed"))
-        );
+        let mut opening = String::from("class");
+        self.push_layout_attributes(&mut opening, compound);
+        opening.push(' ');
+        opening.push_str(compound.name.as_deref().unwrap_or("unnamed"));
 
         // Add base classes if any
         if !compound.base_classes.is_empty() {
@@ -271,62 +350,32 @@ impl<'a> CompoundGenerator<'a> {
         if !compound.nested_types.is_empty() {
             self.output.indent();
             for nested in &compound.nested_types {
-                self.generate(nested, type_sizes);
+                self.generate(nested);
                 self.output.push_newline();
             }
             self.output.dedent();
         }
 
         // Group members and methods by accessibility
-        let mut public_members: Vec<&Variable> = Vec::new();
-        let mut protected_members: Vec<&Variable> = Vec::new();
-        let mut private_members: Vec<&Variable> = Vec::new();
-
-        for member in &compound.members {
-            match member.accessibility.as_deref() {
-                Some("protected") => protected_members.push(member),
-                Some("public") => public_members.push(member),
-                Some("private") => private_members.push(member),
-                None | Some(_) => public_members.push(member),
-            }
-        }
-
-        let mut public_methods: Vec<&Function> = Vec::new();
-        let mut protected_methods: Vec<&Function> = Vec::new();
-        let mut private_methods: Vec<&Function> = Vec::new();
-
-        for method in &compound.methods {
-            match method.accessibility.as_deref() {
-                Some("protected") => protected_methods.push(method),
-                Some("public") => public_methods.push(method),
-                Some("private") => private_methods.push(method),
-                None | Some(_) => public_methods.push(method),
-            }
-        }
+        let members = by_accessibility(&compound.members, |m| m.accessibility.as_deref());
+        let static_members =
+            by_accessibility(&compound.static_members, |m| m.accessibility.as_deref());
+        let methods = by_accessibility(&compound.methods, |m| m.accessibility.as_deref());
 
         // Write sections - access specifiers at same indent level as class
         // Order: public, protected, private (conventional C++ style)
-        if !public_members.is_empty() || !public_methods.is_empty() {
-            self.output.write_line("public:");
-            self.output.indent();
-            self.generate_members(public_members.to_vec().as_slice());
-            self.generate_methods(&public_methods, type_sizes);
-            self.output.dedent();
-        }
-
-        if !protected_members.is_empty() || !protected_methods.is_empty() {
-            self.output.write_line("protected:");
-            self.output.indent();
-            self.generate_members(protected_members.to_vec().as_slice());
-            self.generate_methods(&protected_methods, type_sizes);
-            self.output.dedent();
-        }
-
-        if !private_members.is_empty() || !private_methods.is_empty() {
-            self.output.write_line("private:");
+        for (i, label) in ["public:", "protected:", "private:"]
+            .into_iter()
+            .enumerate()
+        {
+            if members[i].is_empty() && static_members[i].is_empty() && methods[i].is_empty() {
+                continue;
+            }
+            self.output.write_line(label);
             self.output.indent();
-            self.generate_members(private_members.to_vec().as_slice());
-            self.generate_methods(&private_methods, type_sizes);
+            self.generate_members(&members[i]);
+            self.generate_static_members(&static_members[i]);
+            self.generate_methods(&methods[i]);
             self.output.dedent();
         }
 
@@ -371,18 +420,47 @@ impl<'a> CompoundGenerator<'a> {
         base_str
     }
 
+    /// Add the attributes keeping a compound type's layout after its keyword
+    fn push_layout_attributes(&self, opening: &mut String, compound: &Compound) {
+        if let Some(attributes) = self
+            .formatter
+            .layout_attributes(compound.is_packed, compound.alignment)
+        {
+            opening.push(' ');
+            opening.push_str(&attributes);
+        }
+    }
+
     /// Generate methods sorted by line number
-    fn generate_methods(&mut self, methods: &[&Function], type_sizes: &HashMap<String, u64>) {
+    fn generate_methods(&mut self, methods: &[&Function]) {
         let mut sorted_methods: Vec<(usize, &Function)> =
             methods.iter().enumerate().map(|(i, &f)| (i, f)).collect();
         sorted_methods.sort_by_key(|(idx, m)| (m.line, *idx));
 
         for (_, method) in sorted_methods {
-            let mut func_gen = FunctionGenerator::new(self.output, self.config, type_sizes);
+            let mut func_gen = FunctionGenerator::new(self.output, self.config);
             func_gen.generate_method(method);
         }
     }
 
+    /// Generate static data member declarations, in line order. They take no
+    /// space in instances, so have no offset.
+    fn generate_static_members(&mut self, members: &[&Variable]) {
+        let mut sorted: Vec<&Variable> = members.to_vec();
+        sorted.sort_by_key(|m| m.line);
+
+        for member in sorted {
+            let decl = format!(
+                "static {};",
+                self.formatter.format_member_declaration(member)
+            );
+            match member.line {
+                Some(line) => self.output.write_line_comment(&decl, &line.to_string()),
+                None => self.output.write_line(&decl),
+            }
+        }
+    }
+
     /// Generate member declarations
     pub fn generate_members(&mut self, members: &[&Variable]) {
         // Check if we have offset information for members
@@ -397,8 +475,8 @@ impl<'a> CompoundGenerator<'a> {
 
     /// Generate members with offset information
     fn generate_members_with_offsets(&mut self, members: &[&Variable]) {
-        // Group members by line number, sorting by offset within each line
-        let mut lines: HashMap<Option<u64>, Vec<(&Variable, u64)>> = HashMap::new();
+        // Group members by line number, sorting by position within each line
+        let mut lines: BTreeMap<Option<u64>, Vec<(&Variable, u64)>> = BTreeMap::new();
         let mut no_offset_vars = Vec::new();
 
         for member in members {
@@ -409,29 +487,34 @@ impl<'a> CompoundGenerator<'a> {
             }
         }
 
-        // Sort each line's members by offset
+        // Sort each line's members by position
         for vars in lines.values_mut() {
-            vars.sort_by_key(|(_, offset)| *offset);
+            vars.sort_by_key(|(var, offset)| bit_position(var, *offset));
         }
 
-        // Sort lines by their first member's offset (for padding detection)
+        // Sort lines by their first member's position (for padding detection).
+        // Lines starting at the same position, as in unions, stay in line order.
         let mut sorted_lines: Vec<_> = lines.iter().collect();
-        sorted_lines.sort_by_key(|(_, vars)| vars.first().map(|(_, o)| *o).unwrap_or(0));
+        sorted_lines.sort_by_key(|(_, vars)| {
+            vars.first()
+                .map(|(var, offset)| bit_position(var, *offset))
+                .unwrap_or(0)
+        });
 
-        let mut prev_end_offset: Option<u64> = None;
+        // End of the previous member, in bits. Padding after a member of an
+        // incomplete type can't be known.
+        let mut prev_end_bit: Option<u64> = None;
 
         for (line, vars) in sorted_lines {
             if vars.is_empty() {
                 continue;
             }
 
-            let first_offset = vars[0].1;
-            let last_var = vars.last().unwrap().0;
-            let last_offset = vars.last().unwrap().1;
-            let last_member_size = self.formatter.estimate_type_size(&last_var.type_info);
-
-            // Detect padding before this group
-            if let Some(prev_end) = prev_end_offset {
+            // Detect padding before this group. Gaps before bitfields are
+            // filled with unnamed bitfields instead.
+            let (first_var, first_offset) = vars[0];
+            if let (Some(prev_end), None) = (prev_end_bit, first_var.bit_size) {
+                let prev_end = prev_end.div_ceil(8);
                 if first_offset > prev_end {
                     let padding_bytes = first_offset - prev_end;
                     self.output.write_line(&format!(
@@ -442,92 +525,85 @@ impl<'a> CompoundGenerator<'a> {
                 }
             }
 
+            // The unnamed bitfields needed before each member
+            let mut paddings = Vec::new();
+            for (var, offset) in vars {
+                let position = bit_position(var, *offset);
+                let type_size = self.formatter.type_size(&var.type_info);
+                let padding = match (var.bit_size, prev_end_bit, type_size) {
+                    (Some(bit_size), Some(prev_end), Some(unit_size)) => {
+                        bitfield_padding(prev_end, position, bit_size, unit_size)
+                    }
+                    _ => Vec::new(),
+                };
+                paddings.push(padding);
+                prev_end_bit = var
+                    .bit_size
+                    .or(type_size.map(|size| size * 8))
+                    .map(|bits| position + bits);
+            }
+
             // Group by type compatibility within this line
-            let mut type_groups: Vec<Vec<(&Variable, u64)>> = Vec::new();
+            let mut type_groups: Vec<Vec<usize>> = Vec::new();
 
-            for (var, offset) in vars {
+            for (i, (var, _)) in vars.iter().enumerate() {
                 // Bitfields can't be grouped with other variables
                 if var.bit_size.is_some() {
-                    type_groups.push(vec![(var, *offset)]);
+                    type_groups.push(vec![i]);
                     continue;
                 }
 
                 let mut added = false;
                 for group in &mut type_groups {
+                    let first = vars[group[0]].0;
                     // Don't group with bitfields
-                    if group[0].0.bit_size.is_some() {
+                    if first.bit_size.is_some() {
                         continue;
                     }
-                    if self
-                        .formatter
-                        .types_compatible(&group[0].0.type_info, &var.type_info)
-                    {
-                        group.push((var, *offset));
+                    if self.formatter.variables_compatible(first, var) {
+                        group.push(i);
                         added = true;
                         break;
                     }
                 }
                 if !added {
-                    type_groups.push(vec![(var, *offset)]);
+                    type_groups.push(vec![i]);
                 }
             }
 
-            // Generate declarations for this line
-            let mut decls = Vec::new();
-
+            // Output the declarations of this line
             for group in &type_groups {
-                // Check if this group contains function pointers or bitfields
-                if group[0].0.type_info.is_function_pointer || group[0].0.bit_size.is_some() {
-                    // Output individually with bitfield info
-                    for (var, offset) in group {
-                        let mut decl = self.formatter.format_member_declaration(var);
-                        if let (Some(_bit_size), Some(bit_offset)) = (var.bit_size, var.bit_offset)
+                let (first, first_offset) = vars[group[0]];
+                // Function pointers and bitfields are output individually
+                if first.type_info.contains_function() || first.bit_size.is_some() {
+                    for &i in group {
+                        let (var, offset) = vars[i];
+                        let unit_size = self.formatter.type_size(&var.type_info);
+                        for width in &paddings[i] {
+                            self.output.write_line(&format!(
+                                "{} : {};",
+                                self.padding_bitfield_type(var, unit_size),
+                                width
+                            ));
+                        }
+                        let decl = self.formatter.format_member_declaration(var);
+                        let mut full_decl = self.member_line(decl, *line, offset, var.bit_offset);
+                        if let (Some(position), Some(bit_size), Some(unit_size)) =
+                            (var.bit_offset, var.bit_size, unit_size)
                         {
-                            decl.push_str(&format!(" [bit offset: {}]", bit_offset));
+                            if straddles_storage_unit(position, bit_size, unit_size) {
+                                full_decl.push_str(" [straddles storage unit]");
+                            }
                         }
-                        decls.push((decl, Some(*offset)));
+                        self.output.write_line(&full_decl);
                     }
                 } else {
-                    let base_type = &group[0].0.type_info;
-                    let mut var_names = Vec::new();
-
-                    for (var, _) in group {
-                        let ptr_str = "*".repeat(var.type_info.pointer_count);
-                        let mut name_with_array = format!("{}{}", ptr_str, var.name);
-
-                        for size in &var.type_info.array_sizes {
-                            name_with_array.push_str(&format!("[{}]", size));
-                        }
-
-                        var_names.push(name_with_array);
-                    }
-
-                    let transformed_base = self.formatter.transform_type_name(&base_type.base_type);
-                    let mut decl = transformed_base;
-                    decl.push(' ');
-                    decl.push_str(&var_names.join(", "));
-                    decls.push((decl, Some(group[0].1)));
-                }
-            }
-
-            // Output all declarations for this line
-            for (decl, offset) in decls {
-                let mut full_decl = decl;
-                full_decl.push(';');
-
-                if let Some(l) = line {
-                    full_decl.push_str(&format!(" //{}", l));
-                }
-                if !self.config.no_offsets {
-                    if let Some(o) = offset {
-                        full_decl.push_str(&format!(" @ offset {}", o));
-                    }
+                    let group_vars: Vec<&Variable> = group.iter().map(|&i| vars[i].0).collect();
+                    let decl = self.formatter.format_grouped_declaration(&group_vars);
+                    let full_decl = self.member_line(decl, *line, first_offset, None);
+                    self.output.write_line(&full_decl);
                 }
-                self.output.write_line(&full_decl);
             }
-
-            // Update prev_end_offset based on last member in this line group
-            prev_end_offset = Some(last_offset + last_member_size);
         }
 
         // Output members without offsets at the end
@@ -542,9 +618,45 @@ impl<'a> CompoundGenerator<'a> {
         }
     }
 
+    /// A member declaration with its line and its byte offset, or the bit
+    /// position of a bitfield
+    fn member_line(
+        &self,
+        decl: String,
+        line: Option<u64>,
+        offset: u64,
+        bit_position: Option<u64>,
+    ) -> String {
+        let mut full_decl = decl;
+        full_decl.push(';');
+
+        if let Some(l) = line {
+            full_decl.push_str(&format!(" //{}", l));
+        }
+        if !self.config.no_offsets {
+            match bit_position {
+                Some(position) => full_decl.push_str(&format!(" @ bit offset {}", position)),
+                None => full_decl.push_str(&format!(" @ offset {}", offset)),
+            }
+        }
+        full_decl
+    }
+
+    /// The type of an unnamed bitfield padding up to `var`: an unsigned type
+    /// with the same storage unit
+    fn padding_bitfield_type(&self, var: &Variable, unit_size: Option<u64>) -> String {
+        match unit_size {
+            Some(1) => "unsigned char".to_string(),
+            Some(2) => "unsigned short".to_string(),
+            Some(4) => "unsigned int".to_string(),
+            Some(8) => "unsigned long long".to_string(),
+            _ => self.formatter.format_type_string(&var.type_info, ""),
+        }
+    }
+
     /// Generate members without offset information (line-based grouping)
     fn generate_members_without_offsets(&mut self, members: &[&Variable]) {
-        let mut lines: HashMap<u64, Vec<&Variable>> = HashMap::new();
+        let mut lines: BTreeMap<u64, Vec<&Variable>> = BTreeMap::new();
         let mut no_line_vars = Vec::new();
 
         for member in members {
@@ -555,11 +667,8 @@ impl<'a> CompoundGenerator<'a> {
             }
         }
 
-        // Generate grouped members
-        let mut sorted_lines: Vec<_> = lines.iter().collect();
-        sorted_lines.sort_by_key(|(line, _)| *line);
-
-        for (line, vars) in sorted_lines {
+        // Generate grouped members, in line order
+        for (line, vars) in &lines {
             // Group by type compatibility
             let mut type_groups: Vec<Vec<&Variable>> = Vec::new();
 
@@ -576,10 +685,7 @@ impl<'a> CompoundGenerator<'a> {
                     if group[0].bit_size.is_some() {
                         continue;
                     }
-                    if self
-                        .formatter
-                        .types_compatible(&group[0].type_info, &var.type_info)
-                    {
+                    if self.formatter.variables_compatible(group[0], var) {
                         group.push(var);
                         added = true;
                         break;
@@ -593,33 +699,19 @@ impl<'a> CompoundGenerator<'a> {
             // Generate declarations
             let mut decls = Vec::new();
             for group in type_groups {
-                // Check if this group contains function pointers or bitfields - they can't be grouped
-                if group[0].type_info.is_function_pointer || group[0].bit_size.is_some() {
+                // Check if this group contains function pointers, bitfields or
+                // members noting their type - they can't be grouped
+                if group[0].type_info.contains_function()
+                    || group[0].bit_size.is_some()
+                    || group[0].instantiation.is_some()
+                {
                     // Output individually
                     for var in group {
                         let decl = self.formatter.format_member_declaration(var);
                         decls.push(decl);
                     }
                 } else {
-                    let base_type = &group[0].type_info;
-                    let mut var_names = Vec::new();
-
-                    for var in group {
-                        let ptr_str = "*".repeat(var.type_info.pointer_count);
-                        let mut name_with_array = format!("{}{}", ptr_str, var.name);
-
-                        for size in &var.type_info.array_sizes {
-                            name_with_array.push_str(&format!("[{}]", size));
-                        }
-
-                        var_names.push(name_with_array);
-                    }
-
-                    let transformed_base = self.formatter.transform_type_name(&base_type.base_type);
-                    let mut decl = transformed_base;
-                    decl.push(' ');
-                    decl.push_str(&var_names.join(", "));
-                    decls.push(decl);
+                    decls.push(self.formatter.format_grouped_declaration(&group));
                 }
             }
 
@@ -635,3 +727,239 @@ impl<'a> CompoundGenerator<'a> {
         }
     }
 }
+
+/// Split the members or methods of a class into its public, protected and
+/// private sections. Those without an accessibility are public.
+fn by_accessibility<T>(items: &[T], accessibility: impl Fn(&T) -> Option<&str>) -> [Vec<&T>; 3] {
+    let mut sections = [Vec::new(), Vec::new(), Vec::new()];
+    for item in items {
+        let section = match accessibility(item) {
+            Some("protected") => 1,
+            Some("private") => 2,
+            _ => 0,
+        };
+        sections[section].push(item);
+    }
+    sections
+}
+
+/// The position of a member from the start of its struct, in bits
+fn bit_position(var: &Variable, offset: u64) -> u64 {
+    var.bit_offset.unwrap_or(offset * 8)
+}
+
+/// The widths of the unnamed bitfields needed to place a bitfield at
+/// `position` after members ending at bit `prev_end`. Without them a compiler
+/// puts it at `prev_end`, or at the next storage unit if it doesn't fit there.
+/// A width of 0 moves to the next storage unit.
+fn bitfield_padding(prev_end: u64, position: u64, bit_size: u64, unit_size: u64) -> Vec<u64> {
+    let unit_bits = unit_size * 8;
+    if unit_bits == 0 {
+        return Vec::new();
+    }
+    let natural = if straddles_storage_unit(prev_end, bit_size, unit_size) {
+        prev_end.next_multiple_of(unit_bits)
+    } else {
+        prev_end
+    };
+    if position <= natural {
+        return Vec::new();
+    }
+
+    let mut widths = Vec::new();
+    let mut end = prev_end;
+    let unit_start = position - position % unit_bits;
+    if end < unit_start && !end.is_multiple_of(unit_bits) {
+        widths.push(0);
+        end = end.next_multiple_of(unit_bits);
+    }
+    while end < unit_start {
+        widths.push(unit_bits);
+        end += unit_bits;
+    }
+    if position > end {
+        widths.push(position - end);
+    }
+    widths
+}
+
+/// The size a compiler gives a packed enum with these values: the smallest
+/// integer type holding all of them
+fn packed_enum_size(values: &[(String, Option<ConstValue>)]) -> u64 {
+    let values: Vec<i128> = values
+        .iter()
+        .filter_map(|(_, value)| match *value {
+            Some(ConstValue::Signed(v)) => Some(v as i128),
+            Some(ConstValue::Unsigned(v)) => Some(v as i128),
+            None => None,
+        })
+        .collect();
+    let has_negative = values.iter().any(|&v| v < 0);
+    [1, 2, 4]
+        .into_iter()
+        .find(|&bytes| {
+            let bits = bytes * 8;
+            values.iter().all(|&v| {
+                if has_negative {
+                    (-(1i128 << (bits - 1))..(1i128 << (bits - 1))).contains(&v)
+                } else {
+                    v < (1i128 << bits)
+                }
+            })
+        })
+        .unwrap_or(8)
+}
+
+/// Build the primary template of a class template from its instantiations.
+/// A type is written in terms of the template's parameters only if it
+/// follows them in every instantiation; otherwise the first instantiation's
+/// type is kept, and members note it. Offsets and sizes are left out, as
+/// they are those of one instantiation.
+fn primary_template(instantiations: &[&Compound]) -> Compound {
+    let mut primary = instantiations[0].clone();
+    let first_instantiation = primary.name.clone();
+    primary.name = primary
+        .name
+        .as_deref()
+        .map(|name| template_name(name).to_string());
+    primary.template_params = Vec::new();
+    primary.byte_size = None;
+    for base in &mut primary.base_classes {
+        base.offset = None;
+    }
+    for member in &mut primary.members {
+        member.offset = None;
+        member.bit_offset = None;
+    }
+
+    fn generalize_members(
+        members: &mut [Variable],
+        instantiations: &[&Compound],
+        first_instantiation: &Option<String>,
+        members_of: fn(&Compound) -> &[Variable],
+    ) {
+        for member in members {
+            let matches: Vec<(&Compound, &Variable)> = instantiations
+                .iter()
+                .filter_map(|&c| {
+                    members_of(c)
+                        .iter()
+                        .find(|m| m.name == member.name)
+                        .map(|m| (c, m))
+                })
+                .collect();
+            if let Some(type_info) = generalize(&matches, |m| Some(&m.type_info)) {
+                member.type_info = type_info;
+            } else if matches.iter().any(|(_, m)| m.type_info != member.type_info) {
+                member.instantiation = first_instantiation.clone();
+            }
+            // A static member's value may depend on the template arguments
+            if matches
+                .iter()
+                .any(|(_, m)| m.const_value != member.const_value)
+            {
+                member.const_value = None;
+            }
+        }
+    }
+    generalize_members(
+        &mut primary.members,
+        instantiations,
+        &first_instantiation,
+        |c| &c.members,
+    );
+    generalize_members(
+        &mut primary.static_members,
+        instantiations,
+        &first_instantiation,
+        |c| &c.static_members,
+    );
+
+    for method in &mut primary.methods {
+        method.class_name = primary.name.clone();
+        let matches: Vec<(&Compound, &Function)> = instantiations
+            .iter()
+            .filter_map(|&c| {
+                c.methods
+                    .iter()
+                    .find(|m| {
+                        m.name == method.name && m.parameters.len() == method.parameters.len()
+                    })
+                    .map(|m| (c, m))
+            })
+            .collect();
+        if let Some(return_type) = generalize(&matches, |m| Some(&m.return_type)) {
+            method.return_type = return_type;
+        }
+        for (i, param) in method.parameters.iter_mut().enumerate() {
+            if let Some(type_info) =
+                generalize(&matches, |m| m.parameters.get(i).map(|p| &p.type_info))
+            {
+                param.type_info = type_info;
+            }
+        }
+    }
+
+    primary
+}
+
+/// The type `get` finds in each instantiation, with the arguments of the
+/// instantiation's type parameters, and array bounds equal to those of its
+/// value parameters, replaced by the parameters, if the type varies between
+/// instantiations and that gives the same type for all of them
+fn generalize<T>(
+    matches: &[(&Compound, T)],
+    get: impl Fn(&T) -> Option<&TypeInfo>,
+) -> Option<TypeInfo> {
+    let mut varies = false;
+    let mut first: Option<(&TypeInfo, TypeInfo)> = None;
+    for (instantiation, item) in matches {
+        let type_info = get(item)?;
+        let params = &instantiation.template_params;
+        let generalized = type_info.map(
+            &|name| {
+                params
+                    .iter()
+                    .find_map(|param| match (&param.name, &param.argument) {
+                        (Some(param_name), TemplateArgument::Type(argument))
+                            if argument.qualifiers.is_empty()
+                                && argument.kind == TypeKind::Named(name.to_string()) =>
+                        {
+                            Some(param_name.clone())
+                        }
+                        _ => None,
+                    })
+                    .unwrap_or_else(|| name.to_string())
+            },
+            &|bound| {
+                let ArrayBound::Fixed { count, lower: 0 } = *bound else {
+                    return bound.clone();
+                };
+                params
+                    .iter()
+                    .find_map(|param| match (&param.name, &param.argument) {
+                        (
+                            Some(param_name),
+                            TemplateArgument::Value {
+                                value: Some(value), ..
+                            },
+                        ) if value.bits(8) == count => {
+                            Some(ArrayBound::TemplateParameter(param_name.clone()))
+                        }
+                        _ => None,
+                    })
+                    .unwrap_or_else(|| bound.clone())
+            },
+        );
+        match &first {
+            Some((first_type, first_generalized)) => {
+                if *first_generalized != generalized {
+                    return None;
+                }
+                varies |= *first_type != type_info;
+            }
+            None => first = Some((type_info, generalized)),
+        }
+    }
+    first.filter(|_| varies).map(|(_, generalized)| generalized)
+}
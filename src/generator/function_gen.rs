@@ -2,12 +2,17 @@
 
 use crate::types::*;
 use cpp_demangle::Symbol;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, VecDeque};
 
+use super::compound_gen::CompoundGenerator;
 use super::output::OutputWriter;
 use super::type_formatter::TypeFormatter;
 use super::CodeGenConfig;
 
+/// Where an element of a body goes: its line, then types before the rest,
+/// then its index
+type BodyOrder = (Option<u64>, u8, usize);
+
 /// Generates code for functions and methods
 pub struct FunctionGenerator<'a> {
     output: &'a mut OutputWriter,
@@ -16,14 +21,10 @@ pub struct FunctionGenerator<'a> {
 }
 
 impl<'a> FunctionGenerator<'a> {
-    pub fn new(
-        output: &'a mut OutputWriter,
-        config: &'a CodeGenConfig,
-        type_sizes: &'a HashMap<String, u64>,
-    ) -> Self {
+    pub fn new(output: &'a mut OutputWriter, config: &'a CodeGenConfig) -> Self {
         Self {
             output,
-            formatter: TypeFormatter::new(config, type_sizes),
+            formatter: TypeFormatter::new(config),
             config,
         }
     }
@@ -32,6 +33,50 @@ impl<'a> FunctionGenerator<'a> {
     pub fn generate_method(&mut self, func: &Function) {
         // Inside a class definition, always output declaration-only format
         let decl = self.generate_method_declaration(func);
+        self.generate_template_header(func);
+        self.output.write_line(&decl);
+    }
+
+    /// Write `template<>` before an instantiation of a function template
+    fn generate_template_header(&mut self, func: &Function) {
+        if !func.template_params.is_empty() {
+            self.output.write_line(&format!(
+                "template<> // {}",
+                self.formatter
+                    .format_template_arguments(&func.template_params)
+            ));
+        }
+    }
+
+    /// Declare the primary template that an instantiation of a function
+    /// template specializes, with the instantiation's signature so they match
+    fn generate_primary_declaration(&mut self, func: &Function) {
+        if func.template_params.is_empty() || func.is_method {
+            return;
+        }
+
+        let mut decl = format!(
+            "template<{}> ",
+            self.formatter
+                .format_template_parameters(&func.template_params)
+        );
+        if !func.is_external {
+            decl.push_str("static ");
+        }
+        let (prefix, suffix) = self.formatter.format_return_type(&func.return_type);
+        decl.push_str(&prefix);
+        decl.push_str(template_name(&func.name));
+        decl.push('(');
+        let params: Vec<String> = func
+            .parameters
+            .iter()
+            .filter(|p| !(p.name.starts_with("__") && p.line.is_none()))
+            .map(|p| self.formatter.format_type_string(&p.type_info, ""))
+            .collect();
+        decl.push_str(&params.join(", "));
+        decl.push_str(parameter_list_end(func, !params.is_empty()));
+        decl.push_str(&suffix);
+        decl.push(';');
         self.output.write_line(&decl);
     }
 
@@ -40,7 +85,7 @@ impl<'a> FunctionGenerator<'a> {
         let mut decl = String::new();
 
         // Detect constructor: name matches class name
-        let is_constructor = func.class_name.as_ref() == Some(&func.name);
+        let is_constructor = func.class_name.as_deref().map(template_name) == Some(&func.name);
 
         // Virtual keyword
         if func.is_virtual {
@@ -48,20 +93,11 @@ impl<'a> FunctionGenerator<'a> {
         }
 
         // Return type (skip for constructors/destructors, apply type transformations)
+        let mut return_suffix = String::new();
         if !is_constructor && !func.is_destructor {
-            decl.push_str(
-                &self
-                    .formatter
-                    .transform_type_name(&func.return_type.base_type),
-            );
-            if func.return_type.is_rvalue_reference {
-                decl.push_str("&&");
-            } else if func.return_type.is_reference {
-                decl.push('&');
-            } else if func.return_type.pointer_count > 0 {
-                decl.push_str(&"*".repeat(func.return_type.pointer_count));
-            }
-            decl.push(' ');
+            let (prefix, suffix) = self.formatter.format_return_type(&func.return_type);
+            decl.push_str(&prefix);
+            return_suffix = suffix;
         }
 
         // Function name
@@ -95,7 +131,9 @@ impl<'a> FunctionGenerator<'a> {
                     .format_type_string(&param.type_info, &param.name),
             );
         }
-        decl.push_str(");");
+        decl.push_str(parameter_list_end(func, !params.is_empty()));
+        decl.push_str(&return_suffix);
+        decl.push(';');
 
         // Add line comment after semicolon
         if let Some(line) = func.line {
@@ -187,6 +225,8 @@ impl<'a> FunctionGenerator<'a> {
 
     /// Generate function implementation
     fn generate_function_impl(&mut self, func: &Function) {
+        self.generate_primary_declaration(func);
+
         // Write address comment above function if available and not disabled
         if !self.config.no_function_addresses {
             if let (Some(low), Some(high)) = (func.low_pc, func.high_pc) {
@@ -197,12 +237,14 @@ impl<'a> FunctionGenerator<'a> {
         }
 
         let decl = self.generate_function_declaration(func);
+        self.generate_template_header(func);
 
         if !func.has_body
             || (func.variables.is_empty()
                 && func.lexical_blocks.is_empty()
                 && func.inlined_calls.is_empty()
-                && func.labels.is_empty())
+                && func.labels.is_empty()
+                && func.local_types.is_empty())
         {
             // Function declaration only - add semicolon before line comment
             let decl_with_semicolon = self.insert_semicolon_before_comment(&decl);
@@ -213,7 +255,8 @@ impl<'a> FunctionGenerator<'a> {
             // Check if we have a single lexical block at top level with no other content
             let single_block_only = func.lexical_blocks.len() == 1
                 && func.variables.is_empty()
-                && func.inlined_calls.is_empty();
+                && func.inlined_calls.is_empty()
+                && func.local_types.is_empty();
 
             if single_block_only {
                 // Use function braces to contain the block's content
@@ -267,23 +310,15 @@ impl<'a> FunctionGenerator<'a> {
         }
 
         // Detect constructor: name matches class name
-        let is_constructor = func.class_name.as_ref() == Some(&func.name);
+        let is_constructor = func.class_name.as_deref().map(template_name) == Some(&func.name);
 
-        // Return type (skip for constructors/destructors)
+        // Return type (skip for constructors/destructors). Return types such
+        // as function pointers continue after the parameter list.
+        let mut return_suffix = String::new();
         if !is_constructor && !func.is_destructor {
-            decl.push_str(
-                &self
-                    .formatter
-                    .transform_type_name(&func.return_type.base_type),
-            );
-            if func.return_type.is_rvalue_reference {
-                decl.push_str("&&");
-            } else if func.return_type.is_reference {
-                decl.push('&');
-            } else if func.return_type.pointer_count > 0 {
-                decl.push_str(&"*".repeat(func.return_type.pointer_count));
-            }
-            decl.push(' ');
+            let (prefix, suffix) = self.formatter.format_return_type(&func.return_type);
+            decl.push_str(&prefix);
+            return_suffix = suffix;
         }
 
         // Function name (with class prefix for method definitions)
@@ -312,7 +347,8 @@ impl<'a> FunctionGenerator<'a> {
             .collect();
 
         if params.is_empty() {
-            decl.push(')');
+            decl.push_str(parameter_list_end(func, false));
+            decl.push_str(&return_suffix);
             if let Some(line) = func.line {
                 decl.push_str(&format!(" //{}", line));
             }
@@ -325,8 +361,11 @@ impl<'a> FunctionGenerator<'a> {
                 decl.push_str(&metadata);
             }
         } else {
-            // Check if all params are on same line as function
-            let all_same_line = params.iter().all(|p| p.line == func.line);
+            // Check if all params are on same line as function. Those of
+            // prototypes have no line of their own.
+            let all_same_line = params
+                .iter()
+                .all(|p| p.line == func.line || (p.line.is_none() && !func.has_body));
 
             if all_same_line {
                 for (i, param) in params.iter().enumerate() {
@@ -339,7 +378,8 @@ impl<'a> FunctionGenerator<'a> {
                             .format_type_string(&param.type_info, &param.name),
                     );
                 }
-                decl.push(')');
+                decl.push_str(parameter_list_end(func, true));
+                decl.push_str(&return_suffix);
                 if let Some(line) = func.line {
                     decl.push_str(&format!(" //{}", line));
                 }
@@ -353,13 +393,12 @@ impl<'a> FunctionGenerator<'a> {
                 }
             } else {
                 // Parameters on different lines - group by line
-                let mut param_lines: HashMap<Option<u64>, Vec<&Parameter>> = HashMap::new();
+                let mut param_lines: BTreeMap<Option<u64>, Vec<&Parameter>> = BTreeMap::new();
                 for param in &params {
                     param_lines.entry(param.line).or_default().push(param);
                 }
 
-                let mut sorted_lines: Vec<_> = param_lines.iter().collect();
-                sorted_lines.sort_by_key(|(line, _)| *line);
+                let sorted_lines: Vec<_> = param_lines.iter().collect();
 
                 for (idx, (line, params_at_line)) in sorted_lines.iter().enumerate() {
                     if idx == 0 {
@@ -376,7 +415,8 @@ impl<'a> FunctionGenerator<'a> {
                         }
 
                         if sorted_lines.len() == 1 {
-                            decl.push(')');
+                            decl.push_str(parameter_list_end(func, true));
+                            decl.push_str(&return_suffix);
                         } else {
                             decl.push(',');
                         }
@@ -404,7 +444,8 @@ impl<'a> FunctionGenerator<'a> {
                         }
 
                         if idx == sorted_lines.len() - 1 {
-                            decl.push(')');
+                            decl.push_str(parameter_list_end(func, true));
+                            decl.push_str(&return_suffix);
                         } else {
                             decl.push(',');
                         }
@@ -462,6 +503,7 @@ impl<'a> FunctionGenerator<'a> {
             Variable(&'a Variable, usize),
             InlinedCall(&'a InlinedSubroutine, usize),
             LexicalBlock(&'a LexicalBlock, usize),
+            LocalType(&'a Element, usize),
         }
 
         let mut elements: Vec<BodyElement> = Vec::new();
@@ -475,15 +517,20 @@ impl<'a> FunctionGenerator<'a> {
         for (idx, block) in func.lexical_blocks.iter().enumerate() {
             elements.push(BodyElement::LexicalBlock(block, idx));
         }
+        for (idx, local_type) in func.local_types.iter().enumerate() {
+            elements.push(BodyElement::LocalType(local_type, idx));
+        }
 
-        // Sort by line number, then by original index for stable sort
-        let mut keyed_elements: Vec<((Option<u64>, usize), BodyElement)> = elements
+        // Sort by line number, then by original index for stable sort. Types
+        // come first on their line, so `struct s { ... } v;` declares s before v
+        let mut keyed_elements: Vec<(BodyOrder, BodyElement)> = elements
             .into_iter()
             .map(|elem| {
                 let key = match &elem {
-                    BodyElement::Variable(v, idx) => (v.line, *idx),
-                    BodyElement::InlinedCall(i, idx) => (i.line, *idx),
-                    BodyElement::LexicalBlock(bl, idx) => (bl.min_content_line(), *idx),
+                    BodyElement::Variable(v, idx) => (v.line, 1, *idx),
+                    BodyElement::InlinedCall(i, idx) => (i.line, 1, *idx),
+                    BodyElement::LexicalBlock(bl, idx) => (bl.min_content_line(), 1, *idx),
+                    BodyElement::LocalType(t, idx) => (t.line(), 0, *idx),
                 };
                 (key, elem)
             })
@@ -545,6 +592,18 @@ impl<'a> FunctionGenerator<'a> {
                     }
                     self.generate_lexical_block_with_labels(block, &mut pending_labels);
                 }
+                BodyElement::LocalType(local_type, _) => {
+                    self.output_pending_labels_before(&mut pending_labels, local_type.line());
+                    if !variables_buffer.is_empty() {
+                        self.generate_variables(&variables_buffer);
+                        variables_buffer.clear();
+                    }
+                    if !inlined_buffer.is_empty() {
+                        self.generate_inlined_calls(&inlined_buffer);
+                        inlined_buffer.clear();
+                    }
+                    self.generate_local_type(local_type);
+                }
             }
         }
 
@@ -586,6 +645,21 @@ impl<'a> FunctionGenerator<'a> {
         }
     }
 
+    /// Generate a struct, union, enum or typedef defined in the body
+    fn generate_local_type(&mut self, local_type: &Element) {
+        match local_type {
+            Element::Compound(c) => {
+                let mut compound_gen = CompoundGenerator::new(self.output, self.config);
+                compound_gen.generate(c);
+            }
+            Element::TypedefAlias(t) => {
+                self.output
+                    .write_line(&self.formatter.format_typedef_alias(t));
+            }
+            _ => {}
+        }
+    }
+
     /// Generate inlined calls grouped by line
     fn generate_inlined_calls(&mut self, inlined_calls: &[&InlinedSubroutine]) {
         if inlined_calls.is_empty() {
@@ -612,7 +686,7 @@ impl<'a> FunctionGenerator<'a> {
 
     /// Generate local variables grouped by line
     fn generate_variables(&mut self, variables: &[&Variable]) {
-        let mut lines: HashMap<u64, Vec<&Variable>> = HashMap::new();
+        let mut lines: BTreeMap<u64, Vec<&Variable>> = BTreeMap::new();
         let mut no_line_vars = Vec::new();
 
         for &var in variables {
@@ -623,20 +697,16 @@ impl<'a> FunctionGenerator<'a> {
             }
         }
 
-        // Generate grouped variables
-        let mut sorted_lines: Vec<_> = lines.iter().collect();
-        sorted_lines.sort_by_key(|(line, _)| *line);
-
-        for (line, vars) in sorted_lines {
+        // Generate grouped variables, in line order
+        for (line, vars) in &lines {
             // Group by type compatibility
             let mut type_groups: Vec<Vec<&Variable>> = Vec::new();
 
             for var in vars {
                 let mut added = false;
                 for group in &mut type_groups {
-                    if self
-                        .formatter
-                        .types_compatible(&group[0].type_info, &var.type_info)
+                    if group[0].is_extern == var.is_extern
+                        && self.formatter.variables_compatible(group[0], var)
                     {
                         group.push(var);
                         added = true;
@@ -651,11 +721,10 @@ impl<'a> FunctionGenerator<'a> {
             // Generate declarations
             let mut decls = Vec::new();
             for group in type_groups {
-                if group[0].type_info.is_function_pointer {
+                if group[0].type_info.contains_function() {
                     // Output function pointers individually
                     for var in group {
-                        let decl = self.formatter.format_type_string(&var.type_info, &var.name);
-                        decls.push(decl);
+                        decls.push(self.formatter.format_variable(var));
                     }
                 } else {
                     let has_const_values = group.iter().any(|v| v.const_value.is_some());
@@ -663,8 +732,7 @@ impl<'a> FunctionGenerator<'a> {
                     if has_const_values {
                         // Output individually with const values
                         for var in group {
-                            let mut decl =
-                                self.formatter.format_type_string(&var.type_info, &var.name);
+                            let mut decl = self.formatter.format_variable(var);
                             if let Some(ref const_val) = var.const_value {
                                 decl.push_str(" = ");
                                 match const_val {
@@ -675,25 +743,10 @@ impl<'a> FunctionGenerator<'a> {
                             decls.push(decl);
                         }
                     } else {
-                        let base_type = &group[0].type_info;
-                        let mut var_names = Vec::new();
-
-                        for var in group {
-                            let ptr_str = "*".repeat(var.type_info.pointer_count);
-                            let mut name_with_array = format!("{}{}", ptr_str, var.name);
-
-                            for size in &var.type_info.array_sizes {
-                                name_with_array.push_str(&format!("[{}]", size));
-                            }
-
-                            var_names.push(name_with_array);
+                        let mut decl = self.formatter.format_grouped_declaration(&group);
+                        if group[0].is_extern {
+                            decl.insert_str(0, "extern ");
                         }
-
-                        let transformed_base =
-                            self.formatter.transform_type_name(&base_type.base_type);
-                        let mut decl = transformed_base;
-                        decl.push(' ');
-                        decl.push_str(&var_names.join(", "));
                         decls.push(decl);
                     }
                 }
@@ -706,10 +759,8 @@ impl<'a> FunctionGenerator<'a> {
 
         // Variables without line numbers
         for var in no_line_vars {
-            self.output.write_line(&format!(
-                "{};",
-                self.formatter.format_type_string(&var.type_info, &var.name)
-            ));
+            self.output
+                .write_line(&format!("{};", self.formatter.format_variable(var)));
         }
     }
 
@@ -743,6 +794,7 @@ impl<'a> FunctionGenerator<'a> {
             Variable(&'a Variable, usize),
             InlinedCall(&'a InlinedSubroutine, usize),
             NestedBlock(&'a LexicalBlock, usize),
+            LocalType(&'a Element, usize),
         }
 
         let mut elements: Vec<BlockElement> = Vec::new();
@@ -756,15 +808,19 @@ impl<'a> FunctionGenerator<'a> {
         for (idx, nested) in block.nested_blocks.iter().enumerate() {
             elements.push(BlockElement::NestedBlock(nested, idx));
         }
+        for (idx, local_type) in block.local_types.iter().enumerate() {
+            elements.push(BlockElement::LocalType(local_type, idx));
+        }
 
-        // Sort by line number
-        let mut keyed_elements: Vec<((Option<u64>, usize), BlockElement)> = elements
+        // Sort by line number, types first as for function bodies
+        let mut keyed_elements: Vec<(BodyOrder, BlockElement)> = elements
             .into_iter()
             .map(|elem| {
                 let key = match &elem {
-                    BlockElement::Variable(v, idx) => (v.line, *idx),
-                    BlockElement::InlinedCall(i, idx) => (i.line, *idx),
-                    BlockElement::NestedBlock(bl, idx) => (bl.min_content_line(), *idx),
+                    BlockElement::Variable(v, idx) => (v.line, 1, *idx),
+                    BlockElement::InlinedCall(i, idx) => (i.line, 1, *idx),
+                    BlockElement::NestedBlock(bl, idx) => (bl.min_content_line(), 1, *idx),
+                    BlockElement::LocalType(t, idx) => (t.line(), 0, *idx),
                 };
                 (key, elem)
             })
@@ -826,6 +882,19 @@ impl<'a> FunctionGenerator<'a> {
                     }
                     self.generate_lexical_block_with_labels(nested, pending_labels);
                 }
+                BlockElement::LocalType(local_type, _) => {
+                    self.output_pending_labels_before(pending_labels, local_type.line());
+                    self.output_pending_labels_before(&mut all_labels, local_type.line());
+                    if !variables_buffer.is_empty() {
+                        self.generate_variables(&variables_buffer);
+                        variables_buffer.clear();
+                    }
+                    if !inlined_buffer.is_empty() {
+                        self.generate_inlined_calls(&inlined_buffer);
+                        inlined_buffer.clear();
+                    }
+                    self.generate_local_type(local_type);
+                }
             }
         }
 
@@ -845,3 +914,14 @@ impl<'a> FunctionGenerator<'a> {
         }
     }
 }
+
+/// The end of a function's parameter list: `...` for a variadic function,
+/// and `void` for a prototyped function without parameters
+fn parameter_list_end(func: &Function, has_params: bool) -> &'static str {
+    match (func.is_variadic, has_params) {
+        (true, true) => ", ...)",
+        (true, false) => "...)",
+        (false, false) if func.is_prototyped => "void)",
+        _ => ")",
+    }
+}
@@ -24,11 +24,11 @@ pub struct CodeGenConfig {
     pub no_function_addresses: bool,
     pub no_offsets: bool,
     pub no_function_prototypes: bool,
-    pub pointer_size: u64, // 4 for 32-bit, 8 for 64-bit
     pub disable_no_line_comment: bool,
     pub verbose_class_usage: bool, // Include "class " prefix in type references (C mode only)
     pub code_style: String,        // "c" or "c++": controls which type prefixes to strip
     pub skip_namespace_indentation: bool, // Don't indent content inside namespaces
+    pub group_templates: bool,     // Merge class template instantiations into a primary template
 }
 
 impl Default for CodeGenConfig {
@@ -38,11 +38,11 @@ impl Default for CodeGenConfig {
             no_function_addresses: false,
             no_offsets: false,
             no_function_prototypes: false,
-            pointer_size: 4, // Default to 32-bit for backwards compatibility
             disable_no_line_comment: false,
             verbose_class_usage: false, // Don't include "class " prefix by default (C mode)
             code_style: "c".to_string(), // Default to C style (keep struct/union/enum prefixes)
             skip_namespace_indentation: false, // Indent namespace content by default
+            group_templates: false,     // Emit each instantiation as an explicit specialization
         }
     }
 }
@@ -50,72 +50,22 @@ impl Default for CodeGenConfig {
 /// Main code generator
 pub struct CodeGenerator {
     output: OutputWriter,
-    type_sizes: HashMap<String, u64>,
     config: CodeGenConfig,
 }
 
 impl CodeGenerator {
     #[allow(dead_code)]
-    pub fn with_type_sizes(type_sizes: HashMap<String, u64>) -> Self {
-        CodeGenerator {
-            output: OutputWriter::new(),
-            type_sizes,
-            config: CodeGenConfig::default(),
-        }
+    pub fn new() -> Self {
+        Self::with_config(CodeGenConfig::default())
     }
 
-    pub fn with_config(type_sizes: HashMap<String, u64>, config: CodeGenConfig) -> Self {
+    pub fn with_config(config: CodeGenConfig) -> Self {
         CodeGenerator {
             output: OutputWriter::new(),
-            type_sizes,
             config,
         }
     }
 
-    /// Collect type sizes from elements (for size estimation)
-    pub fn collect_type_sizes_from_elements(
-        type_sizes: &mut HashMap<String, u64>,
-        elements: &[Element],
-    ) {
-        for element in elements {
-            match element {
-                Element::Compound(c) => {
-                    if let (Some(name), Some(size)) = (&c.name, c.byte_size) {
-                        // Store both with and without compound type prefix for lookup
-                        type_sizes.insert(name.clone(), size);
-                        type_sizes.insert(format!("{} {}", c.compound_type, name), size);
-                    }
-                    // Also handle typedefs
-                    if let (Some(typedef_name), Some(size)) = (&c.typedef_name, c.byte_size) {
-                        type_sizes.insert(typedef_name.clone(), size);
-                    }
-                    // Also collect sizes from nested types
-                    Self::collect_type_sizes_from_compounds(type_sizes, &c.nested_types);
-                }
-                Element::Namespace(ns) => {
-                    Self::collect_type_sizes_from_elements(type_sizes, &ns.children);
-                }
-                _ => {}
-            }
-        }
-    }
-
-    fn collect_type_sizes_from_compounds(
-        type_sizes: &mut HashMap<String, u64>,
-        compounds: &[Compound],
-    ) {
-        for c in compounds {
-            if let (Some(name), Some(size)) = (&c.name, c.byte_size) {
-                type_sizes.insert(name.clone(), size);
-                type_sizes.insert(format!("{} {}", c.compound_type, name), size);
-            }
-            if let (Some(typedef_name), Some(size)) = (&c.typedef_name, c.byte_size) {
-                type_sizes.insert(typedef_name.clone(), size);
-            }
-            Self::collect_type_sizes_from_compounds(type_sizes, &c.nested_types);
-        }
-    }
-
     /// Generate code for a compile unit
     pub fn generate_compile_unit(&mut self, cu: &CompileUnit) {
         self.output.write_line_comment("", &cu.name);
@@ -127,20 +77,13 @@ impl CodeGenerator {
 
         // Sort elements by line number
         let mut sorted_elements: Vec<(usize, &Element)> = cu.elements.iter().enumerate().collect();
-        sorted_elements.sort_by_key(|(idx, elem)| {
-            let line = match elem {
-                Element::Compound(c) => c.typedef_line.or(c.line),
-                Element::Function(f) => f.line,
-                Element::Variable(v) => v.line,
-                Element::Namespace(ns) => ns.line,
-                Element::TypedefAlias(t) => t.line,
-            };
-            (line, *idx)
-        });
+        sorted_elements.sort_by_key(|(idx, elem)| (elem.line(), *idx));
 
+        let template_groups = self.template_groups(&sorted_elements);
         for (_, element) in sorted_elements {
-            self.generate_element(element);
-            self.output.push_newline();
+            if self.generate_listed_element(element, &template_groups) {
+                self.output.push_newline();
+            }
         }
 
         self.output.write_line_comment("", &cu.name);
@@ -178,20 +121,75 @@ impl CodeGenerator {
             .enumerate()
             .map(|(idx, &elem)| (idx, elem))
             .collect();
-        sorted_elements.sort_by_key(|(idx, elem)| {
-            let line = match elem {
-                Element::Compound(c) => c.typedef_line.or(c.line),
-                Element::Function(f) => f.line,
-                Element::Variable(v) => v.line,
-                Element::Namespace(ns) => ns.line,
-                Element::TypedefAlias(t) => t.line,
-            };
-            (line, *idx)
-        });
+        sorted_elements.sort_by_key(|(idx, elem)| (elem.line(), *idx));
 
+        let template_groups = self.template_groups(&sorted_elements);
         for (_, element) in sorted_elements {
-            self.generate_element(element);
-            self.output.push_newline();
+            if self.generate_listed_element(element, &template_groups) {
+                self.output.push_newline();
+            }
+        }
+    }
+
+    /// The instantiations of each class template among `elements`, in
+    /// order, keyed by their kind and template name
+    fn template_groups<'e>(
+        &self,
+        elements: &[(usize, &'e Element)],
+    ) -> HashMap<(&'e str, &'e str), Vec<&'e Compound>> {
+        let mut groups: HashMap<(&str, &str), Vec<&Compound>> = HashMap::new();
+        for (_, element) in elements {
+            if let (Element::Compound(c), Some(key)) = (element, template_group_key(element)) {
+                groups.entry(key).or_default().push(c);
+            }
+        }
+        groups
+    }
+
+    /// Generate an element of a list. With `group_templates`, the first
+    /// instantiation of a class template generates the primary template; the
+    /// others generate nothing and return false. Otherwise the primary
+    /// template is declared before the first explicit specialization.
+    fn generate_listed_element(
+        &mut self,
+        element: &Element,
+        template_groups: &HashMap<(&str, &str), Vec<&Compound>>,
+    ) -> bool {
+        if self.is_grouped_instantiation(element, template_groups) {
+            return false;
+        }
+        match template_group_key(element).and_then(|key| template_groups.get(&key)) {
+            Some(group) if self.config.group_templates => {
+                let mut compound_gen = CompoundGenerator::new(&mut self.output, &self.config);
+                compound_gen.generate_primary_template(group);
+            }
+            Some(group) => {
+                if matches!(element, Element::Compound(c) if std::ptr::eq(group[0], c)) {
+                    let mut compound_gen = CompoundGenerator::new(&mut self.output, &self.config);
+                    compound_gen.generate_primary_declaration(group[0]);
+                }
+                self.generate_element(element);
+            }
+            None => self.generate_element(element),
+        }
+        true
+    }
+
+    /// Whether an element is an instantiation generated as part of the primary
+    /// template of an earlier instantiation
+    fn is_grouped_instantiation(
+        &self,
+        element: &Element,
+        template_groups: &HashMap<(&str, &str), Vec<&Compound>>,
+    ) -> bool {
+        if !self.config.group_templates {
+            return false;
+        }
+        match (element, template_group_key(element)) {
+            (Element::Compound(c), Some(key)) => template_groups
+                .get(&key)
+                .is_some_and(|group| !std::ptr::eq(group[0], c)),
+            _ => false,
         }
     }
 
@@ -199,13 +197,11 @@ impl CodeGenerator {
     fn generate_element(&mut self, element: &Element) {
         match element {
             Element::Compound(c) => {
-                let mut compound_gen =
-                    CompoundGenerator::new(&mut self.output, &self.config, &self.type_sizes);
-                compound_gen.generate(c, &self.type_sizes);
+                let mut compound_gen = CompoundGenerator::new(&mut self.output, &self.config);
+                compound_gen.generate(c);
             }
             Element::Function(f) => {
-                let mut func_gen =
-                    FunctionGenerator::new(&mut self.output, &self.config, &self.type_sizes);
+                let mut func_gen = FunctionGenerator::new(&mut self.output, &self.config);
                 func_gen.generate_function(f);
             }
             Element::Variable(v) => self.generate_global_variable(v),
@@ -228,22 +224,19 @@ impl CodeGenerator {
 
         // Sort namespace children by line number
         let mut sorted_children: Vec<(usize, &Element)> = ns.children.iter().enumerate().collect();
-        sorted_children.sort_by_key(|(idx, elem)| {
-            let line = match elem {
-                Element::Compound(c) => c.typedef_line.or(c.line),
-                Element::Function(f) => f.line,
-                Element::Variable(v) => v.line,
-                Element::Namespace(ns) => ns.line,
-                Element::TypedefAlias(t) => t.line,
-            };
-            (line, *idx)
-        });
-
-        for (i, (_, child)) in sorted_children.iter().enumerate() {
-            if i > 0 {
+        sorted_children.sort_by_key(|(idx, elem)| (elem.line(), *idx));
+
+        let template_groups = self.template_groups(&sorted_children);
+        let mut first = true;
+        for (_, child) in sorted_children {
+            if self.is_grouped_instantiation(child, &template_groups) {
+                continue;
+            }
+            if !first {
                 self.output.push_newline();
             }
-            self.generate_element(child);
+            first = false;
+            self.generate_listed_element(child, &template_groups);
         }
 
         if !self.config.skip_namespace_indentation {
@@ -257,16 +250,22 @@ impl CodeGenerator {
 
     /// Generate a global variable declaration
     fn generate_global_variable(&mut self, var: &Variable) {
-        let formatter = TypeFormatter::new(&self.config, &self.type_sizes);
-        let mut decl = formatter.format_type_string(&var.type_info, &var.name);
+        let formatter = TypeFormatter::new(&self.config);
+        let mut decl = formatter.format_variable(var);
 
         // Add const value if present
         if let Some(ref const_val) = var.const_value {
-            decl.push_str(" = ");
-            match const_val {
-                ConstValue::Signed(v) => decl.push_str(&v.to_string()),
-                ConstValue::Unsigned(v) => decl.push_str(&v.to_string()),
-            }
+            decl.push_str(&format!(" = {}", const_val));
+        }
+
+        // Members of a class generated as a primary template are specialized
+        // for its instantiation. Those of an explicit specialization are not.
+        let instantiated_class = var
+            .name
+            .rsplit_once("::")
+            .is_some_and(|(class, _)| class.contains('<'));
+        if self.config.group_templates && instantiated_class {
+            decl.insert_str(0, "template<> ");
         }
 
         let line_comment = var.line.map(|l| format!(" //{}", l)).unwrap_or_default();
@@ -276,17 +275,9 @@ impl CodeGenerator {
 
     /// Generate a typedef alias
     fn generate_typedef_alias(&mut self, typedef_alias: &TypedefAlias) {
-        let formatter = TypeFormatter::new(&self.config, &self.type_sizes);
-        let type_str =
-            formatter.format_type_string(&typedef_alias.target_type, &typedef_alias.name);
-        let mut decl = format!("typedef {}", type_str);
-        decl.push(';');
-
-        if let Some(line) = typedef_alias.line {
-            decl.push_str(&format!(" //{}", line));
-        }
-
-        self.output.write_line(&decl);
+        let formatter = TypeFormatter::new(&self.config);
+        self.output
+            .write_line(&formatter.format_typedef_alias(typedef_alias));
     }
 
     /// Get the accumulated output
@@ -294,3 +285,14 @@ impl CodeGenerator {
         self.output.get_output()
     }
 }
+
+/// The kind and template name of an instantiation of a class template
+fn template_group_key(element: &Element) -> Option<(&str, &str)> {
+    match element {
+        Element::Compound(c) if !c.template_params.is_empty() && !c.is_typedef => c
+            .name
+            .as_deref()
+            .map(|name| (c.compound_type.as_str(), template_name(name))),
+        _ => None,
+    }
+}
@@ -1,19 +1,17 @@
 //! Type formatting and transformation utilities
 
 use crate::types::*;
-use std::collections::HashMap;
 
 use super::CodeGenConfig;
 
 /// Handles type formatting and transformation
 pub struct TypeFormatter<'a> {
     config: &'a CodeGenConfig,
-    type_sizes: &'a HashMap<String, u64>,
 }
 
 impl<'a> TypeFormatter<'a> {
-    pub fn new(config: &'a CodeGenConfig, type_sizes: &'a HashMap<String, u64>) -> Self {
-        Self { config, type_sizes }
+    pub fn new(config: &'a CodeGenConfig) -> Self {
+        Self { config }
     }
 
     /// Shorten integer type names (e.g., "short int" -> "short")
@@ -78,166 +76,202 @@ impl<'a> TypeFormatter<'a> {
         result
     }
 
+    /// Apply type name transformations to every named type in a type
+    pub fn transform_type(&self, type_info: &TypeInfo) -> TypeInfo {
+        type_info.map_names(&|name| self.transform_type_name(name))
+    }
+
     /// Format a type with a variable name
     pub fn format_type_string(&self, type_info: &TypeInfo, var_name: &str) -> String {
-        // Clone type_info and transform the base type
-        let mut transformed_type = type_info.clone();
-        transformed_type.base_type = self.transform_type_name(&type_info.base_type);
+        self.transform_type(type_info).to_string(var_name)
+    }
 
-        // For function pointers, also transform return type and parameter types
-        if transformed_type.is_function_pointer {
-            if let Some(ref mut ret_type) = transformed_type.function_return_type {
-                ret_type.base_type = self.transform_type_name(&ret_type.base_type);
-            }
-            for param in &mut transformed_type.function_params {
-                param.base_type = self.transform_type_name(&param.base_type);
+    /// Format the parameter list of a primary template (e.g. `typename T, int N`)
+    pub fn format_template_parameters(&self, params: &[TemplateParameter]) -> String {
+        params
+            .iter()
+            .enumerate()
+            .map(|(i, param)| {
+                let name = param.name.clone().unwrap_or_else(|| format!("T{}", i));
+                match &param.argument {
+                    TemplateArgument::Type(_) => format!("typename {}", name),
+                    TemplateArgument::Value { type_info, .. } => {
+                        self.format_type_string(type_info, &name)
+                    }
+                    TemplateArgument::Pack(arguments) => match arguments.first() {
+                        Some(TemplateArgument::Value { type_info, .. }) => {
+                            self.format_type_string(type_info, &format!("... {}", name))
+                        }
+                        _ => format!("typename... {}", name),
+                    },
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Format the arguments of a template instantiation (e.g. `T = int, N = 4`)
+    pub fn format_template_arguments(&self, params: &[TemplateParameter]) -> String {
+        params
+            .iter()
+            .map(|param| {
+                let argument = self.format_template_argument(&param.argument);
+                match &param.name {
+                    Some(name) => format!("{} = {}", name, argument),
+                    None => argument,
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn format_template_argument(&self, argument: &TemplateArgument) -> String {
+        match argument {
+            TemplateArgument::Type(type_info) => self.format_type_string(type_info, ""),
+            TemplateArgument::Value {
+                type_info,
+                value,
+                symbol,
+            } => {
+                if let Some(symbol) = symbol {
+                    return format!("&{}", symbol);
+                }
+                let Some(value) = value else {
+                    return "?".to_string();
+                };
+                let raw = match *value {
+                    ConstValue::Signed(v) => v,
+                    ConstValue::Unsigned(v) => v as i64,
+                };
+                match type_info.base_name() {
+                    "bool" => (raw != 0).to_string(),
+                    "char" | "signed char" | "unsigned char"
+                        if (0x20..0x7f).contains(&raw) && raw != 0x27 && raw != 0x5c =>
+                    {
+                        format!("'{}'", raw as u8 as char)
+                    }
+                    _ => value.to_string(),
+                }
             }
+            TemplateArgument::Pack(arguments) => format!(
+                "<{}>",
+                arguments
+                    .iter()
+                    .map(|argument| self.format_template_argument(argument))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
         }
+    }
 
-        transformed_type.to_string(var_name)
+    /// Format a variable declaration, with its storage class and alignment
+    pub fn format_variable(&self, var: &Variable) -> String {
+        let decl = format!(
+            "{}{}",
+            self.alignment_specifier(var.alignment),
+            self.format_type_string(&var.type_info, &var.name)
+        );
+        if var.is_extern {
+            format!("extern {}", decl)
+        } else {
+            decl
+        }
     }
 
-    /// Estimate the size of a type in bytes
-    pub fn estimate_type_size(&self, type_info: &TypeInfo) -> u64 {
-        let ptr_size = self.config.pointer_size;
+    /// The alignment specifier of a declaration, followed by a space
+    fn alignment_specifier(&self, alignment: Option<u64>) -> String {
+        match alignment {
+            Some(alignment) if self.config.code_style == "c++" => {
+                format!("alignas({}) ", alignment)
+            }
+            Some(alignment) => format!("_Alignas({}) ", alignment),
+            None => String::new(),
+        }
+    }
 
-        // Determine the base element size
-        let base_size = if type_info.pointer_count > 0 || type_info.is_function_pointer {
-            // Pointers use architecture-specific size
-            ptr_size
+    /// The GCC attributes keeping the layout of a struct, class, union or
+    /// typedef (`__attribute__((packed, aligned(8)))`), if it needs any
+    pub fn layout_attributes(&self, is_packed: bool, alignment: Option<u64>) -> Option<String> {
+        let mut attributes = Vec::new();
+        if is_packed {
+            attributes.push("packed".to_string());
+        }
+        if let Some(alignment) = alignment {
+            attributes.push(format!("aligned({})", alignment));
+        }
+        if attributes.is_empty() {
+            None
         } else {
-            // Calculate size based on base type
-            self.get_base_type_size(&type_info.base_type, ptr_size)
-        };
+            Some(format!("__attribute__(({}))", attributes.join(", ")))
+        }
+    }
 
-        // If there are arrays, multiply by total array size
-        // This handles both arrays of base types and arrays of pointers
-        if !type_info.array_sizes.is_empty() {
-            let total_elements: usize = type_info.array_sizes.iter().product();
-            return base_size * (total_elements as u64);
+    /// Format a typedef alias, with its line comment
+    pub fn format_typedef_alias(&self, typedef_alias: &TypedefAlias) -> String {
+        let type_str = self.format_type_string(&typedef_alias.target_type, &typedef_alias.name);
+        let mut decl = format!("typedef {}", type_str);
+        if let Some(attributes) = self.layout_attributes(false, typedef_alias.alignment) {
+            decl.push(' ');
+            decl.push_str(&attributes);
         }
+        decl.push(';');
 
-        base_size
+        if let Some(line) = typedef_alias.line {
+            decl.push_str(&format!(" //{}", line));
+        }
+        decl
     }
 
-    /// Get the size of a base type
-    fn get_base_type_size(&self, base_type: &str, ptr_size: u64) -> u64 {
-        match base_type {
-            "char" | "unsigned char" | "signed char" | "bool" | "boolean" => 1,
-            "short" | "short int" | "unsigned short" | "signed short" | "short unsigned int" => 2,
-            "int" | "unsigned int" | "signed int" => 4,
-            // long is 4 bytes on 32-bit, but can vary; use pointer size for LP64/LLP64 compat
-            "long" | "unsigned long" | "signed long" | "long int" | "long unsigned int" => {
-                if ptr_size == 8 {
-                    8
-                } else {
-                    4
-                }
-            }
-            "long long"
-            | "unsigned long long"
-            | "signed long long"
-            | "long long int"
-            | "long long unsigned int" => 8,
-            "float" => 4,
-            "double" => 8,
-            "long double" => {
-                if ptr_size == 8 {
-                    16
-                } else {
-                    12
-                }
-            } // Architecture dependent
-            "void" => 0,
-            // For GLuint, GLint and similar types (typically typedef to unsigned int / int)
-            s if s.starts_with("GL") => 4,
-            // Platform-dependent types - use pointer size
-            "size_t" | "ssize_t" | "ptrdiff_t" | "intptr_t" | "uintptr_t" => ptr_size,
-            // Other common system types (typically 4 bytes even on 64-bit)
-            "fpos_t" => ptr_size, // Often contains a pointer
-            "time_t" => {
-                if ptr_size == 8 {
-                    8
-                } else {
-                    4
-                }
-            }
-            "off_t" => {
-                if ptr_size == 8 {
-                    8
-                } else {
-                    4
-                }
-            }
-            "pid_t" => 4,
-            "uid_t" => 4,
-            "gid_t" => 4,
-            "suseconds_t" => {
-                if ptr_size == 8 {
-                    8
-                } else {
-                    4
-                }
-            }
-            "clock_t" => {
-                if ptr_size == 8 {
-                    8
-                } else {
-                    4
-                }
-            }
-            "dev_t" => {
-                if ptr_size == 8 {
-                    8
-                } else {
-                    4
-                }
-            }
-            "ino_t" => {
-                if ptr_size == 8 {
-                    8
-                } else {
-                    4
-                }
-            }
-            "mode_t" => 4,
-            "nlink_t" => {
-                if ptr_size == 8 {
-                    8
-                } else {
-                    4
-                }
-            }
-            "blksize_t" => {
-                if ptr_size == 8 {
-                    8
-                } else {
-                    4
-                }
-            }
-            "blkcnt_t" => {
-                if ptr_size == 8 {
-                    8
-                } else {
-                    4
-                }
+    /// Format a declaration of several variables of compatible types, as in
+    /// `int a, *b, c[4]`
+    pub fn format_grouped_declaration(&self, vars: &[&Variable]) -> String {
+        let specifiers = self.transform_type(&vars[0].type_info).specifiers();
+        let declarators: Vec<String> = vars
+            .iter()
+            .map(|var| {
+                let (before, after) = self.transform_type(&var.type_info).declarator_parts(true);
+                format!("{}{}{}", before, var.name, after)
+            })
+            .collect();
+        format!(
+            "{}{} {}",
+            self.alignment_specifier(vars[0].alignment),
+            specifiers,
+            declarators.join(", ")
+        )
+    }
+
+    /// The parts of a function declaration written before and after its name
+    /// and parameter list, for its return type. Pointer and reference return
+    /// types are attached to the type (`char* name(...)`); others, such as
+    /// function pointers, are written around the function's declarator.
+    pub fn format_return_type(&self, return_type: &TypeInfo) -> (String, String) {
+        let return_type = self.transform_type(return_type);
+        let (before, after) = return_type.declarator_parts(true);
+        if after.is_empty() {
+            let separator = if attaches_to_specifiers(&before) {
+                ""
+            } else {
+                " "
+            };
+            let mut prefix = format!("{}{}{}", return_type.specifiers(), separator, before);
+            if !prefix.ends_with(' ') {
+                prefix.push(' ');
             }
-            // Common typedefs from various libraries
-            "INT32" | "UINT32" | "DWORD" => 4,
-            "INT16" | "UINT16" | "WORD" => 2,
-            "INT8" | "UINT8" | "BYTE" => 1,
-            "INT64" | "UINT64" | "QWORD" => 8,
-            "JCOEF" => 2,      // JPEG coefficient (short)
-            "JDIMENSION" => 4, // JPEG dimension (unsigned int)
-            "JOCTET" => 1,     // JPEG octet (unsigned char)
-            // For struct/class types, look up the byte_size from parsed types
-            _ => *self.type_sizes.get(base_type).unwrap_or(&4), // Default to 4 bytes if unknown
+            (prefix, after)
+        } else {
+            (format!("{} {}", return_type.specifiers(), before), after)
         }
     }
 
+    /// The size of a type in bytes, if it is complete
+    pub fn type_size(&self, type_info: &TypeInfo) -> Option<u64> {
+        type_info.layout.map(|layout| layout.size)
+    }
+
     /// Format a member declaration with optional bitfield and const value
     pub fn format_member_declaration(&self, var: &Variable) -> String {
-        let mut decl = self.format_type_string(&var.type_info, &var.name);
+        let mut decl = self.format_variable(var);
 
         // Add bitfield specification if present
         if let Some(bit_size) = var.bit_size {
@@ -253,13 +287,23 @@ impl<'a> TypeFormatter<'a> {
             }
         }
 
+        if let Some(ref instantiation) = var.instantiation {
+            decl.push_str(&format!(" /* type of {} */", instantiation));
+        }
+
         decl
     }
 
-    /// Check if two types are compatible for grouping on the same line
-    pub fn types_compatible(&self, t1: &TypeInfo, t2: &TypeInfo) -> bool {
-        // Two types are compatible for joining if they have the same base type
-        // and differ only in pointer count or array sizes
-        t1.base_type == t2.base_type && !t1.is_function_pointer && !t2.is_function_pointer
+    /// Check if two variables can be declared together on the same line
+    pub fn variables_compatible(&self, v1: &Variable, v2: &Variable) -> bool {
+        // Two types are compatible for joining if they have the same specifiers
+        // and differ only in their declarators (pointers and array sizes)
+        let (t1, t2) = (&v1.type_info, &v2.type_info);
+        t1.base() == t2.base()
+            && !t1.contains_function()
+            && !t2.contains_function()
+            && v1.alignment == v2.alignment
+            && v1.instantiation.is_none()
+            && v2.instantiation.is_none()
     }
 }
@@ -1,96 +1,139 @@
+mod debug_link;
 mod element_processing;
 mod error;
 mod generator;
 mod parser;
 mod types;
 
-use clap::Parser as ClapParser;
+use clap::{CommandFactory, Parser as ClapParser};
 use element_processing::{
     group_elements_by_file, merge_namespaces_with_config, normalize_path,
-    wrap_method_definitions_in_namespaces, MergeConfig, MergeStats,
+    partition_extern_prototypes, wrap_method_definitions_in_namespaces, MergeConfig, MergeStats,
 };
 use error::Result;
 use generator::{CodeGenConfig, CodeGenerator};
+use memmap2::Mmap;
 use object::read::archive::ArchiveFile;
 use object::Object;
-use parser::DwarfParser;
-use std::collections::HashMap;
+use parser::{DwarfParser, PackageFile, ParserConfig};
+use rayon::prelude::*;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Header holding the prototypes of functions whose declaring file is unknown
+const EXTERNS_HEADER: &str = "externs.h";
 
 /// Parse a single object file's DWARF data
-fn parse_object_file(data: &[u8]) -> Result<Vec<types::CompileUnit>> {
-    let mut parser = DwarfParser::new(data)?;
+fn parse_object_file(data: &[u8], config: &ParserConfig) -> Result<Vec<types::CompileUnit>> {
+    let mut parser = DwarfParser::with_config(data, config.clone())?;
     parser.parse()
 }
 
-/// Detect pointer size from an object file (returns 4 for 32-bit, 8 for 64-bit)
-fn detect_pointer_size(data: &[u8]) -> u64 {
-    if let Ok(obj) = object::File::parse(data) {
-        if obj.is_64() {
-            return 8;
+/// Read the separate debug file of a stripped binary, along with its path.
+///
+/// Returns `None` if the binary has DWARF of its own or no debug file was found.
+fn read_separate_debug_file(
+    binary_path: &Path,
+    data: &[u8],
+    debug_dirs: &[PathBuf],
+) -> Result<Option<(PathBuf, Mmap)>> {
+    let object = object::File::parse(data)?;
+    if debug_link::has_dwarf(&object) {
+        return Ok(None);
+    }
+
+    match debug_link::find_debug_file(binary_path, &object, debug_dirs) {
+        Some(debug_path) => {
+            println!("Using debug info from {}", debug_path.display());
+            let debug_data = parser::map_file(&debug_path)?;
+            Ok(Some((debug_path, debug_data)))
+        }
+        None => {
+            eprintln!(
+                "Warning: No separate debug file found for {}",
+                binary_path.display()
+            );
+            Ok(None)
         }
     }
-    4 // Default to 32-bit
 }
 
-/// Detect pointer size from an archive by checking the first object member
-fn detect_pointer_size_from_archive(archive: &ArchiveFile<'_>, archive_data: &[u8]) -> u64 {
-    for member in archive.members().flatten() {
-        if let Ok(member_data) = member.data(archive_data) {
-            if object::File::parse(member_data).is_ok() {
-                return detect_pointer_size(member_data);
-            }
+/// Find the supplementary file (from dwz) that a file's DWARF refers to.
+///
+/// Returns `None` if the file has no `.gnu_debugaltlink` or the supplementary
+/// file was not found.
+fn find_supplementary_file(
+    dwarf_path: &Path,
+    data: &[u8],
+    debug_dirs: &[PathBuf],
+) -> Option<PathBuf> {
+    let object = object::File::parse(data).ok()?;
+    object.gnu_debugaltlink().ok()??;
+
+    match debug_link::find_supplementary_file(dwarf_path, &object, debug_dirs) {
+        Some(supplementary_path) => {
+            println!(
+                "Using supplementary debug info from {}",
+                supplementary_path.display()
+            );
+            Some(supplementary_path)
+        }
+        None => {
+            eprintln!(
+                "Warning: No supplementary debug file found for {}",
+                dwarf_path.display()
+            );
+            None
         }
     }
-    4 // Default to 32-bit
 }
 
-/// Parse an archive file and process all object file members
-fn parse_archive(archive: ArchiveFile<'_>, archive_data: &[u8]) -> Result<Vec<types::CompileUnit>> {
-    let mut all_compile_units = Vec::new();
-
-    // Collect all member data into owned Vec<u8> to ensure proper lifetime
-    let mut member_data_storage: Vec<Vec<u8>> = Vec::new();
-
+/// Parse an archive file and process all object file members.
+///
+/// Members are parsed in place on the thread pool, and their compile units
+/// returned in member order.
+fn parse_archive(
+    archive: ArchiveFile<'_>,
+    archive_data: &[u8],
+    config: &ParserConfig,
+) -> Result<Vec<types::CompileUnit>> {
+    let mut members = Vec::new();
     for member_result in archive.members() {
         let member = member_result?;
         let member_data = member.data(archive_data)?;
 
         // Skip non-object files (like symbol tables)
-        if object::File::parse(member_data).is_ok() {
-            member_data_storage.push(member_data.to_vec());
+        if object::File::parse(member_data).is_err() {
+            continue;
         }
+        members.push(member_data);
     }
 
-    // Now parse each member's DWARF data
-    for member_data in &member_data_storage {
-        match parse_object_file(member_data) {
-            Ok(mut compile_units) => {
-                all_compile_units.append(&mut compile_units);
-            }
+    let member_units: Vec<Vec<types::CompileUnit>> = members
+        .into_par_iter()
+        .map(|member_data| match parse_object_file(member_data, config) {
+            Ok(compile_units) => compile_units,
             Err(e) => {
                 // Some members might not have DWARF data, that's okay
                 eprintln!("Warning: Failed to parse archive member: {}", e);
+                Vec::new()
             }
-        }
-    }
-
-    // Perform cross-CU matching across all object files in the archive.
-    // This is necessary because class declarations and method definitions
-    // may be in different object files (e.g., header.o vs implementation.o).
-    parser::cross_cu_match_method_definitions(&mut all_compile_units);
+        })
+        .collect();
 
-    Ok(all_compile_units)
+    Ok(member_units.into_iter().flatten().collect())
 }
 
 /// DWARF C reconstructor - generates C++ code from DWARF debugging information
 #[derive(ClapParser, Debug, Clone)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// Path to the ELF file or archive to analyze
-    #[arg(value_name = "FILE")]
-    file_path: String,
+    /// Paths to the ELF files or archives to analyze, or directories containing them
+    #[arg(value_name = "FILE", required = true)]
+    file_paths: Vec<String>,
 
     /// Output directory for generated files
     #[arg(short, long, default_value = "output")]
@@ -133,6 +176,10 @@ struct Args {
     #[arg(long)]
     skip_namespace_indentation: bool,
 
+    /// Emit the instantiations of each class template as one primary template, listing the instantiations
+    #[arg(long)]
+    group_templates: bool,
+
     /// Enable verbose output (show details of merged anonymous types)
     #[arg(long)]
     verbose: bool,
@@ -140,34 +187,238 @@ struct Args {
     /// Disable merging of duplicate anonymous types (enums, structs, unions)
     #[arg(long)]
     no_anonymous_merge: bool,
-}
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+    /// Additional directory to search for split DWARF (.dwo) files (can be repeated)
+    #[arg(long, value_name = "DIR")]
+    dwo_dir: Vec<String>,
+
+    /// DWARF package (.dwp) holding the split DWARF units of a single input (defaults to FILE.dwp if present)
+    #[arg(long, value_name = "FILE")]
+    dwp: Option<String>,
+
+    /// Load DWARF from a separate debug file (via build ID or .gnu_debuglink) if FILE is stripped
+    #[arg(long)]
+    find_debug_file: bool,
+
+    /// Additional directory to search for separate debug files (can be repeated; implies --find-debug-file)
+    #[arg(long, value_name = "DIR")]
+    debug_dir: Vec<String>,
+
+    /// Supplementary debug file made by dwz (defaults to the file named by .gnu_debugaltlink)
+    #[arg(long, value_name = "FILE")]
+    alt_file: Option<String>,
+
+    /// Write each input's source files into a subdirectory named after the input
+    /// (headers are still merged across inputs). Used anyway when inputs share source paths
+    #[arg(long)]
+    per_input_dirs: bool,
 
-    // Read file data
-    let file_data = fs::read(&args.file_path)?;
+    /// Number of threads used for parsing (defaults to the number of CPUs)
+    #[arg(short, long, value_name = "N")]
+    jobs: Option<usize>,
+}
+
+/// Parse one input file, returning its compile units
+fn parse_input(
+    file_path: &Path,
+    args: &Args,
+    debug_dirs: &[PathBuf],
+) -> Result<Vec<types::CompileUnit>> {
+    // Map the file rather than reading it, so archive members and sections
+    // without relocations are parsed in place
+    let file_data = parser::map_file(file_path)?;
     let file_data_slice: &[u8] = &file_data;
 
-    // Detect pointer size and parse file
-    let (compile_units, pointer_size) = if let Ok(archive) = ArchiveFile::parse(file_data_slice) {
-        // It's an archive file - detect pointer size and process each member
-        let ptr_size = detect_pointer_size_from_archive(&archive, file_data_slice);
-        // Re-parse archive for member iteration (iterators are consumed)
-        let archive = ArchiveFile::parse(file_data_slice)?;
-        (parse_archive(archive, file_data_slice)?, ptr_size)
+    // Split DWARF files are looked up in the user's directories, then next to the input
+    let mut dwo_search_paths: Vec<PathBuf> = args.dwo_dir.iter().map(PathBuf::from).collect();
+    if let Some(input_dir) = file_path.parent() {
+        dwo_search_paths.push(input_dir.to_path_buf());
+    }
+    // The package is loaded once and shared by every parser of this input
+    let default_dwp = PathBuf::from(format!("{}.dwp", file_path.display()));
+    let dwp_path = match &args.dwp {
+        Some(dwp) => Some(PathBuf::from(dwp)),
+        None => default_dwp.is_file().then_some(default_dwp),
+    };
+    let dwp = match dwp_path {
+        Some(dwp_path) => Some(Arc::new(PackageFile::load(&dwp_path)?)),
+        None => None,
+    };
+    let mut parser_config = ParserConfig {
+        dwo_search_paths,
+        dwp,
+        supplementary_path: args.alt_file.as_ref().map(PathBuf::from),
+    };
+
+    if let Ok(archive) = ArchiveFile::parse(file_data_slice) {
+        // It's an archive file - process each member
+        parse_archive(archive, file_data_slice, &parser_config)
     } else {
-        // It's a regular object file
-        let ptr_size = detect_pointer_size(file_data_slice);
-        (parse_object_file(file_data_slice)?, ptr_size)
+        // It's a regular object file, whose DWARF may live in a separate
+        // debug file
+        let debug_file = if args.find_debug_file || !args.debug_dir.is_empty() {
+            read_separate_debug_file(file_path, file_data_slice, debug_dirs)?
+        } else {
+            None
+        };
+        let (dwarf_path, dwarf_data) = match &debug_file {
+            Some((debug_path, debug_data)) => (debug_path.as_path(), &debug_data[..]),
+            None => (file_path, file_data_slice),
+        };
+        if parser_config.supplementary_path.is_none() {
+            parser_config.supplementary_path =
+                find_supplementary_file(dwarf_path, dwarf_data, debug_dirs);
+        }
+        match &debug_file {
+            Some((_, debug_data)) => {
+                DwarfParser::with_debug_file(file_data_slice, debug_data, parser_config)?.parse()
+            }
+            None => parse_object_file(file_data_slice, &parser_config),
+        }
+    }
+}
+
+/// An input file, with the subdirectory its source files go to when
+/// --per-input-dirs is set
+struct Input {
+    path: PathBuf,
+    output_subdir: PathBuf,
+}
+
+/// Expand the command line inputs, replacing each directory by the object
+/// files and archives found under it
+/// The number of source paths with compile units from more than one input
+fn shared_source_paths(compile_units: &[types::CompileUnit], cu_inputs: &[usize]) -> usize {
+    let mut inputs_by_path: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (cu, &input_index) in compile_units.iter().zip(cu_inputs) {
+        let inputs = inputs_by_path.entry(normalize_path(&cu.name)).or_default();
+        if !inputs.contains(&input_index) {
+            inputs.push(input_index);
+        }
+    }
+    inputs_by_path
+        .values()
+        .filter(|inputs| inputs.len() > 1)
+        .count()
+}
+
+fn collect_inputs(file_paths: &[String]) -> Result<Vec<Input>> {
+    let mut inputs: Vec<Input> = Vec::new();
+    for file_path in file_paths {
+        let file_path = Path::new(file_path);
+        let mut found = Vec::new();
+        if file_path.is_dir() {
+            collect_object_files(file_path, &mut found)?;
+        } else {
+            found.push(file_path.to_path_buf());
+        }
+
+        for path in found {
+            // Files found in a directory are named by their path below it
+            let name = match path.strip_prefix(file_path) {
+                Ok(relative) if !relative.as_os_str().is_empty() => relative.to_path_buf(),
+                _ => PathBuf::from(path.file_name().unwrap_or(path.as_os_str())),
+            };
+            let mut output_subdir = name.clone();
+            let mut suffix = 2;
+            while inputs
+                .iter()
+                .any(|input| input.output_subdir == output_subdir)
+            {
+                output_subdir = PathBuf::from(format!("{}-{}", name.display(), suffix));
+                suffix += 1;
+            }
+            inputs.push(Input {
+                path,
+                output_subdir,
+            });
+        }
+    }
+    Ok(inputs)
+}
+
+/// Recursively collect the object files and archives in a directory, in
+/// sorted order. Split DWARF files are left out, as they are loaded through
+/// the skeleton units referring to them.
+fn collect_object_files(dir: &Path, found: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<_>>()?;
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            collect_object_files(&path, found)?;
+            continue;
+        }
+        let is_split_dwarf = path
+            .extension()
+            .is_some_and(|extension| extension == "dwo" || extension == "dwp");
+        if !is_split_dwarf && is_object_or_archive(&path) {
+            found.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Whether a file starts like an object file or an archive
+fn is_object_or_archive(path: &Path) -> bool {
+    let mut magic = [0u8; 16];
+    let Ok(mut file) = fs::File::open(path) else {
+        return false;
+    };
+    let Ok(length) = file.read(&mut magic) else {
+        return false;
     };
+    matches!(
+        object::FileKind::parse(&magic[..length]),
+        Ok(kind) if kind != object::FileKind::DyldCache
+    )
+}
 
-    // Collect all type sizes from all compile units
-    let mut type_sizes = HashMap::new();
-    for cu in &compile_units {
-        CodeGenerator::collect_type_sizes_from_elements(&mut type_sizes, &cu.elements);
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let debug_dirs: Vec<PathBuf> = args.debug_dir.iter().map(PathBuf::from).collect();
+
+    if let Some(jobs) = args.jobs {
+        if let Err(e) = rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build_global()
+        {
+            eprintln!("Warning: Failed to set the number of threads: {}", e);
+        }
     }
 
+    // Inputs, the members of archives and the units of each file are parsed
+    // in parallel; results are kept in input order
+    let inputs = collect_inputs(&args.file_paths)?;
+    // A package holds the split units of one program, so it can't apply to several
+    if args.dwp.is_some() && inputs.len() > 1 {
+        Args::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "--dwp can only be used with a single input file",
+            )
+            .exit();
+    }
+    let parsed_inputs = inputs
+        .par_iter()
+        .map(|input| parse_input(&input.path, &args, &debug_dirs))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut compile_units = Vec::new();
+    // Input each compile unit came from
+    let mut cu_inputs = Vec::new();
+    for (input_index, input_units) in parsed_inputs.into_iter().enumerate() {
+        cu_inputs.extend(std::iter::repeat_n(input_index, input_units.len()));
+        compile_units.extend(input_units);
+    }
+
+    // Class declarations and method definitions may be in different compile
+    // units, object files (e.g., header.o vs implementation.o) or inputs, as
+    // with a library split over several shared objects
+    parser::cross_cu_match_method_definitions(&mut compile_units);
+
     // Generate code for each compile unit
     let output_dir = Path::new(&args.output_dir);
     fs::create_dir_all(output_dir)?;
@@ -179,11 +430,11 @@ fn main() -> Result<()> {
         no_function_addresses: args.no_function_addresses || args.minimal,
         no_offsets: args.no_offsets || args.minimal,
         no_function_prototypes: args.no_function_prototypes || args.minimal,
-        pointer_size,
         disable_no_line_comment: args.disable_no_line_comment,
         verbose_class_usage: args.verbose_class_usage,
         code_style: args.code_style.clone(),
         skip_namespace_indentation: args.skip_namespace_indentation,
+        group_templates: args.group_templates,
     };
 
     // Create merge config for anonymous type merging
@@ -196,8 +447,9 @@ fn main() -> Result<()> {
     let mut total_merge_stats = MergeStats::default();
 
     // First pass: collect all header file elements from all compile units
-    // Map from normalized path to (elements, original_path)
-    let mut header_elements: HashMap<String, (Vec<types::Element>, String)> = HashMap::new();
+    // Map from normalized path to (elements, original_path), so headers are
+    // generated in path order
+    let mut header_elements: BTreeMap<String, (Vec<types::Element>, String)> = BTreeMap::new();
 
     for cu in &compile_units {
         // Group elements by declaration file, properly handling namespaces
@@ -226,11 +478,22 @@ fn main() -> Result<()> {
                 }
             }
         }
+
+        // Prototypes of functions declared in an unknown file
+        if let Some(elements) = elements_by_file.get(&None) {
+            let (prototypes, _) = partition_extern_prototypes(elements.clone());
+            if !prototypes.is_empty() {
+                let entry = header_elements
+                    .entry(EXTERNS_HEADER.to_string())
+                    .or_insert_with(|| (Vec::new(), EXTERNS_HEADER.to_string()));
+                entry.0.extend(prototypes);
+            }
+        }
     }
 
     // Generate merged header files
     for (normalized_path, (elements, original_path)) in &header_elements {
-        let mut generator = CodeGenerator::with_config(type_sizes.clone(), config.clone());
+        let mut generator = CodeGenerator::with_config(config.clone());
 
         // Generate header comment (use original path for display)
         generator.generate_header_comment_simple(original_path);
@@ -264,8 +527,19 @@ fn main() -> Result<()> {
         );
     }
 
+    // Inputs built from the same sources would overwrite each other's files
+    let shared_sources = shared_source_paths(&compile_units, &cu_inputs);
+    let per_input_dirs = args.per_input_dirs || shared_sources > 0;
+    if shared_sources > 0 && !args.per_input_dirs {
+        eprintln!(
+            "Warning: {} source paths are shared by several inputs; writing each input's sources to its own subdirectory",
+            shared_sources
+        );
+    }
+
     // Second pass: generate source files for each compile unit
-    for cu in &compile_units {
+    for (cu, &input_index) in compile_units.iter().zip(&cu_inputs) {
+        let input = &inputs[input_index];
         // Group elements by declaration file, properly handling namespaces
         // by splitting their children by decl_file
         let elements_by_file = group_elements_by_file(&cu.elements);
@@ -286,9 +560,10 @@ fn main() -> Result<()> {
         // We collect owned elements so we can merge namespaces with the same name
         let mut main_elements_owned: Vec<types::Element> = Vec::new();
 
-        // Add elements without decl_file
+        // Add elements without decl_file, except the prototypes in externs.h
         if let Some(elems) = elements_by_file.get(&None) {
-            main_elements_owned.extend(elems.iter().cloned());
+            let (_, others) = partition_extern_prototypes(elems.clone());
+            main_elements_owned.extend(others);
         }
 
         // Add elements with decl_file = 0 (some DWARF producers use 0 for the CU file)
@@ -319,7 +594,7 @@ fn main() -> Result<()> {
         let main_elements: Vec<&types::Element> = main_elements_merged.iter().collect();
 
         if !main_elements.is_empty() || elements_by_file.is_empty() {
-            let mut generator = CodeGenerator::with_config(type_sizes.clone(), config.clone());
+            let mut generator = CodeGenerator::with_config(config.clone());
 
             // Generate the compile unit with only the elements that belong to it
             if !main_elements.is_empty() {
@@ -329,7 +604,13 @@ fn main() -> Result<()> {
                 generator.generate_compile_unit(cu);
             }
 
-            let output_path = output_dir.join(&cu_path_normalized);
+            let output_path = if per_input_dirs {
+                output_dir
+                    .join(&input.output_subdir)
+                    .join(&cu_path_normalized)
+            } else {
+                output_dir.join(&cu_path_normalized)
+            };
 
             // Create parent directories if they don't exist
             if let Some(parent) = output_path.parent() {
@@ -1,46 +1,53 @@
 //! DWARF attribute extraction helpers
 
 use crate::types::*;
-use gimli::{AttributeValue, DebuggingInformationEntry, Dwarf, Reader};
+use gimli::{AttributeValue, DebuggingInformationEntry, Dwarf, Reader, Section};
+
+use super::die_index::{self, TypeSignatures};
+
+/// The value of a subrange's DW_AT_lower_bound, DW_AT_upper_bound or
+/// DW_AT_count
+pub enum BoundValue<'r> {
+    Constant(i64),
+    /// The DIE of a variable holding the bound, by absolute offset
+    Reference(usize),
+    /// A DWARF expression computing the bound at run time
+    Expression(gimli::Expression<DwarfReader<'r>>),
+}
 
 /// Helper trait for extracting DWARF attributes from entries
 pub struct AttributeExtractor<'a> {
     dwarf: &'a Dwarf<DwarfReader<'a>>,
+    signatures: &'a TypeSignatures,
 }
 
 impl<'a> AttributeExtractor<'a> {
-    pub fn new(dwarf: &'a Dwarf<DwarfReader<'a>>) -> Self {
-        Self { dwarf }
+    pub fn new(dwarf: &'a Dwarf<DwarfReader<'a>>, signatures: &'a TypeSignatures) -> Self {
+        Self { dwarf, signatures }
     }
 
+    /// Read a string attribute in any of its forms (inline, `.debug_str`,
+    /// `.debug_line_str`, the supplementary file's `.debug_str`, or indexed
+    /// through the unit's str_offsets_base)
     pub fn get_string_attr(
         &self,
-        _unit: &DwarfUnit,
+        unit: &DwarfUnit,
         entry: &DebuggingInformationEntry<DwarfReader>,
         attr: gimli::DwAt,
     ) -> Option<String> {
-        if let Some(attr_value) = entry.attr(attr).ok()? {
-            match attr_value.value() {
-                AttributeValue::String(s) => {
-                    if let Ok(slice) = s.to_slice() {
-                        return Some(String::from_utf8_lossy(&slice).to_string());
-                    }
-                }
-                AttributeValue::DebugStrRef(offset) => {
-                    if let Ok(s) = self.dwarf.debug_str.get_str(offset) {
-                        if let Ok(slice) = s.to_slice() {
-                            return Some(String::from_utf8_lossy(&slice).to_string());
-                        }
-                    }
-                }
-                _ => {}
-            }
-        }
-        None
+        let attr_value = entry.attr_value(attr).ok()??;
+        let s = die_index::unit_dwarf(self.dwarf, unit)
+            .attr_string(unit, attr_value)
+            .ok()?;
+        let slice = s.to_slice().ok()?;
+        Some(String::from_utf8_lossy(&slice).to_string())
     }
 
+    /// Read an unsigned constant, file index or address attribute. Indexed
+    /// addresses are resolved through the unit's addr_base.
     pub fn get_u64_attr(
         &self,
+        unit: &DwarfUnit,
         entry: &DebuggingInformationEntry<DwarfReader>,
         attr: gimli::DwAt,
     ) -> Option<u64> {
@@ -51,7 +58,9 @@ impl<'a> AttributeExtractor<'a> {
                 AttributeValue::Data2(v) => return Some(v as u64),
                 AttributeValue::Data4(v) => return Some(v as u64),
                 AttributeValue::Data8(v) => return Some(v),
-                AttributeValue::Addr(v) => return Some(v),
+                value @ (AttributeValue::Addr(_) | AttributeValue::DebugAddrIndex(_)) => {
+                    return self.dwarf.attr_address(unit, value).ok()?;
+                }
                 AttributeValue::FileIndex(v) => return Some(v),
                 _ => {}
             }
@@ -59,23 +68,51 @@ impl<'a> AttributeExtractor<'a> {
         None
     }
 
-    pub fn get_i64_attr(
+    /// Read an array bound. A 4 or 8 byte constant of all ones is -1, which
+    /// older GCCs write as the upper bound of zero-length arrays; smaller
+    /// forms hold real bounds such as 255.
+    pub fn get_bound_attr<'r>(
         &self,
-        entry: &DebuggingInformationEntry<DwarfReader>,
+        unit: &DwarfUnit,
+        entry: &DebuggingInformationEntry<DwarfReader<'r>>,
         attr: gimli::DwAt,
-    ) -> Option<i64> {
-        if let Some(attr_value) = entry.attr(attr).ok()? {
-            match attr_value.value() {
-                AttributeValue::Sdata(v) => return Some(v),
-                AttributeValue::Udata(v) => return Some(v as i64),
-                AttributeValue::Data1(v) => return Some(v as i64),
-                AttributeValue::Data2(v) => return Some(v as i64),
-                AttributeValue::Data4(v) => return Some(v as i64),
-                AttributeValue::Data8(v) => return Some(v as i64),
-                _ => {}
+    ) -> Option<BoundValue<'r>> {
+        let value = match entry.attr_value(attr).ok()?? {
+            AttributeValue::Sdata(v) => v,
+            AttributeValue::Udata(v) => v as i64,
+            AttributeValue::Data4(u32::MAX) | AttributeValue::Data8(u64::MAX) => -1,
+            AttributeValue::Data1(v) => v as i64,
+            AttributeValue::Data2(v) => v as i64,
+            AttributeValue::Data4(v) => v as i64,
+            AttributeValue::Data8(v) => v as i64,
+            AttributeValue::Exprloc(expression) => {
+                return Some(BoundValue::Expression(expression));
+            }
+            AttributeValue::Block(block) => {
+                return Some(BoundValue::Expression(gimli::Expression(block)));
+            }
+            _ => {
+                return self
+                    .get_ref_attr(unit, entry, attr)
+                    .map(BoundValue::Reference);
             }
+        };
+        Some(BoundValue::Constant(value))
+    }
+
+    /// Read DW_AT_high_pc as an address. Its constant forms are an offset
+    /// from low_pc rather than an address.
+    pub fn get_high_pc(
+        &self,
+        unit: &DwarfUnit,
+        entry: &DebuggingInformationEntry<DwarfReader>,
+        low_pc: Option<u64>,
+    ) -> Option<u64> {
+        let attr_value = entry.attr_value(gimli::DW_AT_high_pc).ok()??;
+        if let Some(address) = self.dwarf.attr_address(unit, attr_value).ok()? {
+            return Some(address);
         }
-        None
+        Some(low_pc?.wrapping_add(attr_value.udata_value()?))
     }
 
     pub fn get_bool_attr(
@@ -91,6 +128,123 @@ impl<'a> AttributeExtractor<'a> {
         false
     }
 
+    /// Whether every function of a unit has a prototype, as in C++ and
+    /// Objective-C++, for which g++ doesn't write DW_AT_prototyped
+    pub fn unit_has_prototypes(&self, unit: &DwarfUnit) -> bool {
+        let mut entries = unit.entries();
+        let Ok(Some((_, root))) = entries.next_dfs() else {
+            return false;
+        };
+        matches!(
+            root.attr_value(gimli::DW_AT_language),
+            Ok(Some(AttributeValue::Language(
+                gimli::DW_LANG_C_plus_plus
+                    | gimli::DW_LANG_C_plus_plus_03
+                    | gimli::DW_LANG_C_plus_plus_11
+                    | gimli::DW_LANG_C_plus_plus_14
+                    | gimli::DW_LANG_C_plus_plus_17
+                    | gimli::DW_LANG_C_plus_plus_20
+                    | gimli::DW_LANG_ObjC_plus_plus
+            )))
+        )
+    }
+
+    /// Whether a child of a struct, class or union is a static data member:
+    /// a DW_TAG_variable since DWARF 5, and an external or declared
+    /// DW_TAG_member before
+    pub fn is_static_member(&self, entry: &DebuggingInformationEntry<DwarfReader>) -> bool {
+        entry.tag() == gimli::DW_TAG_variable
+            || self.get_bool_attr(entry, gimli::DW_AT_declaration)
+            || self.get_bool_attr(entry, gimli::DW_AT_external)
+    }
+
+    /// The expressions of a variable's location, each with the address range
+    /// it applies to when the location is a list
+    pub fn get_location_expressions<'r>(
+        &self,
+        unit: &DwarfUnit<'r>,
+        entry: &DebuggingInformationEntry<DwarfReader<'r>>,
+    ) -> Vec<(Option<gimli::Range>, gimli::Expression<DwarfReader<'r>>)>
+    where
+        'a: 'r,
+    {
+        let Some(attr_value) = entry.attr_value(gimli::DW_AT_location).ok().flatten() else {
+            return Vec::new();
+        };
+        if let AttributeValue::Exprloc(expression) = attr_value {
+            return vec![(None, expression)];
+        }
+        let Ok(Some(mut locations)) =
+            die_index::unit_dwarf(self.dwarf, unit).attr_locations(unit, attr_value)
+        else {
+            return Vec::new();
+        };
+        let mut expressions = Vec::new();
+        while let Ok(Some(location)) = locations.next() {
+            expressions.push((Some(location.range), location.data));
+        }
+        expressions
+    }
+
+    /// The register a parameter arrives in: its only location, or the one
+    /// its location list gives at the function's entry point
+    pub fn get_entry_register(
+        &self,
+        unit: &DwarfUnit,
+        entry: &DebuggingInformationEntry<DwarfReader>,
+        low_pc: Option<u64>,
+    ) -> Option<u16> {
+        let (_, expression) = self
+            .get_location_expressions(unit, entry)
+            .into_iter()
+            .find(|(range, _)| match range {
+                None => true,
+                Some(range) => Some(range.begin) == low_pc,
+            })?;
+        single_register(expression, unit.header.encoding())
+    }
+
+    /// The registers a variable or parameter is held in over its scope
+    pub fn get_held_registers(
+        &self,
+        unit: &DwarfUnit,
+        entry: &DebuggingInformationEntry<DwarfReader>,
+    ) -> Vec<u16> {
+        self.get_location_expressions(unit, entry)
+            .into_iter()
+            .filter_map(|(_, expression)| single_register(expression, unit.header.encoding()))
+            .collect()
+    }
+
+    /// The address a variable lives at, or that a pointer template argument
+    /// holds, if its location is a single DW_OP_addr
+    pub fn get_location_address(
+        &self,
+        unit: &DwarfUnit,
+        entry: &DebuggingInformationEntry<DwarfReader>,
+    ) -> Option<u64> {
+        let AttributeValue::Exprloc(expression) =
+            entry.attr_value(gimli::DW_AT_location).ok()??
+        else {
+            return None;
+        };
+        let mut reader = expression.0;
+        let encoding = unit.header.encoding();
+        let address = match gimli::Operation::parse(&mut reader, encoding).ok()? {
+            gimli::Operation::Address { address } => address,
+            gimli::Operation::AddressIndex { index } => self.dwarf.address(unit, index).ok()?,
+            _ => return None,
+        };
+        // A value rather than a location, as for template arguments
+        if !reader.is_empty() {
+            match gimli::Operation::parse(&mut reader, encoding).ok()? {
+                gimli::Operation::StackValue if reader.is_empty() => {}
+                _ => return None,
+            }
+        }
+        Some(address)
+    }
+
     pub fn get_member_offset(
         &self,
         unit: &DwarfUnit,
@@ -119,6 +273,42 @@ impl<'a> AttributeExtractor<'a> {
         None
     }
 
+    /// The position of a bitfield's first bit from the start of its struct.
+    /// DW_AT_data_bit_offset already is one; the older DW_AT_bit_offset counts
+    /// from the most significant bit of a storage unit of DW_AT_byte_size
+    /// bytes (or `type_size`) at the member's DW_AT_data_member_location.
+    pub fn get_bit_position(
+        &self,
+        unit: &DwarfUnit,
+        entry: &DebuggingInformationEntry<DwarfReader>,
+        bit_size: u64,
+        type_size: Option<u64>,
+    ) -> Option<u64> {
+        if let Some(position) = self.get_u64_attr(unit, entry, gimli::DW_AT_data_bit_offset) {
+            return Some(position);
+        }
+
+        // GCC writes a negative offset for fields of packed structs that
+        // extend past the end of their storage unit
+        let bit_offset = match entry.attr_value(gimli::DW_AT_bit_offset).ok()?? {
+            AttributeValue::Sdata(v) => v,
+            _ => self.get_u64_attr(unit, entry, gimli::DW_AT_bit_offset)? as i64,
+        };
+        let storage_size = self
+            .get_u64_attr(unit, entry, gimli::DW_AT_byte_size)
+            .or(type_size)?;
+        let unit_start = (self.get_member_offset(unit, entry).unwrap_or(0) * 8) as i64;
+        let position = match self.dwarf.debug_info.reader().endian() {
+            gimli::RunTimeEndian::Big => unit_start + bit_offset,
+            gimli::RunTimeEndian::Little => {
+                unit_start + (storage_size * 8) as i64 - bit_offset - bit_size as i64
+            }
+        };
+        u64::try_from(position).ok()
+    }
+
+    /// Read a reference attribute as an absolute `.debug_info` offset, so
+    /// that references into other units can be followed through the DIE index
     pub fn get_ref_attr(
         &self,
         unit: &DwarfUnit,
@@ -127,17 +317,24 @@ impl<'a> AttributeExtractor<'a> {
     ) -> Option<usize> {
         if let Some(attr_value) = entry.attr(attr).ok()? {
             match attr_value.value() {
-                AttributeValue::UnitRef(offset) => return Some(offset.0),
+                AttributeValue::UnitRef(offset) => {
+                    return Some(unit_base_offset(unit) + offset.0);
+                }
+                // DW_FORM_ref_addr is already absolute and may point into any
+                // unit of the same file
                 AttributeValue::DebugInfoRef(offset) => {
-                    // This is an absolute reference (DW_FORM_ref_addr)
-                    // Convert to unit-relative offset if within this unit
-                    let unit_base = unit_base_offset(unit);
-                    if offset.0 >= unit_base {
-                        return Some(offset.0 - unit_base);
+                    if is_supplementary_unit(unit) {
+                        return Some(SUPPLEMENTARY_OFFSET_BASE + offset.0);
                     }
-                    // If it's before our unit, it's a cross-unit reference
-                    // which we can't resolve with unit-relative offsets
-                    // Return None and let the caller handle it
+                    return Some(offset.0);
+                }
+                // DW_FORM_GNU_ref_alt points into the supplementary file
+                AttributeValue::DebugInfoRefSup(offset) => {
+                    return Some(SUPPLEMENTARY_OFFSET_BASE + offset.0);
+                }
+                // DW_FORM_ref_sig8 names the type unit defining the type
+                AttributeValue::DebugTypesRef(signature) => {
+                    return self.signatures.get(&signature).copied();
                 }
                 _ => {}
             }
@@ -179,4 +376,32 @@ impl<'a> AttributeExtractor<'a> {
         }
         None
     }
+
+    /// Read a constant of a signed type. Fixed-size forms hold the value's
+    /// bits, as clang writes -1 as `DW_FORM_data1 0xff`.
+    pub fn get_signed_const_value(
+        &self,
+        entry: &DebuggingInformationEntry<DwarfReader>,
+    ) -> Option<ConstValue> {
+        let value = match entry.attr_value(gimli::DW_AT_const_value).ok()?? {
+            AttributeValue::Data1(v) => v as i8 as i64,
+            AttributeValue::Data2(v) => v as i16 as i64,
+            AttributeValue::Data4(v) => v as i32 as i64,
+            AttributeValue::Data8(v) => v as i64,
+            _ => return self.get_const_value(entry),
+        };
+        Some(ConstValue::Signed(value))
+    }
+}
+
+/// The register a location expression consisting of a single DW_OP_reg names
+fn single_register(
+    expression: gimli::Expression<DwarfReader>,
+    encoding: gimli::Encoding,
+) -> Option<u16> {
+    let mut reader = expression.0;
+    match gimli::Operation::parse(&mut reader, encoding).ok()? {
+        gimli::Operation::Register { register } if reader.is_empty() => Some(register.0),
+        _ => None,
+    }
 }
@@ -0,0 +1,167 @@
+//! Index of the units in `.debug_info` and `.debug_types`
+//!
+//! References in the `DW_FORM_ref_addr` form hold an absolute `.debug_info`
+//! offset and may point into any unit. GCC's LTO output relies on this: the
+//! units holding code refer back into the early-debug units for names, types
+//! and declarations. The index finds the unit containing such an offset.
+//!
+//! Type units (`-fdebug-types-section`) are referred to by their 8-byte
+//! signature instead (`DW_FORM_ref_sig8`), which the index maps to the
+//! absolute offset of the type they define.
+//!
+//! Debug info compressed with `dwz` moves DIEs shared between programs into a
+//! supplementary file named by `.gnu_debugaltlink`, and refers to them with
+//! `DW_FORM_GNU_ref_alt`. The supplementary file's units are indexed at
+//! offsets starting from [`SUPPLEMENTARY_OFFSET_BASE`].
+
+use crate::error::Result;
+use crate::types::*;
+use gimli::{DebugTypeSignature, Dwarf, UnitHeader};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Absolute offsets of the types defined by type units, by signature
+pub type TypeSignatures = HashMap<DebugTypeSignature, usize>;
+
+/// All units of a DWARF file, sorted by absolute offset
+pub struct DieIndex<'a> {
+    units: Vec<Arc<DwarfUnit<'a>>>,
+    signatures: TypeSignatures,
+}
+
+impl<'a> DieIndex<'a> {
+    /// Load every unit of `dwarf`, including DWARF 4 type units and the units
+    /// of its supplementary file.
+    ///
+    /// For a split DWARF file, the unit matching `skeleton` gets the
+    /// skeleton's address, range and location list bases.
+    pub fn new(dwarf: &Dwarf<DwarfReader<'a>>, skeleton: Option<&DwarfUnit<'a>>) -> Result<Self> {
+        let mut units = Vec::new();
+        let mut headers = dwarf.units();
+        while let Some(header) = headers.next()? {
+            let mut unit = dwarf.unit(header)?;
+            if let Some(skeleton) = skeleton {
+                if unit.dwo_id.is_some() && unit.dwo_id == skeleton.dwo_id {
+                    unit.copy_relocated_attributes(skeleton);
+                }
+            }
+            units.push(Arc::new(unit));
+        }
+        let mut headers = dwarf.type_units();
+        while let Some(header) = headers.next()? {
+            units.push(Arc::new(dwarf.unit(header)?));
+        }
+        if let Some(sup) = dwarf.sup() {
+            let mut headers = sup.units();
+            while let Some(header) = headers.next()? {
+                units.push(Arc::new(sup.unit(supplementary_header(header)?)?));
+            }
+        }
+        units.sort_by_key(|unit| unit_base_offset(unit));
+
+        let mut signatures = TypeSignatures::new();
+        for unit in &units {
+            if let gimli::UnitType::Type {
+                type_signature,
+                type_offset,
+            }
+            | gimli::UnitType::SplitType {
+                type_signature,
+                type_offset,
+            } = unit.header.type_()
+            {
+                signatures
+                    .entry(type_signature)
+                    .or_insert(unit_base_offset(unit) + type_offset.0);
+            }
+        }
+
+        Ok(Self { units, signatures })
+    }
+
+    /// The units in order of their absolute offsets
+    pub fn units(&self) -> &[Arc<DwarfUnit<'a>>] {
+        &self.units
+    }
+
+    /// Type unit signatures, for resolving `DW_FORM_ref_sig8` references
+    pub fn signatures(&self) -> &TypeSignatures {
+        &self.signatures
+    }
+
+    /// The unit whose header starts at an absolute offset
+    pub fn unit_at(&self, unit_offset: usize) -> Option<Arc<DwarfUnit<'a>>> {
+        self.units
+            .binary_search_by_key(&unit_offset, |unit| unit_base_offset(unit))
+            .ok()
+            .map(|index| Arc::clone(&self.units[index]))
+    }
+
+    /// Find the unit holding the DIE at an absolute offset, along with the
+    /// DIE's offset within that unit
+    pub fn lookup(&self, offset: usize) -> Option<(Arc<DwarfUnit<'a>>, gimli::UnitOffset)> {
+        let index = self
+            .units
+            .partition_point(|unit| unit_base_offset(unit) <= offset)
+            .checked_sub(1)?;
+        let unit = &self.units[index];
+        let unit_offset = to_unit_offset(unit, offset)?;
+        Some((Arc::clone(unit), unit_offset))
+    }
+}
+
+/// Whether decl_file indices of the two units index the same file table,
+/// as for a compile unit and the type units GCC emits alongside it
+pub fn shares_file_table(a: &DwarfUnit, b: &DwarfUnit) -> bool {
+    if unit_base_offset(a) == unit_base_offset(b) {
+        return true;
+    }
+    // The supplementary file has a `.debug_line` of its own
+    if is_supplementary_unit(a) != is_supplementary_unit(b) {
+        return false;
+    }
+    match (&a.line_program, &b.line_program) {
+        (Some(a), Some(b)) => a.header().offset() == b.header().offset(),
+        _ => false,
+    }
+}
+
+/// The DWARF sections a unit's string and line table references point into
+pub fn unit_dwarf<'d, 'a>(
+    dwarf: &'d Dwarf<DwarfReader<'a>>,
+    unit: &DwarfUnit,
+) -> &'d Dwarf<DwarfReader<'a>> {
+    match dwarf.sup() {
+        Some(sup) if is_supplementary_unit(unit) => sup,
+        _ => dwarf,
+    }
+}
+
+/// Rebase a header of the supplementary file to its range of absolute offsets
+fn supplementary_header<'a>(
+    header: UnitHeader<DwarfReader<'a>>,
+) -> Result<UnitHeader<DwarfReader<'a>>> {
+    let offset = match header.offset() {
+        gimli::UnitSectionOffset::DebugInfoOffset(offset) => offset.0,
+        gimli::UnitSectionOffset::DebugTypesOffset(offset) => offset.0,
+    };
+    let entries = header.range_from(gimli::UnitOffset(header.header_size())..)?;
+    Ok(UnitHeader::new(
+        header.encoding(),
+        header.unit_length(),
+        header.type_(),
+        header.debug_abbrev_offset(),
+        gimli::DebugInfoOffset(SUPPLEMENTARY_OFFSET_BASE + offset).into(),
+        entries,
+    ))
+}
+
+/// Convert an absolute offset to one relative to `unit`, if it lies within
+/// the unit's entries
+fn to_unit_offset(unit: &DwarfUnit, offset: usize) -> Option<gimli::UnitOffset> {
+    let relative = offset.checked_sub(unit_base_offset(unit))?;
+    if relative < unit.header.header_size() || relative >= unit.header.length_including_self() {
+        return None;
+    }
+    Some(gimli::UnitOffset(relative))
+}
@@ -29,19 +29,17 @@ pub fn cross_cu_match_method_definitions(compile_units: &mut [CompileUnit]) {
 fn collect_definitions(elements: &[Element], definitions: &mut HashMap<String, MethodDefinition>) {
     for element in elements {
         match element {
-            Element::Function(func) => {
-                // Index any function that has parameters and a linkage name.
-                // This handles both:
-                // 1. Method definitions with specification_offset
-                // 2. Functions that might match method declarations across CUs
-                //    (where the definition may not have is_method set if the class
-                //    declaration is in a different CU)
-                if !func.parameters.is_empty() {
-                    if let Some(ref linkage_name) = func.linkage_name {
-                        definitions
-                            .entry(linkage_name.clone())
-                            .or_insert_with(|| MethodDefinition::from_function(func));
-                    }
+            // Index any function definition that has parameters and a linkage name.
+            // This handles both:
+            // 1. Method definitions with specification_offset
+            // 2. Functions that might match method declarations across CUs
+            //    (where the definition may not have is_method set if the class
+            //    declaration is in a different CU)
+            Element::Function(func) if func.has_body && !func.parameters.is_empty() => {
+                if let Some(ref linkage_name) = func.linkage_name {
+                    definitions
+                        .entry(linkage_name.clone())
+                        .or_insert_with(|| MethodDefinition::from_function(func));
                 }
             }
             Element::Namespace(ns) => {
@@ -181,7 +179,8 @@ fn collect_method_definitions(
 ) {
     for element in elements.iter() {
         match element {
-            Element::Function(func) => {
+            // Prototypes of functions defined elsewhere have nothing to apply
+            Element::Function(func) if func.has_body => {
                 let definition_data = MethodDefinition::from_function(func);
 
                 // If this function has a specification_offset, it's a definition referencing a declaration
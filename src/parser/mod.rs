@@ -4,202 +4,202 @@
 //! from ELF files and extract C/C++ type definitions and function signatures.
 
 mod attributes;
+mod die_index;
 mod method_matcher;
+mod partial_units;
+mod relocations;
+mod sections;
+mod split_dwarf;
 mod type_resolver;
+mod type_units;
 
 use crate::error::Result;
 use crate::types::*;
 use gimli::{AttributeValue, DebuggingInformationEntry, Dwarf, Reader};
-use object::{Object, ObjectSection, ObjectSymbol};
-use std::borrow::Cow;
-use std::collections::HashMap;
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
 use attributes::AttributeExtractor;
+use die_index::DieIndex;
+use sections::SectionStore;
 use type_resolver::TypeResolver;
 
 // Re-export the cross-CU matching function for use in main.rs
 pub use method_matcher::cross_cu_match_method_definitions;
+pub use sections::map_file;
+pub use split_dwarf::PackageFile;
+
+/// Configuration for DWARF parsing
+#[derive(Debug, Clone, Default)]
+pub struct ParserConfig {
+    /// Extra directories searched for split DWARF (.dwo) files
+    pub dwo_search_paths: Vec<PathBuf>,
+    /// DWARF package (.dwp) holding the split units of the input
+    pub dwp: Option<Arc<PackageFile>>,
+    /// Supplementary file (from dwz) that the input's `.gnu_debugaltlink` names
+    pub supplementary_path: Option<PathBuf>,
+}
 
-/// Apply relocations to a DWARF section
-fn apply_relocations<'a>(
-    object_file: &object::File,
-    section_data: &'a [u8],
-    section_name: &str,
-) -> Cow<'a, [u8]> {
-    use object::RelocationTarget;
-
-    // Get the DWARF section to access its relocations
-    let dwarf_section = match object_file.section_by_name(section_name) {
-        Some(section) => section,
-        None => return Cow::Borrowed(section_data),
-    };
-
-    // Clone the section data so we can modify it
-    let mut data = section_data.to_vec();
-
-    // Get relocations from the DWARF section
-    for (offset, relocation) in dwarf_section.relocations() {
-        let offset = offset as usize;
-
-        // Get the value to add (from the symbol or addend)
-        let value: u64 = match relocation.target() {
-            RelocationTarget::Symbol(symbol_idx) => {
-                if let Ok(symbol) = object_file.symbol_by_index(symbol_idx) {
-                    // For section symbols, use the section's address (0 for object files)
-                    // plus the addend
-                    if symbol.kind() == object::SymbolKind::Section {
-                        relocation.addend() as u64
-                    } else {
-                        symbol.address().wrapping_add(relocation.addend() as u64)
-                    }
-                } else {
-                    relocation.addend() as u64
-                }
-            }
-            _ => relocation.addend() as u64,
-        };
-
-        // Apply the relocation based on its type
-        use object::RelocationKind;
-        match relocation.kind() {
-            RelocationKind::Absolute if relocation.size() == 32 => {
-                // R_X86_64_32: S + A (32-bit absolute)
-                if offset + 4 <= data.len() {
-                    let bytes = (value as u32).to_le_bytes();
-                    data[offset..offset + 4].copy_from_slice(&bytes);
-                }
-            }
-            RelocationKind::Absolute if relocation.size() == 64 => {
-                // R_X86_64_64: S + A (64-bit absolute)
-                if offset + 8 <= data.len() {
-                    let bytes = value.to_le_bytes();
-                    data[offset..offset + 8].copy_from_slice(&bytes);
-                }
-            }
-            _ => {
-                // Ignore other relocation types
-            }
-        }
-    }
-
-    Cow::Owned(data)
+/// The metadata gathered from one unit in the first pass
+#[derive(Default)]
+struct UnitMetadata {
+    typedefs: Vec<(usize, TypedefInfo)>,
+    abstract_origins: Vec<(usize, String)>,
+    concrete_ranges: Vec<(usize, (u64, Option<u64>))>,
+    specified_functions: Vec<usize>,
+    static_members: Vec<(usize, String)>,
+    type_unit_refs: Vec<usize>,
 }
 
 pub struct DwarfParser<'a> {
     dwarf: Dwarf<DwarfReader<'a>>,
-    type_cache: HashMap<usize, TypeInfo>,
+    index: DieIndex<'a>,
+    // Shared by the threads parsing units; entries depend only on the offset
+    type_cache: Mutex<HashMap<usize, TypeInfo>>,
+    layout_cache: Mutex<HashMap<usize, Option<TypeLayout>>>,
     typedef_map: HashMap<usize, TypedefInfo>,
     abstract_origins: HashMap<usize, String>,
+    // Address ranges of out-of-line concrete instances, keyed by the
+    // absolute offset of their abstract origin
+    concrete_ranges: HashMap<usize, (u64, Option<u64>)>,
+    // Function declarations completed by a definition's DW_AT_specification,
+    // by absolute offset
+    specified_functions: HashSet<usize>,
+    // Qualified names of the classes declaring static data members, by the
+    // absolute offset of the member's declaration
+    static_member_classes: HashMap<usize, String>,
+    // Type units each unit refers to by signature, by absolute unit offset
+    type_unit_refs: HashMap<usize, Vec<usize>>,
+    // Names of the functions and variables in the symbol tables of a linked
+    // input, by address and whether they are functions. None where several
+    // share an address.
+    address_symbols: HashMap<(u64, bool), Option<String>>,
+    config: ParserConfig,
     // Keep the relocated section data alive in stable heap allocations
-    _section_data: Vec<Box<[u8]>>,
+    _section_data: SectionStore,
 }
 
 #[allow(dead_code)] // Some parser methods are called via offset-based parsing
 #[allow(clippy::too_many_arguments)] // Parser methods need many parameters from DWARF
 #[allow(clippy::while_let_loop)] // Some loops are clearer with explicit match
 impl<'a> DwarfParser<'a> {
+    #[allow(dead_code)]
     pub fn new(file_data: &'a [u8]) -> Result<Self> {
+        Self::with_config(file_data, ParserConfig::default())
+    }
+
+    pub fn with_config(file_data: &'a [u8], config: ParserConfig) -> Result<Self> {
         let object = object::File::parse(file_data)?;
 
-        // Pre-load and relocate all sections, storing them in stable heap allocations
-        let mut section_data_map: HashMap<&str, Box<[u8]>> = HashMap::new();
-
-        let section_ids = [
-            gimli::SectionId::DebugAbbrev,
-            gimli::SectionId::DebugInfo,
-            gimli::SectionId::DebugLine,
-            gimli::SectionId::DebugStr,
-            gimli::SectionId::DebugStrOffsets,
-            gimli::SectionId::DebugTypes,
-            gimli::SectionId::DebugLoc,
-            gimli::SectionId::DebugLocLists,
-            gimli::SectionId::DebugRanges,
-            gimli::SectionId::DebugRngLists,
-            gimli::SectionId::DebugAddr,
-            gimli::SectionId::DebugLineStr,
-        ];
-
-        // Preload all sections that need relocation, storing in stable Box allocations
-        for &id in &section_ids {
-            let section_data = object
-                .section_by_name(id.name())
-                .and_then(|section| section.uncompressed_data().ok())
-                .unwrap_or(Cow::Borrowed(&[]));
-
-            let relocated_data = apply_relocations(&object, &section_data, id.name());
-            if let Cow::Owned(data) = relocated_data {
-                section_data_map.insert(id.name(), data.into_boxed_slice());
-            } else if let Cow::Owned(data) = section_data {
-                // Section was decompressed but not relocated - still need to store it
-                section_data_map.insert(id.name(), data.into_boxed_slice());
-            }
-        }
+        // Relocated and decompressed sections are stored in stable heap allocations
+        let mut store = SectionStore::new();
 
-        // Now load sections with proper references
-        // Box ensures the data address is stable even when moved
-        let load_section =
-            |id: gimli::SectionId| -> std::result::Result<DwarfReader<'a>, gimli::Error> {
-                // First check if we have pre-stored data (decompressed and/or relocated)
-                let data_ref: &'a [u8] = if let Some(boxed_data) = section_data_map.get(id.name()) {
-                    // SAFETY: We're extending the lifetime of the reference from the Box to 'a.
-                    // This is safe because:
-                    // 1. section_data_map contains the data in Box (stable address)
-                    // 2. The Box data will be moved into the DwarfParser struct
-                    // 3. The DwarfParser has lifetime 'a
-                    // 4. The references won't outlive the parser
-                    unsafe { std::slice::from_raw_parts(boxed_data.as_ptr(), boxed_data.len()) }
-                } else {
-                    // No pre-stored data, use raw section data (for non-compressed, non-relocated sections)
-                    object
-                        .section_by_name(id.name())
-                        .and_then(|section| section.data().ok())
-                        .unwrap_or(&[])
-                };
+        // A .dwo file given directly only has the split variants of the sections
+        let split = sections::is_split_dwarf_object(&object);
+        let dwarf = sections::load_dwarf(&object, split, &mut store)?;
 
-                Ok(gimli::EndianSlice::new(data_ref, gimli::LittleEndian))
-            };
+        Self::with_dwarf(dwarf, store, config, &[&object])
+    }
+
+    /// Parse the separate debug file of a stripped binary. Sections and
+    /// symbols missing from the debug file are taken from the binary.
+    pub fn with_debug_file(
+        binary_data: &'a [u8],
+        debug_data: &'a [u8],
+        config: ParserConfig,
+    ) -> Result<Self> {
+        let binary = object::File::parse(binary_data)?;
+        let debug_file = object::File::parse(debug_data)?;
+
+        let mut store = SectionStore::new();
+        let dwarf = sections::load_separate_dwarf(&debug_file, &binary, &mut store)?;
+        Self::with_dwarf(dwarf, store, config, &[&debug_file, &binary])
+    }
 
-        let dwarf = Dwarf::load(load_section)?;
+    /// Wrap the DWARF sections of the input, adding the supplementary file's
+    /// and the symbols of `object_files`
+    fn with_dwarf(
+        mut dwarf: Dwarf<DwarfReader<'a>>,
+        mut store: SectionStore,
+        config: ParserConfig,
+        object_files: &[&object::File],
+    ) -> Result<Self> {
+        if let Some(supplementary_path) = &config.supplementary_path {
+            let sup_data = sections::store_mapped_file(&mut store, supplementary_path)?;
+            let sup_object = object::File::parse(sup_data)?;
+            dwarf.set_sup(sections::load_dwarf(&sup_object, false, &mut store)?);
+        }
 
-        // Convert HashMap values to Vec for storage
-        let section_data_storage: Vec<Box<[u8]>> = section_data_map.into_values().collect();
+        let mut parser = Self::from_dwarf(dwarf, store, config, None)?;
+        parser.address_symbols = address_symbols(object_files);
+        Ok(parser)
+    }
 
+    /// Wrap already-loaded DWARF sections, backed by `store`. The split unit
+    /// of `skeleton`, if given, is set up with the skeleton's bases.
+    fn from_dwarf(
+        dwarf: Dwarf<DwarfReader<'a>>,
+        store: SectionStore,
+        config: ParserConfig,
+        skeleton: Option<&DwarfUnit<'a>>,
+    ) -> Result<Self> {
+        let index = DieIndex::new(&dwarf, skeleton)?;
         Ok(DwarfParser {
             dwarf,
-            type_cache: HashMap::new(),
+            index,
+            type_cache: Mutex::new(HashMap::new()),
+            layout_cache: Mutex::new(HashMap::new()),
             typedef_map: HashMap::new(),
             abstract_origins: HashMap::new(),
-            _section_data: section_data_storage,
+            concrete_ranges: HashMap::new(),
+            specified_functions: HashSet::new(),
+            static_member_classes: HashMap::new(),
+            type_unit_refs: HashMap::new(),
+            address_symbols: HashMap::new(),
+            config,
+            _section_data: store,
         })
     }
 
+    /// Parse every compile unit, in order. Units are parsed on the rayon
+    /// thread pool.
+    ///
+    /// Cross-CU method matching is left to the caller, which runs it once
+    /// over the compile units of all inputs.
     pub fn parse(&mut self) -> Result<Vec<CompileUnit>> {
-        let mut compile_units = Vec::new();
-
-        // First pass: collect typedefs and abstract origins
-        let mut units = self.dwarf.units();
-        while let Some(header) = units.next()? {
-            let unit = self.dwarf.unit(header)?;
-            self.collect_metadata(&unit)?;
+        let units: Vec<Arc<DwarfUnit<'a>>> = self.index.units().to_vec();
+
+        // First pass: collect typedefs and abstract origins. Units are read in
+        // parallel and merged in order, as if they had been read serially.
+        let metadata = units
+            .par_iter()
+            .map(|unit| self.unit_metadata(unit))
+            .collect::<Result<Vec<_>>>()?;
+        for (unit, unit_metadata) in units.iter().zip(metadata) {
+            self.collect_metadata(unit_base_offset(unit), unit_metadata);
         }
 
         // Second pass: parse compile units
-        let mut units = self.dwarf.units();
-        while let Some(header) = units.next()? {
-            let unit = self.dwarf.unit(header)?;
-            if let Some(mut cu) = self.parse_compile_unit(&unit)? {
-                // First do intra-CU matching (uses decl_offset/spec_offset)
-                method_matcher::match_method_definitions(&mut cu.elements);
-                compile_units.push(cu);
-            }
-        }
-
-        // Third pass: cross-CU matching for methods that couldn't be matched within their CU
-        // This handles the case where class declarations are in headers included by multiple CUs,
-        // but method definitions are only in one CU
-        cross_cu_match_method_definitions(&mut compile_units);
-
-        Ok(compile_units)
+        let parser = &*self;
+        let compile_units = units
+            .par_iter()
+            .map(|unit| {
+                let cu = if parser.is_skeleton_unit(unit) {
+                    parser.parse_split_unit(unit)?
+                } else {
+                    parser.parse_compile_unit(unit)?
+                };
+                Ok(cu.map(|mut cu| {
+                    // Intra-CU matching (uses decl_offset/spec_offset)
+                    method_matcher::match_method_definitions(&mut cu.elements);
+                    cu
+                }))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(compile_units.into_iter().flatten().collect())
     }
 
     /// Match method declarations across all CUs using linkage names.
@@ -212,32 +212,97 @@ impl<'a> DwarfParser<'a> {
     // Metadata collection
     // ========================================================================
 
-    fn collect_metadata(&mut self, unit: &DwarfUnit) -> Result<()> {
-        let attrs = AttributeExtractor::new(&self.dwarf);
+    /// Read the typedefs, abstract origins, concrete instances, static data
+    /// members and type unit references of one unit
+    fn unit_metadata(&self, unit: &DwarfUnit) -> Result<UnitMetadata> {
+        let mut metadata = UnitMetadata::default();
+        let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
         let mut entries = unit.entries();
 
         // Get unit base offset for converting to absolute offsets
         let unit_base = unit_base_offset(unit);
 
-        while let Some((_, entry)) = entries.next_dfs()? {
+        // The tag and name of each entry enclosing the current one
+        let mut scopes: Vec<(gimli::DwTag, Option<String>)> = Vec::new();
+        let mut depth = 0;
+
+        while let Some((depth_delta, entry)) = entries.next_dfs()? {
             let abs_offset = unit_base + entry.offset().0;
+            depth += depth_delta;
+            scopes.truncate(depth.max(0) as usize);
+
+            // Collect static data members with the class declaring them
+            let in_compound = matches!(
+                scopes.last(),
+                Some((
+                    gimli::DW_TAG_structure_type
+                        | gimli::DW_TAG_class_type
+                        | gimli::DW_TAG_union_type,
+                    _
+                ))
+            );
+            if in_compound
+                && matches!(entry.tag(), gimli::DW_TAG_member | gimli::DW_TAG_variable)
+                && attrs.is_static_member(entry)
+            {
+                // Members of unnamed namespaces are named without them;
+                // unnamed classes can't be named at all
+                let names: Option<Vec<&str>> = scopes
+                    .iter()
+                    .filter(|(tag, name)| match *tag {
+                        gimli::DW_TAG_namespace => name.is_some(),
+                        gimli::DW_TAG_structure_type
+                        | gimli::DW_TAG_class_type
+                        | gimli::DW_TAG_union_type => true,
+                        _ => false,
+                    })
+                    .map(|(_, name)| name.as_deref())
+                    .collect();
+                if let Some(names) = names {
+                    metadata.static_members.push((abs_offset, names.join("::")));
+                }
+            }
+            let scope_name = match entry.tag() {
+                gimli::DW_TAG_namespace
+                | gimli::DW_TAG_structure_type
+                | gimli::DW_TAG_class_type
+                | gimli::DW_TAG_union_type => attrs.get_string_attr(unit, entry, gimli::DW_AT_name),
+                _ => None,
+            };
+            scopes.push((entry.tag(), scope_name));
+
+            // Collect the type units this unit uses
+            if !self.index.signatures().is_empty() {
+                for type_unit in self.referenced_type_units(entry)? {
+                    let refs = &mut metadata.type_unit_refs;
+                    if type_unit != unit_base && !refs.contains(&type_unit) {
+                        refs.push(type_unit);
+                    }
+                }
+            }
 
             // Collect typedefs
             if entry.tag() == gimli::DW_TAG_typedef {
                 if let Some(name) = attrs.get_string_attr(unit, entry, gimli::DW_AT_name) {
-                    let line = attrs.get_u64_attr(entry, gimli::DW_AT_decl_line);
-                    let decl_file = attrs.get_u64_attr(entry, gimli::DW_AT_decl_file);
-                    if let Some(type_offset) = attrs.get_ref_attr(unit, entry, gimli::DW_AT_type) {
-                        // Convert to absolute offset
-                        let abs_type_offset = unit_base + type_offset;
-                        self.typedef_map.insert(
-                            abs_type_offset,
+                    let line = attrs.get_u64_attr(unit, entry, gimli::DW_AT_decl_line);
+                    let decl_file = attrs.get_u64_attr(unit, entry, gimli::DW_AT_decl_file);
+                    // Only typedefs of types whose decl_file indexes the same
+                    // file table are merged, so their files can be compared
+                    if let Some(type_offset) = attrs
+                        .get_ref_attr(unit, entry, gimli::DW_AT_type)
+                        .filter(|&offset| match self.index.lookup(offset) {
+                            Some((type_unit, _)) => die_index::shares_file_table(unit, &type_unit),
+                            None => false,
+                        })
+                    {
+                        metadata.typedefs.push((
+                            type_offset,
                             TypedefInfo {
                                 name,
                                 line,
                                 decl_file,
                             },
-                        );
+                        ));
                     }
                 }
             }
@@ -245,20 +310,54 @@ impl<'a> DwarfParser<'a> {
             // Collect abstract origins (for inlined functions)
             if entry.tag() == gimli::DW_TAG_subprogram {
                 if let Some(name) = attrs.get_string_attr(unit, entry, gimli::DW_AT_name) {
-                    self.abstract_origins.insert(abs_offset, name);
+                    metadata.abstract_origins.push((abs_offset, name));
+                }
+
+                // Out-of-line instances of inline functions, and the code GCC's
+                // LTO emits for functions described in its early-debug units,
+                // only carry an abstract origin and the address range
+                if let Some(origin) = attrs.get_ref_attr(unit, entry, gimli::DW_AT_abstract_origin)
+                {
+                    let low_pc = attrs.get_u64_attr(unit, entry, gimli::DW_AT_low_pc);
+                    if let Some(low_pc) = low_pc {
+                        let high_pc = attrs.get_high_pc(unit, entry, Some(low_pc));
+                        metadata.concrete_ranges.push((origin, (low_pc, high_pc)));
+                    }
+                }
+
+                if let Some(declaration) =
+                    attrs.get_ref_attr(unit, entry, gimli::DW_AT_specification)
+                {
+                    metadata.specified_functions.push(declaration);
                 }
             }
         }
 
-        Ok(())
+        Ok(metadata)
+    }
+
+    /// Add the metadata read from the unit at `unit_base`
+    fn collect_metadata(&mut self, unit_base: usize, metadata: UnitMetadata) {
+        self.typedef_map.extend(metadata.typedefs);
+        self.abstract_origins.extend(metadata.abstract_origins);
+        for (origin, range) in metadata.concrete_ranges {
+            self.concrete_ranges.entry(origin).or_insert(range);
+        }
+        self.specified_functions
+            .extend(metadata.specified_functions);
+        self.static_member_classes.extend(metadata.static_members);
+        if !metadata.type_unit_refs.is_empty() {
+            self.type_unit_refs
+                .insert(unit_base, metadata.type_unit_refs);
+        }
     }
 
     // ========================================================================
     // Compile unit parsing
     // ========================================================================
 
-    fn parse_compile_unit(&mut self, unit: &DwarfUnit) -> Result<Option<CompileUnit>> {
-        let attrs = AttributeExtractor::new(&self.dwarf);
+    fn parse_compile_unit(&self, unit: &DwarfUnit) -> Result<Option<CompileUnit>> {
+        let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
         let mut entries = unit.entries();
 
         if let Some((_, entry)) = entries.next_dfs()? {
@@ -269,9 +368,12 @@ impl<'a> DwarfParser<'a> {
                 let producer = attrs.get_string_attr(unit, entry, gimli::DW_AT_producer);
 
                 // Extract file table from line program
-                let file_table = self.extract_file_table(unit, entry)?;
+                let mut file_table = self.extract_file_table(unit, entry, unit.comp_dir)?;
 
-                let mut elements = Vec::new();
+                // Types from type units come first, as they would in the unit
+                // itself, followed by the units dwz factored out of it
+                let mut elements = self.parse_type_units_for(unit, &mut file_table)?;
+                elements.extend(self.parse_imported_units_for(unit, &mut file_table)?);
                 self.parse_children(unit, &mut entries, &mut elements)?;
 
                 return Ok(Some(CompileUnit {
@@ -286,25 +388,29 @@ impl<'a> DwarfParser<'a> {
         Ok(None)
     }
 
-    /// Extract the file table from the DWARF line program
+    /// Extract the file table from the DWARF line program. Relative
+    /// directories are taken to be relative to `comp_dir`.
     fn extract_file_table(
         &self,
         unit: &DwarfUnit,
         entry: &DebuggingInformationEntry<DwarfReader>,
+        comp_dir: Option<DwarfReader>,
     ) -> Result<Vec<String>> {
         let mut file_table = Vec::new();
-
-        // Try to get the line program from DW_AT_stmt_list
-        if let Some(AttributeValue::DebugLineRef(line_offset)) = entry
-            .attr(gimli::DW_AT_stmt_list)
-            .ok()
-            .flatten()
-            .map(|a| a.value())
-        {
-            if let Ok(program) = self.dwarf.debug_line.program(
+        let dwarf = die_index::unit_dwarf(&self.dwarf, unit);
+
+        // Try to get the line program from DW_AT_stmt_list. Split units have
+        // none; their file table is the only one in .debug_line.dwo.
+        let line_offset = match entry.attr_value(gimli::DW_AT_stmt_list).ok().flatten() {
+            Some(AttributeValue::DebugLineRef(line_offset)) => Some(line_offset),
+            _ if dwarf.file_type == gimli::DwarfFileType::Dwo => Some(gimli::DebugLineOffset(0)),
+            _ => None,
+        };
+        if let Some(line_offset) = line_offset {
+            if let Ok(program) = dwarf.debug_line.program(
                 line_offset,
                 unit.header.address_size(),
-                unit.comp_dir,
+                comp_dir,
                 unit.name,
             ) {
                 let header = program.header();
@@ -320,7 +426,7 @@ impl<'a> DwarfParser<'a> {
 
                         // Get directory if present
                         if let Some(dir_attr) = file_entry.directory(header) {
-                            if let Ok(dir_slice) = self.dwarf.attr_string(unit, dir_attr) {
+                            if let Ok(dir_slice) = dwarf.attr_string(unit, dir_attr) {
                                 if let Ok(dir_cow) = dir_slice.to_slice() {
                                     let dir_str = String::from_utf8_lossy(&dir_cow);
                                     if !dir_str.is_empty() {
@@ -332,8 +438,7 @@ impl<'a> DwarfParser<'a> {
                         }
 
                         // Get file name
-                        if let Ok(file_slice) = self.dwarf.attr_string(unit, file_entry.path_name())
-                        {
+                        if let Ok(file_slice) = dwarf.attr_string(unit, file_entry.path_name()) {
                             if let Ok(file_cow) = file_slice.to_slice() {
                                 let file_str = String::from_utf8_lossy(&file_cow);
                                 path_buf.push_str(&file_str);
@@ -358,7 +463,7 @@ impl<'a> DwarfParser<'a> {
     // ========================================================================
 
     fn parse_children(
-        &mut self,
+        &self,
         unit: &DwarfUnit,
         entries: &mut gimli::EntriesCursor<DwarfReader>,
         elements: &mut Vec<Element>,
@@ -449,11 +554,11 @@ impl<'a> DwarfParser<'a> {
     // ========================================================================
 
     fn parse_namespace_at(
-        &mut self,
+        &self,
         unit: &DwarfUnit,
         offset: gimli::UnitOffset,
     ) -> Result<Option<Namespace>> {
-        let attrs = AttributeExtractor::new(&self.dwarf);
+        let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
         // Create cursor at offset and parse
         let mut entries = unit.entries_at_offset(offset)?;
         let (_, entry) = entries.next_dfs()?.unwrap();
@@ -463,13 +568,13 @@ impl<'a> DwarfParser<'a> {
             Some(n) => n,
             None => return Ok(None),
         };
-        let line = attrs.get_u64_attr(entry, gimli::DW_AT_decl_line);
+        let line = attrs.get_u64_attr(unit, entry, gimli::DW_AT_decl_line);
 
         self.parse_namespace_children(unit, name, line, &mut entries)
     }
 
     fn parse_namespace_children(
-        &mut self,
+        &self,
         unit: &DwarfUnit,
         name: String,
         line: Option<u64>,
@@ -553,25 +658,35 @@ impl<'a> DwarfParser<'a> {
     // ========================================================================
 
     fn parse_compound_at(
-        &mut self,
+        &self,
         unit: &DwarfUnit,
         offset: gimli::UnitOffset,
         compound_type: &str,
     ) -> Result<Option<Compound>> {
-        let attrs = AttributeExtractor::new(&self.dwarf);
+        let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
         let mut entries = unit.entries_at_offset(offset)?;
         let (_, entry) = entries.next_dfs()?.unwrap();
 
+        // Declarations standing in for a type unit's type are emitted from the
+        // type unit, unless they carry method declarations for this unit
+        if !entry.has_children()
+            && attrs
+                .get_ref_attr(unit, entry, gimli::DW_AT_signature)
+                .is_some()
+        {
+            return Ok(None);
+        }
+
         let name = attrs.get_string_attr(unit, entry, gimli::DW_AT_name);
-        let line = attrs.get_u64_attr(entry, gimli::DW_AT_decl_line);
-        let byte_size = attrs.get_u64_attr(entry, gimli::DW_AT_byte_size);
-        let decl_file = attrs.get_u64_attr(entry, gimli::DW_AT_decl_file);
+        let line = attrs.get_u64_attr(unit, entry, gimli::DW_AT_decl_line);
+        let byte_size = attrs.get_u64_attr(unit, entry, gimli::DW_AT_byte_size);
+        let decl_file = attrs.get_u64_attr(unit, entry, gimli::DW_AT_decl_file);
 
         // Convert to absolute offset for typedef lookup
         let abs_offset = unit_base_offset(unit) + entry.offset().0;
 
         // Only merge typedef if it's in the same file as the struct
-        let metadata = self.build_compound_metadata_with_typedef(
+        let mut metadata = self.build_compound_metadata_with_typedef(
             name,
             line,
             byte_size,
@@ -580,22 +695,39 @@ impl<'a> DwarfParser<'a> {
             abs_offset,
         );
 
+        let (natural_alignment, is_packed) = self.compound_alignment(unit, entry)?;
+        metadata.alignment = requested_alignment(
+            attrs.get_u64_attr(unit, entry, gimli::DW_AT_alignment),
+            Some(natural_alignment),
+        );
+        metadata.is_packed = is_packed;
+
         self.parse_compound_children(unit, metadata, &mut entries)
     }
 
     fn parse_enum_at(
-        &mut self,
+        &self,
         unit: &DwarfUnit,
         offset: gimli::UnitOffset,
     ) -> Result<Option<Compound>> {
-        let attrs = AttributeExtractor::new(&self.dwarf);
+        let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
         let mut entries = unit.entries_at_offset(offset)?;
         let (_, entry) = entries.next_dfs()?.unwrap();
 
+        // Declarations standing in for a type unit's type are emitted from the
+        // type unit, unless they carry method declarations for this unit
+        if !entry.has_children()
+            && attrs
+                .get_ref_attr(unit, entry, gimli::DW_AT_signature)
+                .is_some()
+        {
+            return Ok(None);
+        }
+
         let name = attrs.get_string_attr(unit, entry, gimli::DW_AT_name);
-        let line = attrs.get_u64_attr(entry, gimli::DW_AT_decl_line);
-        let byte_size = attrs.get_u64_attr(entry, gimli::DW_AT_byte_size);
-        let decl_file = attrs.get_u64_attr(entry, gimli::DW_AT_decl_file);
+        let line = attrs.get_u64_attr(unit, entry, gimli::DW_AT_decl_line);
+        let byte_size = attrs.get_u64_attr(unit, entry, gimli::DW_AT_byte_size);
+        let decl_file = attrs.get_u64_attr(unit, entry, gimli::DW_AT_decl_file);
 
         // Convert to absolute offset for typedef lookup
         let abs_offset = unit_base_offset(unit) + entry.offset().0;
@@ -605,7 +737,22 @@ impl<'a> DwarfParser<'a> {
             name, line, byte_size, "enum", decl_file, abs_offset,
         );
 
-        self.parse_enum_children(unit, metadata, &mut entries)
+        let is_enum_class = attrs.get_bool_attr(entry, gimli::DW_AT_enum_class);
+        let underlying_type = if entry.attr(gimli::DW_AT_type)?.is_some() {
+            Some(self.resolve_type(unit, entry)?)
+        } else {
+            None
+        };
+        let is_signed = self.is_signed_type(unit, entry)?;
+
+        self.parse_enum_children(
+            unit,
+            metadata,
+            underlying_type,
+            is_enum_class,
+            is_signed,
+            &mut entries,
+        )
     }
 
     /// Build CompoundMetadata with typedef information if applicable.
@@ -642,19 +789,24 @@ impl<'a> DwarfParser<'a> {
             typedef_line,
             compound_type: compound_type.to_string(),
             decl_file,
+            alignment: None,
+            is_packed: false,
         }
     }
 
     fn parse_compound_children(
-        &mut self,
+        &self,
         unit: &DwarfUnit,
         metadata: CompoundMetadata,
         entries: &mut gimli::EntriesCursor<DwarfReader>,
     ) -> Result<Option<Compound>> {
+        let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
         let mut members = Vec::new();
+        let mut static_members = Vec::new();
         let mut methods = Vec::new();
         let mut base_classes = Vec::new();
         let mut nested_types = Vec::new();
+        let mut template_params = Vec::new();
         let mut is_virtual = false;
         let mut absolute_depth = 1; // We start at the struct/union level (depth 1 from compile unit)
 
@@ -676,6 +828,13 @@ impl<'a> DwarfParser<'a> {
                 let offset = child_entry.offset();
 
                 match tag {
+                    gimli::DW_TAG_member | gimli::DW_TAG_variable
+                        if attrs.is_static_member(child_entry) =>
+                    {
+                        if let Some(var) = self.parse_member(unit, child_entry)? {
+                            static_members.push(var);
+                        }
+                    }
                     gimli::DW_TAG_member => {
                         if let Some(var) = self.parse_member(unit, child_entry)? {
                             // Check if this is a vtable pointer
@@ -722,6 +881,11 @@ impl<'a> DwarfParser<'a> {
                             nested_types.push(compound);
                         }
                     }
+                    gimli::DW_TAG_template_type_parameter
+                    | gimli::DW_TAG_template_value_parameter
+                    | gimli::DW_TAG_GNU_template_parameter_pack => {
+                        template_params.push(self.parse_template_parameter_at(unit, offset)?);
+                    }
                     _ => {}
                 }
             }
@@ -731,9 +895,12 @@ impl<'a> DwarfParser<'a> {
             name: metadata.name,
             compound_type: metadata.compound_type,
             members,
+            static_members,
             methods,
             nested_types,
             enum_values: Vec::new(),
+            enum_underlying_type: None,
+            is_enum_class: false,
             line: metadata.line,
             is_typedef: metadata.is_typedef,
             typedef_name: metadata.typedef_name,
@@ -742,16 +909,22 @@ impl<'a> DwarfParser<'a> {
             base_classes,
             is_virtual,
             decl_file: metadata.decl_file,
+            template_params,
+            alignment: metadata.alignment,
+            is_packed: metadata.is_packed,
         }))
     }
 
     fn parse_enum_children(
-        &mut self,
+        &self,
         unit: &DwarfUnit,
         metadata: CompoundMetadata,
+        underlying_type: Option<TypeInfo>,
+        is_enum_class: bool,
+        is_signed: bool,
         entries: &mut gimli::EntriesCursor<DwarfReader>,
     ) -> Result<Option<Compound>> {
-        let attrs = AttributeExtractor::new(&self.dwarf);
+        let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
         let mut enum_values = Vec::new();
         let mut absolute_depth = 1; // We start at the enum level (depth 1 from compile unit)
 
@@ -771,7 +944,11 @@ impl<'a> DwarfParser<'a> {
             if absolute_depth == 2 && child_entry.tag() == gimli::DW_TAG_enumerator {
                 if let Some(enum_name) = attrs.get_string_attr(unit, child_entry, gimli::DW_AT_name)
                 {
-                    let value = attrs.get_i64_attr(child_entry, gimli::DW_AT_const_value);
+                    let value = if is_signed {
+                        attrs.get_signed_const_value(child_entry)
+                    } else {
+                        attrs.get_const_value(child_entry)
+                    };
                     enum_values.push((enum_name, value));
                 }
             }
@@ -781,9 +958,12 @@ impl<'a> DwarfParser<'a> {
             name: metadata.name,
             compound_type: metadata.compound_type,
             members: Vec::new(),
+            static_members: Vec::new(),
             methods: Vec::new(),
             nested_types: Vec::new(),
             enum_values,
+            enum_underlying_type: underlying_type,
+            is_enum_class,
             line: metadata.line,
             is_typedef: metadata.is_typedef,
             typedef_name: metadata.typedef_name,
@@ -792,6 +972,9 @@ impl<'a> DwarfParser<'a> {
             base_classes: Vec::new(),
             decl_file: metadata.decl_file,
             is_virtual: false,
+            template_params: Vec::new(),
+            alignment: metadata.alignment,
+            is_packed: metadata.is_packed,
         }))
     }
 
@@ -800,43 +983,60 @@ impl<'a> DwarfParser<'a> {
     // ========================================================================
 
     fn parse_member(
-        &mut self,
+        &self,
         unit: &DwarfUnit,
         entry: &DebuggingInformationEntry<DwarfReader>,
     ) -> Result<Option<Variable>> {
         // Extract all attributes first to avoid borrow conflicts with resolve_type
-        let (name, line, accessibility, offset, bit_size, bit_offset, const_value, decl_file) = {
-            let attrs = AttributeExtractor::new(&self.dwarf);
+        let (name, line, accessibility, offset, bit_size, const_value, decl_file, alignment) = {
+            let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
             let name = match attrs.get_string_attr(unit, entry, gimli::DW_AT_name) {
                 Some(n) => n,
                 None => return Ok(None),
             };
-            let line = attrs.get_u64_attr(entry, gimli::DW_AT_decl_line);
+            let line = attrs.get_u64_attr(unit, entry, gimli::DW_AT_decl_line);
             let accessibility = attrs.get_accessibility(entry);
             let offset = attrs.get_member_offset(unit, entry);
-            let bit_size = attrs.get_u64_attr(entry, gimli::DW_AT_bit_size);
-            let bit_offset = attrs
-                .get_u64_attr(entry, gimli::DW_AT_bit_offset)
-                .or_else(|| attrs.get_u64_attr(entry, gimli::DW_AT_data_bit_offset));
+            let bit_size = attrs.get_u64_attr(unit, entry, gimli::DW_AT_bit_size);
             let const_value = attrs.get_const_value(entry);
-            let decl_file = attrs.get_u64_attr(entry, gimli::DW_AT_decl_file);
+            let decl_file = attrs.get_u64_attr(unit, entry, gimli::DW_AT_decl_file);
+            let alignment = attrs.get_u64_attr(unit, entry, gimli::DW_AT_alignment);
             (
                 name,
                 line,
                 accessibility,
                 offset,
                 bit_size,
-                bit_offset,
                 const_value,
                 decl_file,
+                alignment,
             )
         };
 
         let type_info = self.resolve_type(unit, entry)?;
+        let alignment = requested_alignment(alignment, type_info.layout.map(|l| l.alignment));
+
+        // Bitfields are placed by their absolute bit position, and at the
+        // offset of the storage unit holding them
+        let type_size = type_info.layout.map(|l| l.size);
+        let bit_offset = bit_size.and_then(|bit_size| {
+            AttributeExtractor::new(&self.dwarf, self.index.signatures())
+                .get_bit_position(unit, entry, bit_size, type_size)
+        });
+        let offset = match (bit_offset, bit_size) {
+            (Some(position), Some(bit_size)) => Some(match type_size {
+                Some(size) if size != 0 && !straddles_storage_unit(position, bit_size, size) => {
+                    position / (size * 8) * size
+                }
+                _ => position / 8,
+            }),
+            _ => offset,
+        };
 
         Ok(Some(Variable {
             name,
             type_info,
+            is_extern: false,
             line,
             accessibility,
             offset,
@@ -844,17 +1044,19 @@ impl<'a> DwarfParser<'a> {
             bit_offset,
             const_value,
             decl_file,
+            alignment,
+            instantiation: None,
         }))
     }
 
     fn parse_inheritance(
-        &mut self,
+        &self,
         unit: &DwarfUnit,
         entry: &DebuggingInformationEntry<DwarfReader>,
     ) -> Result<Option<BaseClass>> {
         // Extract all attributes first to avoid borrow conflicts with resolve_type
         let (offset, accessibility, is_virtual) = {
-            let attrs = AttributeExtractor::new(&self.dwarf);
+            let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
             let offset = attrs.get_member_offset(unit, entry);
             let accessibility = attrs.get_accessibility(entry);
             let is_virtual = attrs.get_bool_attr(entry, gimli::DW_AT_virtuality);
@@ -863,7 +1065,7 @@ impl<'a> DwarfParser<'a> {
 
         // Get the type of the base class
         let type_info = self.resolve_type(unit, entry)?;
-        let type_name = type_info.base_type;
+        let type_name = type_info.base_name().to_string();
 
         Ok(Some(BaseClass {
             type_name,
@@ -874,34 +1076,32 @@ impl<'a> DwarfParser<'a> {
     }
 
     fn parse_variable(
-        &mut self,
+        &self,
         unit: &DwarfUnit,
         entry: &DebuggingInformationEntry<DwarfReader>,
     ) -> Result<Option<Variable>> {
         // Extract all attributes first to avoid borrow conflicts with resolve_type
-        let (name, line, is_external, const_value, decl_file) = {
-            let attrs = AttributeExtractor::new(&self.dwarf);
+        let (name, line, is_external, const_value, decl_file, alignment) = {
+            let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
             let name = match attrs.get_string_attr(unit, entry, gimli::DW_AT_name) {
                 Some(n) => n,
-                None => return Ok(None),
+                None => return self.parse_static_member_definition(unit, entry),
             };
-            let line = attrs.get_u64_attr(entry, gimli::DW_AT_decl_line);
+            let line = attrs.get_u64_attr(unit, entry, gimli::DW_AT_decl_line);
             let is_external = attrs.get_bool_attr(entry, gimli::DW_AT_external);
             let const_value = attrs.get_const_value(entry);
-            let decl_file = attrs.get_u64_attr(entry, gimli::DW_AT_decl_file);
-            (name, line, is_external, const_value, decl_file)
+            let decl_file = attrs.get_u64_attr(unit, entry, gimli::DW_AT_decl_file);
+            let alignment = attrs.get_u64_attr(unit, entry, gimli::DW_AT_alignment);
+            (name, line, is_external, const_value, decl_file, alignment)
         };
 
-        let mut type_info = self.resolve_type(unit, entry)?;
-
-        // Check for extern/static
-        if is_external {
-            type_info.is_extern = true;
-        }
+        let type_info = self.resolve_type(unit, entry)?;
+        let alignment = requested_alignment(alignment, type_info.layout.map(|l| l.alignment));
 
         Ok(Some(Variable {
             name,
             type_info,
+            is_extern: is_external,
             line,
             accessibility: None,
             offset: None,
@@ -909,6 +1109,81 @@ impl<'a> DwarfParser<'a> {
             bit_offset: None,
             const_value,
             decl_file,
+            alignment,
+            instantiation: None,
+        }))
+    }
+
+    /// The definition of a static data member, which is unnamed and refers to
+    /// the declaration in its class through DW_AT_specification. It is named
+    /// after the class, and takes the attributes it lacks from the
+    /// declaration.
+    fn parse_static_member_definition(
+        &self,
+        unit: &DwarfUnit,
+        entry: &DebuggingInformationEntry<DwarfReader>,
+    ) -> Result<Option<Variable>> {
+        let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
+        let Some(spec_offset) = attrs.get_ref_attr(unit, entry, gimli::DW_AT_specification) else {
+            return Ok(None);
+        };
+        let Some(class_name) = self.static_member_classes.get(&spec_offset) else {
+            return Ok(None);
+        };
+        let Some((spec_unit, spec_unit_offset)) = self.index.lookup(spec_offset) else {
+            return Ok(None);
+        };
+        let mut spec_entries = spec_unit.entries_at_offset(spec_unit_offset)?;
+        let Some((_, spec_entry)) = spec_entries.next_dfs()? else {
+            return Ok(None);
+        };
+        let Some(name) = attrs.get_string_attr(&spec_unit, spec_entry, gimli::DW_AT_name) else {
+            return Ok(None);
+        };
+
+        // Arrays declared without a bound are completed by their definition
+        let type_info = if entry.attr_value(gimli::DW_AT_type)?.is_some() {
+            self.resolve_type(unit, entry)?
+        } else {
+            self.resolve_type(&spec_unit, spec_entry)?
+        };
+        let alignment = requested_alignment(
+            attrs.get_u64_attr(unit, entry, gimli::DW_AT_alignment),
+            type_info.layout.map(|l| l.alignment),
+        );
+
+        // decl_file indices only carry over within the same file table
+        let (line, decl_file) = match attrs.get_u64_attr(unit, entry, gimli::DW_AT_decl_file) {
+            Some(decl_file) => (
+                attrs.get_u64_attr(unit, entry, gimli::DW_AT_decl_line),
+                Some(decl_file),
+            ),
+            None if die_index::shares_file_table(unit, &spec_unit) => (
+                attrs
+                    .get_u64_attr(unit, entry, gimli::DW_AT_decl_line)
+                    .or_else(|| attrs.get_u64_attr(&spec_unit, spec_entry, gimli::DW_AT_decl_line)),
+                attrs.get_u64_attr(&spec_unit, spec_entry, gimli::DW_AT_decl_file),
+            ),
+            None => (
+                attrs.get_u64_attr(unit, entry, gimli::DW_AT_decl_line),
+                None,
+            ),
+        };
+
+        Ok(Some(Variable {
+            name: format!("{}::{}", class_name, name),
+            type_info,
+            is_extern: false,
+            line,
+            accessibility: None,
+            offset: None,
+            bit_size: None,
+            bit_offset: None,
+            // A value given in the class can't be repeated here
+            const_value: attrs.get_const_value(entry),
+            decl_file,
+            alignment,
+            instantiation: None,
         }))
     }
 
@@ -917,7 +1192,7 @@ impl<'a> DwarfParser<'a> {
     // ========================================================================
 
     fn parse_function_at(
-        &mut self,
+        &self,
         unit: &DwarfUnit,
         offset: gimli::UnitOffset,
         is_method: bool,
@@ -925,8 +1200,8 @@ impl<'a> DwarfParser<'a> {
         let mut entries = unit.entries_at_offset(offset)?;
         let (_, entry) = entries.next_dfs()?.unwrap();
 
-        // Get the unit base for absolute offset calculation
-        let unit_base = unit_base_offset(unit);
+        // Get the absolute offset, used to match declarations and concrete instances
+        let abs_offset = unit_base_offset(unit) + offset.0;
 
         // First, extract all attributes that don't require resolve_type
         // to avoid borrow conflicts
@@ -944,6 +1219,7 @@ impl<'a> DwarfParser<'a> {
             is_external: bool,
             is_artificial: bool,
             decl_file: Option<u64>,
+            is_prototyped: bool,
         }
 
         struct SpecAttrs {
@@ -955,7 +1231,7 @@ impl<'a> DwarfParser<'a> {
 
         // Extract main entry attributes
         let main_attrs = {
-            let attrs = AttributeExtractor::new(&self.dwarf);
+            let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
             FuncAttrs {
                 specification_offset: attrs.get_ref_attr(unit, entry, gimli::DW_AT_specification),
                 name: attrs.get_string_attr(unit, entry, gimli::DW_AT_name),
@@ -965,30 +1241,49 @@ impl<'a> DwarfParser<'a> {
                     .get_string_attr(unit, entry, gimli::DW_AT_linkage_name)
                     .or_else(|| attrs.get_string_attr(unit, entry, gimli::DW_AT_MIPS_linkage_name)),
                 is_declaration: attrs.get_bool_attr(entry, gimli::DW_AT_declaration),
-                line: attrs.get_u64_attr(entry, gimli::DW_AT_decl_line),
-                low_pc: attrs.get_u64_attr(entry, gimli::DW_AT_low_pc),
-                high_pc: attrs.get_u64_attr(entry, gimli::DW_AT_high_pc),
-                is_inline: attrs.get_u64_attr(entry, gimli::DW_AT_inline).is_some(),
+                line: attrs.get_u64_attr(unit, entry, gimli::DW_AT_decl_line),
+                low_pc: attrs.get_u64_attr(unit, entry, gimli::DW_AT_low_pc),
+                high_pc: attrs.get_high_pc(
+                    unit,
+                    entry,
+                    attrs.get_u64_attr(unit, entry, gimli::DW_AT_low_pc),
+                ),
+                is_inline: attrs
+                    .get_u64_attr(unit, entry, gimli::DW_AT_inline)
+                    .is_some(),
                 is_external: attrs.get_bool_attr(entry, gimli::DW_AT_external),
                 is_artificial: attrs.get_bool_attr(entry, gimli::DW_AT_artificial),
-                decl_file: attrs.get_u64_attr(entry, gimli::DW_AT_decl_file),
+                decl_file: attrs.get_u64_attr(unit, entry, gimli::DW_AT_decl_file),
+                is_prototyped: attrs.get_bool_attr(entry, gimli::DW_AT_prototyped),
             }
         };
 
+        // The declaration a definition refers to may live in another unit
+        let spec_location = match main_attrs.specification_offset {
+            Some(spec_offset) => match self.index.lookup(spec_offset) {
+                Some(location) => Some(location),
+                None => return Ok(None),
+            },
+            None => None,
+        };
+
         // Extract specification entry attributes if present
-        let spec_attrs = if let Some(spec_offset) = main_attrs.specification_offset {
-            let spec_unit_offset = gimli::UnitOffset(spec_offset);
-            let mut spec_entries = unit.entries_at_offset(spec_unit_offset)?;
+        let spec_attrs = if let Some((spec_unit, spec_unit_offset)) = &spec_location {
+            let mut spec_entries = spec_unit.entries_at_offset(*spec_unit_offset)?;
             if let Some((_, spec_entry)) = spec_entries.next_dfs()? {
-                let attrs = AttributeExtractor::new(&self.dwarf);
+                let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
                 Some(SpecAttrs {
-                    name: attrs.get_string_attr(unit, spec_entry, gimli::DW_AT_name),
+                    name: attrs.get_string_attr(spec_unit, spec_entry, gimli::DW_AT_name),
                     accessibility: attrs.get_accessibility(spec_entry),
                     is_virtual: attrs.get_bool_attr(spec_entry, gimli::DW_AT_virtuality),
                     linkage_name: attrs
-                        .get_string_attr(unit, spec_entry, gimli::DW_AT_linkage_name)
+                        .get_string_attr(spec_unit, spec_entry, gimli::DW_AT_linkage_name)
                         .or_else(|| {
-                            attrs.get_string_attr(unit, spec_entry, gimli::DW_AT_MIPS_linkage_name)
+                            attrs.get_string_attr(
+                                spec_unit,
+                                spec_entry,
+                                gimli::DW_AT_MIPS_linkage_name,
+                            )
                         }),
                 })
             } else {
@@ -1006,10 +1301,9 @@ impl<'a> DwarfParser<'a> {
             is_virtual_from_spec,
             linkage_name_from_spec,
             spec_abs_offset,
-        ) = if let Some(spec_offset) = main_attrs.specification_offset {
+        ) = if let Some((spec_unit, spec_unit_offset)) = &spec_location {
             // Follow the specification to get name, return_type, etc from the declaration
-            let spec_unit_offset = gimli::UnitOffset(spec_offset);
-            let mut spec_entries = unit.entries_at_offset(spec_unit_offset)?;
+            let mut spec_entries = spec_unit.entries_at_offset(*spec_unit_offset)?;
 
             if let Some((_, spec_entry)) = spec_entries.next_dfs()? {
                 let spec = spec_attrs.unwrap();
@@ -1017,15 +1311,14 @@ impl<'a> DwarfParser<'a> {
                     Some(n) => n,
                     None => return Ok(None),
                 };
-                let return_type = self.resolve_type(unit, spec_entry)?;
-                let abs_offset = unit_base + spec_offset;
+                let return_type = self.resolve_type(spec_unit, spec_entry)?;
                 (
                     name,
                     return_type,
                     spec.accessibility,
                     spec.is_virtual,
                     spec.linkage_name,
-                    Some(abs_offset),
+                    main_attrs.specification_offset,
                 )
             } else {
                 return Ok(None);
@@ -1037,7 +1330,12 @@ impl<'a> DwarfParser<'a> {
                 None => return Ok(None),
             };
 
-            if main_attrs.is_declaration && !is_method {
+            // Declarations of functions defined elsewhere are kept as
+            // prototypes, unless a definition refers back to them
+            if main_attrs.is_declaration
+                && !is_method
+                && self.specified_functions.contains(&abs_offset)
+            {
                 return Ok(None);
             }
 
@@ -1055,17 +1353,6 @@ impl<'a> DwarfParser<'a> {
         // If we have a specification, this is a definition (has body)
         let has_body = main_attrs.specification_offset.is_some() || !main_attrs.is_declaration;
 
-        let mut high_pc = main_attrs.high_pc;
-
-        // high_pc can be either an absolute address or an offset from low_pc
-        // If we have both and high_pc is small (likely an offset), convert it
-        if let (Some(low), Some(high)) = (main_attrs.low_pc, high_pc) {
-            if high < low {
-                // high_pc is an offset, convert to absolute address
-                high_pc = Some(low + high);
-            }
-        }
-
         // Use virtual flag from specification if we have one, otherwise from entry
         let is_virtual = is_virtual_from_spec || main_attrs.is_virtual;
         // Prefer linkage name from entry, fall back to one from specification
@@ -1084,11 +1371,18 @@ impl<'a> DwarfParser<'a> {
             && main_attrs.is_declaration
             && main_attrs.specification_offset.is_none()
         {
-            Some(unit_base + offset.0)
+            Some(abs_offset)
         } else {
             None
         };
 
+        // Code for this function may be described by a concrete instance
+        // elsewhere, possibly in another unit
+        let (low_pc, high_pc) = match (main_attrs.low_pc, self.concrete_ranges.get(&abs_offset)) {
+            (None, Some(&(low_pc, high_pc))) => (Some(low_pc), high_pc),
+            _ => (main_attrs.low_pc, main_attrs.high_pc),
+        };
+
         let metadata = FunctionMetadata {
             name,
             decl_file: main_attrs.decl_file,
@@ -1097,7 +1391,7 @@ impl<'a> DwarfParser<'a> {
             accessibility,
             has_body,
             is_method: effective_is_method,
-            low_pc: main_attrs.low_pc,
+            low_pc,
             high_pc,
             is_inline: main_attrs.is_inline,
             is_external: main_attrs.is_external,
@@ -1108,22 +1402,32 @@ impl<'a> DwarfParser<'a> {
             is_artificial: main_attrs.is_artificial,
             specification_offset: spec_abs_offset,
             decl_offset,
+            is_prototyped: main_attrs.is_prototyped,
         };
 
         self.parse_function_children(unit, metadata, &mut entries)
     }
 
     fn parse_function_children(
-        &mut self,
+        &self,
         unit: &DwarfUnit,
         metadata: FunctionMetadata,
         entries: &mut gimli::EntriesCursor<DwarfReader>,
     ) -> Result<Option<Function>> {
+        let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
         let mut parameters = Vec::new();
         let mut variables = Vec::new();
         let mut lexical_blocks = Vec::new();
         let mut inlined_calls = Vec::new();
         let mut labels = Vec::new();
+        let mut local_types = Vec::new();
+        // Offsets of the variables' types and of the local types
+        let mut variable_types = Vec::new();
+        let mut local_type_offsets = Vec::new();
+        let mut template_params = Vec::new();
+        let mut is_variadic = false;
+        let mut parameter_registers = ParameterRegisters::default();
+        let mut shared_registers = HashSet::new();
         let mut absolute_depth = 1; // We start at the function level (depth 1 from compile unit)
 
         // Parse function children
@@ -1145,12 +1449,40 @@ impl<'a> DwarfParser<'a> {
 
                 match tag {
                     gimli::DW_TAG_formal_parameter => {
-                        if let Some(param) = self.parse_parameter(unit, child_entry)? {
+                        // Prototypes keep their unnamed parameters
+                        let allow_unnamed = !metadata.has_body && !metadata.is_method;
+                        if let Some(param) =
+                            self.parse_parameter(unit, child_entry, allow_unnamed)?
+                        {
+                            if let Some(register) =
+                                attrs.get_entry_register(unit, child_entry, metadata.low_pc)
+                            {
+                                parameter_registers
+                                    .at_entry
+                                    .insert(register, param.name.clone());
+                            }
+                            for register in attrs.get_held_registers(unit, child_entry) {
+                                let held = &mut parameter_registers.held;
+                                if held
+                                    .insert(register, param.name.clone())
+                                    .is_some_and(|other| other != param.name)
+                                {
+                                    shared_registers.insert(register);
+                                }
+                            }
                             parameters.push(param);
                         }
                     }
+                    gimli::DW_TAG_unspecified_parameters => {
+                        is_variadic = true;
+                    }
                     gimli::DW_TAG_variable => {
                         if let Some(var) = self.parse_variable(unit, child_entry)? {
+                            variable_types.push(attrs.get_ref_attr(
+                                unit,
+                                child_entry,
+                                gimli::DW_AT_type,
+                            ));
                             variables.push(var);
                         }
                     }
@@ -1169,11 +1501,61 @@ impl<'a> DwarfParser<'a> {
                             labels.push(label);
                         }
                     }
+                    gimli::DW_TAG_structure_type
+                    | gimli::DW_TAG_class_type
+                    | gimli::DW_TAG_union_type
+                    | gimli::DW_TAG_enumeration_type
+                    | gimli::DW_TAG_typedef => {
+                        if let Some(local_type) = self.parse_local_type(unit, child_entry)? {
+                            local_type_offsets.push(unit_base_offset(unit) + offset.0);
+                            local_types.push(local_type);
+                        }
+                    }
+                    gimli::DW_TAG_template_type_parameter
+                    | gimli::DW_TAG_template_value_parameter
+                    | gimli::DW_TAG_GNU_template_parameter_pack => {
+                        template_params.push(self.parse_template_parameter_at(unit, offset)?);
+                    }
                     _ => {}
                 }
             }
         }
 
+        // g++ leaves out the unspecified parameters of definitions taking
+        // only `...`, which their mangled name still has
+        let is_variadic = is_variadic
+            || metadata
+                .linkage_name
+                .as_deref()
+                .is_some_and(has_mangled_ellipsis);
+        let is_variadic = type_resolver::is_variadic_function(
+            is_variadic,
+            metadata.is_prototyped || attrs.unit_has_prototypes(unit),
+            parameters.len(),
+        );
+
+        inline_anonymous_enums(
+            &mut variables,
+            &variable_types,
+            &mut local_types,
+            &local_type_offsets,
+        );
+
+        // Optimized code computes the bounds of variable length arrays from
+        // the parameters still in their registers
+        parameter_registers
+            .held
+            .retain(|register, _| !shared_registers.contains(register));
+        for var in &mut variables {
+            var.type_info.name_register_bounds(&parameter_registers);
+        }
+        for block in &mut lexical_blocks {
+            block.name_register_bounds(&parameter_registers);
+        }
+        for local_type in &mut local_types {
+            local_type.name_register_bounds(&parameter_registers);
+        }
+
         Ok(Some(Function {
             name: metadata.name,
             return_type: metadata.return_type,
@@ -1182,6 +1564,7 @@ impl<'a> DwarfParser<'a> {
             lexical_blocks,
             inlined_calls,
             labels,
+            local_types,
             line: metadata.line,
             is_method: metadata.is_method,
             class_name: None,
@@ -1200,21 +1583,26 @@ impl<'a> DwarfParser<'a> {
             decl_file: metadata.decl_file,
             specification_offset: metadata.specification_offset,
             decl_offset: metadata.decl_offset,
+            template_params,
+            is_variadic,
+            is_prototyped: metadata.is_prototyped,
         }))
     }
 
     fn parse_parameter(
-        &mut self,
+        &self,
         unit: &DwarfUnit,
         entry: &DebuggingInformationEntry<DwarfReader>,
+        allow_unnamed: bool,
     ) -> Result<Option<Parameter>> {
-        let attrs = AttributeExtractor::new(&self.dwarf);
+        let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
         let name = match attrs.get_string_attr(unit, entry, gimli::DW_AT_name) {
             Some(n) => n,
+            None if allow_unnamed => String::new(),
             None => return Ok(None),
         };
 
-        let line = attrs.get_u64_attr(entry, gimli::DW_AT_decl_line);
+        let line = attrs.get_u64_attr(unit, entry, gimli::DW_AT_decl_line);
         let type_info = self.resolve_type(unit, entry)?;
 
         Ok(Some(Parameter {
@@ -1229,29 +1617,34 @@ impl<'a> DwarfParser<'a> {
     // ========================================================================
 
     fn parse_lexical_block_at(
-        &mut self,
+        &self,
         unit: &DwarfUnit,
         offset: gimli::UnitOffset,
     ) -> Result<Option<LexicalBlock>> {
-        let attrs = AttributeExtractor::new(&self.dwarf);
+        let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
         let mut entries = unit.entries_at_offset(offset)?;
         let (_, entry) = entries.next_dfs()?.unwrap();
 
-        let line = attrs.get_u64_attr(entry, gimli::DW_AT_decl_line);
+        let line = attrs.get_u64_attr(unit, entry, gimli::DW_AT_decl_line);
 
         self.parse_lexical_block_children(unit, line, &mut entries)
     }
 
     fn parse_lexical_block_children(
-        &mut self,
+        &self,
         unit: &DwarfUnit,
         line: Option<u64>,
         entries: &mut gimli::EntriesCursor<DwarfReader>,
     ) -> Result<Option<LexicalBlock>> {
+        let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
         let mut variables = Vec::new();
         let mut nested_blocks = Vec::new();
         let mut inlined_calls = Vec::new();
         let mut labels = Vec::new();
+        let mut local_types = Vec::new();
+        // Offsets of the variables' types and of the local types
+        let mut variable_types = Vec::new();
+        let mut local_type_offsets = Vec::new();
         let mut absolute_depth = 2; // We start at the lexical block level (depth 2 from compile unit)
 
         loop {
@@ -1273,6 +1666,11 @@ impl<'a> DwarfParser<'a> {
                 match tag {
                     gimli::DW_TAG_variable => {
                         if let Some(var) = self.parse_variable(unit, child_entry)? {
+                            variable_types.push(attrs.get_ref_attr(
+                                unit,
+                                child_entry,
+                                gimli::DW_AT_type,
+                            ));
                             variables.push(var);
                         }
                     }
@@ -1291,39 +1689,80 @@ impl<'a> DwarfParser<'a> {
                             labels.push(label);
                         }
                     }
+                    gimli::DW_TAG_structure_type
+                    | gimli::DW_TAG_class_type
+                    | gimli::DW_TAG_union_type
+                    | gimli::DW_TAG_enumeration_type
+                    | gimli::DW_TAG_typedef => {
+                        if let Some(local_type) = self.parse_local_type(unit, child_entry)? {
+                            local_type_offsets.push(unit_base_offset(unit) + offset.0);
+                            local_types.push(local_type);
+                        }
+                    }
                     _ => {}
                 }
             }
         }
 
+        inline_anonymous_enums(
+            &mut variables,
+            &variable_types,
+            &mut local_types,
+            &local_type_offsets,
+        );
+
         Ok(Some(LexicalBlock {
             variables,
             nested_blocks,
             inlined_calls,
             labels,
+            local_types,
             line,
         }))
     }
 
+    /// Parse a struct, class, union, enum or typedef defined in a function
+    /// body or lexical block
+    fn parse_local_type(
+        &self,
+        unit: &DwarfUnit,
+        entry: &DebuggingInformationEntry<DwarfReader>,
+    ) -> Result<Option<Element>> {
+        let offset = entry.offset();
+        Ok(match entry.tag() {
+            gimli::DW_TAG_structure_type => self
+                .parse_compound_at(unit, offset, "struct")?
+                .map(Element::Compound),
+            gimli::DW_TAG_class_type => self
+                .parse_compound_at(unit, offset, "class")?
+                .map(Element::Compound),
+            gimli::DW_TAG_union_type => self
+                .parse_compound_at(unit, offset, "union")?
+                .map(Element::Compound),
+            gimli::DW_TAG_enumeration_type => {
+                self.parse_enum_at(unit, offset)?.map(Element::Compound)
+            }
+            gimli::DW_TAG_typedef => self
+                .parse_typedef_alias(unit, entry)?
+                .map(Element::TypedefAlias),
+            _ => None,
+        })
+    }
+
     // ========================================================================
     // Inlined subroutine and label parsing
     // ========================================================================
 
     fn parse_inlined_subroutine(
-        &mut self,
+        &self,
         unit: &DwarfUnit,
         entry: &DebuggingInformationEntry<DwarfReader>,
     ) -> Result<Option<InlinedSubroutine>> {
-        let attrs = AttributeExtractor::new(&self.dwarf);
+        let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
         // Try to get name from abstract origin
-        let name = if let Some(origin_offset) =
-            attrs.get_ref_attr(unit, entry, gimli::DW_AT_abstract_origin)
-        {
-            // Convert to absolute offset
-            let abs_origin_offset = unit_base_offset(unit) + origin_offset;
-            self.abstract_origins.get(&abs_origin_offset).cloned()
-        } else {
-            None
+        let name = match attrs.get_ref_attr(unit, entry, gimli::DW_AT_abstract_origin) {
+            Some(origin_offset) => self.subprogram_name(origin_offset)?,
+            None => None,
         };
 
         let name = match name {
@@ -1332,28 +1771,213 @@ impl<'a> DwarfParser<'a> {
         };
 
         let line = attrs
-            .get_u64_attr(entry, gimli::DW_AT_call_line)
-            .or_else(|| attrs.get_u64_attr(entry, gimli::DW_AT_decl_line));
+            .get_u64_attr(unit, entry, gimli::DW_AT_call_line)
+            .or_else(|| attrs.get_u64_attr(unit, entry, gimli::DW_AT_decl_line));
 
         Ok(Some(InlinedSubroutine { name, line }))
     }
 
+    /// Name of the subprogram at an absolute offset. Abstract instances of
+    /// C++ methods are unnamed and refer to the declaration through
+    /// DW_AT_specification, possibly in another unit.
+    fn subprogram_name(&self, offset: usize) -> Result<Option<String>> {
+        let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
+        let mut current_offset = offset;
+
+        for _ in 0..8 {
+            if let Some(name) = self.abstract_origins.get(&current_offset) {
+                return Ok(Some(name.clone()));
+            }
+
+            let Some((unit, unit_offset)) = self.index.lookup(current_offset) else {
+                break;
+            };
+            let mut entries = unit.entries_at_offset(unit_offset)?;
+            let Some((_, entry)) = entries.next_dfs()? else {
+                break;
+            };
+            match attrs
+                .get_ref_attr(&unit, entry, gimli::DW_AT_specification)
+                .or_else(|| attrs.get_ref_attr(&unit, entry, gimli::DW_AT_abstract_origin))
+            {
+                Some(next_offset) => current_offset = next_offset,
+                None => break,
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Whether an entry's DW_AT_type is a signed integer type, looking
+    /// through typedefs and qualifiers
+    fn is_signed_type(
+        &self,
+        unit: &DwarfUnit,
+        entry: &DebuggingInformationEntry<DwarfReader>,
+    ) -> Result<bool> {
+        let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
+        let mut type_offset = attrs.get_ref_attr(unit, entry, gimli::DW_AT_type);
+
+        for _ in 0..8 {
+            let Some((unit, unit_offset)) = type_offset.and_then(|o| self.index.lookup(o)) else {
+                break;
+            };
+            let mut entries = unit.entries_at_offset(unit_offset)?;
+            let Some((_, type_entry)) = entries.next_dfs()? else {
+                break;
+            };
+            match type_entry.tag() {
+                gimli::DW_TAG_base_type => {
+                    return Ok(matches!(
+                        type_entry.attr_value(gimli::DW_AT_encoding)?,
+                        Some(gimli::AttributeValue::Encoding(
+                            gimli::DW_ATE_signed | gimli::DW_ATE_signed_char
+                        ))
+                    ));
+                }
+                gimli::DW_TAG_typedef | gimli::DW_TAG_const_type | gimli::DW_TAG_volatile_type => {
+                    type_offset = attrs.get_ref_attr(&unit, type_entry, gimli::DW_AT_type);
+                }
+                _ => break,
+            }
+        }
+
+        Ok(false)
+    }
+
     fn parse_label(
-        &mut self,
+        &self,
         unit: &DwarfUnit,
         entry: &DebuggingInformationEntry<DwarfReader>,
     ) -> Result<Option<Label>> {
-        let attrs = AttributeExtractor::new(&self.dwarf);
+        let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
         let name = match attrs.get_string_attr(unit, entry, gimli::DW_AT_name) {
             Some(n) => n,
             None => return Ok(None),
         };
 
-        let line = attrs.get_u64_attr(entry, gimli::DW_AT_decl_line);
+        let line = attrs.get_u64_attr(unit, entry, gimli::DW_AT_decl_line);
 
         Ok(Some(Label { name, line }))
     }
 
+    // ========================================================================
+    // Template parameter parsing
+    // ========================================================================
+
+    fn parse_template_parameter_at(
+        &self,
+        unit: &DwarfUnit,
+        offset: gimli::UnitOffset,
+    ) -> Result<TemplateParameter> {
+        let mut entries = unit.entries_at_offset(offset)?;
+        let (_, entry) = entries.next_dfs()?.unwrap();
+
+        let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
+        let name = attrs.get_string_attr(unit, entry, gimli::DW_AT_name);
+
+        let argument = if entry.tag() == gimli::DW_TAG_GNU_template_parameter_pack {
+            // The pack's arguments are unnamed parameters among its children
+            let mut arguments = Vec::new();
+            let mut depth = 0;
+            while let Some((depth_delta, child_entry)) = entries.next_dfs()? {
+                depth += depth_delta;
+                if depth <= 0 {
+                    break;
+                }
+                if depth == 1 {
+                    arguments.push(self.parse_template_argument(unit, child_entry)?);
+                }
+            }
+            TemplateArgument::Pack(arguments)
+        } else {
+            self.parse_template_argument(unit, entry)?
+        };
+
+        Ok(TemplateParameter { name, argument })
+    }
+
+    fn parse_template_argument(
+        &self,
+        unit: &DwarfUnit,
+        entry: &DebuggingInformationEntry<DwarfReader>,
+    ) -> Result<TemplateArgument> {
+        let type_info = self.resolve_type(unit, entry)?;
+        if entry.tag() == gimli::DW_TAG_template_type_parameter {
+            return Ok(TemplateArgument::Type(type_info));
+        }
+
+        let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
+        let symbol = match attrs.get_location_address(unit, entry) {
+            Some(address) => {
+                let points_to_function = matches!(&type_info.kind,
+                    TypeKind::Pointer(target) if matches!(target.kind, TypeKind::Function { .. }));
+                self.symbol_at_address(unit, address, points_to_function)?
+            }
+            None => None,
+        };
+        Ok(TemplateArgument::Value {
+            type_info,
+            value: attrs.get_const_value(entry),
+            symbol,
+        })
+    }
+
+    /// The name of the function, or else variable, at `address` in `unit`.
+    /// None if there is none, or several, as in an unlinked object whose
+    /// sections all start at 0.
+    fn symbol_at_address(
+        &self,
+        unit: &DwarfUnit,
+        address: u64,
+        is_function: bool,
+    ) -> Result<Option<String>> {
+        let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
+        let mut symbols = Vec::new();
+        let mut entries = unit.entries();
+        while let Some((_, entry)) = entries.next_dfs()? {
+            let entry_address = match entry.tag() {
+                gimli::DW_TAG_variable if !is_function => attrs.get_location_address(unit, entry),
+                gimli::DW_TAG_subprogram if is_function => {
+                    attrs.get_u64_attr(unit, entry, gimli::DW_AT_low_pc)
+                }
+                _ => None,
+            };
+            if entry_address != Some(address) {
+                continue;
+            }
+            // Definitions outside their class are named by their declaration
+            let name = attrs
+                .get_string_attr(unit, entry, gimli::DW_AT_name)
+                .or_else(|| {
+                    attrs
+                        .get_ref_attr(unit, entry, gimli::DW_AT_specification)
+                        .and_then(|offset| self.index.lookup(offset))
+                        .and_then(|(spec_unit, spec_offset)| {
+                            let mut spec_entries = spec_unit.entries_at_offset(spec_offset).ok()?;
+                            let (_, spec_entry) = spec_entries.next_dfs().ok()??;
+                            attrs.get_string_attr(&spec_unit, spec_entry, gimli::DW_AT_name)
+                        })
+                });
+            if let Some(name) = name {
+                if !symbols.contains(&name) {
+                    symbols.push(name);
+                }
+            }
+        }
+        Ok(match symbols.as_slice() {
+            [symbol] => Some(symbol.clone()),
+            // Variables and functions without debug info of their own, as
+            // in objects built without -g, are found in the symbol table
+            [] => self
+                .address_symbols
+                .get(&(address, is_function))
+                .cloned()
+                .flatten(),
+            _ => None,
+        })
+    }
+
     // ========================================================================
     // Typedef alias parsing
     // ========================================================================
@@ -1362,18 +1986,19 @@ impl<'a> DwarfParser<'a> {
     /// For struct/class/union/enum, only create TypedefAlias if the typedef is in a different file
     /// (same-file typedefs are handled via merging with the compound definition)
     fn parse_typedef_alias(
-        &mut self,
+        &self,
         unit: &DwarfUnit,
         entry: &DebuggingInformationEntry<DwarfReader>,
     ) -> Result<Option<TypedefAlias>> {
-        let attrs = AttributeExtractor::new(&self.dwarf);
+        let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
         let name = match attrs.get_string_attr(unit, entry, gimli::DW_AT_name) {
             Some(n) => n,
             None => return Ok(None),
         };
 
-        let line = attrs.get_u64_attr(entry, gimli::DW_AT_decl_line);
-        let decl_file = attrs.get_u64_attr(entry, gimli::DW_AT_decl_file);
+        let line = attrs.get_u64_attr(unit, entry, gimli::DW_AT_decl_line);
+        let decl_file = attrs.get_u64_attr(unit, entry, gimli::DW_AT_decl_file);
+        let alignment = attrs.get_u64_attr(unit, entry, gimli::DW_AT_alignment);
 
         // Get what this typedef points to
         let type_offset = match attrs.get_ref_attr(unit, entry, gimli::DW_AT_type) {
@@ -1381,9 +2006,11 @@ impl<'a> DwarfParser<'a> {
             None => return Ok(None), // typedef with no type
         };
 
-        // Resolve the target type
-        let unit_offset = gimli::UnitOffset(type_offset);
-        let mut entries = unit.entries_at_offset(unit_offset)?;
+        // Resolve the target type, which may be in another unit
+        let Some((type_unit, unit_offset)) = self.index.lookup(type_offset) else {
+            return Ok(None);
+        };
+        let mut entries = type_unit.entries_at_offset(unit_offset)?;
 
         if let Some((_, type_entry)) = entries.next_dfs()? {
             // Follow typedef chains to find the ultimate target type
@@ -1391,15 +2018,17 @@ impl<'a> DwarfParser<'a> {
             let mut max_depth = 20; // Prevent infinite loops
 
             loop {
-                let unit_offset = gimli::UnitOffset(current_offset);
-                let mut current_entries = unit.entries_at_offset(unit_offset)?;
+                let Some((current_unit, unit_offset)) = self.index.lookup(current_offset) else {
+                    break;
+                };
+                let mut current_entries = current_unit.entries_at_offset(unit_offset)?;
 
                 if let Some((_, current_entry)) = current_entries.next_dfs()? {
                     match current_entry.tag() {
                         gimli::DW_TAG_typedef => {
                             // Follow the typedef chain
                             if let Some(next_offset) =
-                                attrs.get_ref_attr(unit, current_entry, gimli::DW_AT_type)
+                                attrs.get_ref_attr(&current_unit, current_entry, gimli::DW_AT_type)
                             {
                                 current_offset = next_offset;
                                 max_depth -= 1;
@@ -1429,11 +2058,17 @@ impl<'a> DwarfParser<'a> {
 
                             // This is an actual struct definition (not forward declaration)
                             // Get the target type's decl_file to check if merge will happen
-                            let target_decl_file =
-                                attrs.get_u64_attr(current_entry, gimli::DW_AT_decl_file);
-
-                            // Check if typedef and target are in the same file (merge will happen)
-                            if is_same_decl_file(decl_file, target_decl_file) {
+                            let target_decl_file = attrs.get_u64_attr(
+                                &current_unit,
+                                current_entry,
+                                gimli::DW_AT_decl_file,
+                            );
+
+                            // Check if typedef and target are in the same file (merge will happen).
+                            // This logic MUST match unit_metadata's file table check.
+                            if die_index::shares_file_table(unit, &current_unit)
+                                && is_same_decl_file(decl_file, target_decl_file)
+                            {
                                 // Same file - these are handled by the typedef_map merging, skip them
                                 return Ok(None);
                             }
@@ -1452,18 +2087,22 @@ impl<'a> DwarfParser<'a> {
             // (e.g., "struct tag_mpFace" instead of "mpFace")
             let target_type = {
                 let mut resolver = TypeResolver::new(
-                    AttributeExtractor::new(&self.dwarf),
-                    &mut self.type_cache,
+                    AttributeExtractor::new(&self.dwarf, self.index.signatures()),
+                    &self.index,
+                    &self.type_cache,
+                    &self.layout_cache,
                     &self.typedef_map,
                 );
-                resolver.resolve_type_entry_raw(unit, type_entry)?
+                resolver.resolve_type_entry_raw(&type_unit, type_entry)?
             };
+            let alignment = requested_alignment(alignment, target_type.layout.map(|l| l.alignment));
 
             Ok(Some(TypedefAlias {
                 name,
                 target_type,
                 line,
                 decl_file,
+                alignment,
             }))
         } else {
             Ok(None)
@@ -1474,16 +2113,128 @@ impl<'a> DwarfParser<'a> {
     // Type resolution (delegated to TypeResolver)
     // ========================================================================
 
+    /// The natural alignment of a struct, class or union and whether it is
+    /// packed
+    fn compound_alignment(
+        &self,
+        unit: &DwarfUnit,
+        entry: &DebuggingInformationEntry<DwarfReader>,
+    ) -> Result<(u64, bool)> {
+        let resolver = TypeResolver::new(
+            AttributeExtractor::new(&self.dwarf, self.index.signatures()),
+            &self.index,
+            &self.type_cache,
+            &self.layout_cache,
+            &self.typedef_map,
+        );
+        resolver.compound_alignment(unit, entry)
+    }
+
     fn resolve_type(
-        &mut self,
+        &self,
         unit: &DwarfUnit,
         entry: &DebuggingInformationEntry<DwarfReader>,
     ) -> Result<TypeInfo> {
         let mut resolver = TypeResolver::new(
-            AttributeExtractor::new(&self.dwarf),
-            &mut self.type_cache,
+            AttributeExtractor::new(&self.dwarf, self.index.signatures()),
+            &self.index,
+            &self.type_cache,
+            &self.layout_cache,
             &self.typedef_map,
         );
         resolver.resolve_type(unit, entry)
     }
 }
+
+/// The alignment requested for a type, member or variable through
+/// DW_AT_alignment, if greater than it would have anyway
+fn requested_alignment(alignment: Option<u64>, natural: Option<u64>) -> Option<u64> {
+    alignment.filter(|&alignment| natural.is_none_or(|natural| alignment > natural))
+}
+
+/// Anonymous enums defined in a body can only be named by their definition,
+/// so it moves into the declarations of the variables of that type, given
+/// the offsets of their types and of the local types
+fn inline_anonymous_enums(
+    variables: &mut [Variable],
+    variable_types: &[Option<usize>],
+    local_types: &mut Vec<Element>,
+    local_type_offsets: &[usize],
+) {
+    let mut inlined = HashSet::new();
+    for (index, local_type) in local_types.iter().enumerate() {
+        let Element::Compound(compound) = local_type else {
+            continue;
+        };
+        if compound.compound_type != "enum" || compound.name.is_some() || compound.is_typedef {
+            continue;
+        }
+        let definition = compound.inline_enum_definition();
+        for (var, type_offset) in variables.iter_mut().zip(variable_types) {
+            if *type_offset == Some(local_type_offsets[index]) {
+                var.type_info.kind = TypeKind::Named(definition.clone());
+                inlined.insert(index);
+            }
+        }
+    }
+
+    *local_types = std::mem::take(local_types)
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| !inlined.contains(index))
+        .map(|(_, local_type)| local_type)
+        .collect();
+}
+
+/// Whether the parameter list of a C++ mangled function name ends in `...`
+fn has_mangled_ellipsis(linkage_name: &str) -> bool {
+    let Ok(symbol) = cpp_demangle::Symbol::new(linkage_name.as_bytes()) else {
+        return false;
+    };
+    let demangled = symbol.to_string();
+    demangled
+        .rfind(')')
+        .is_some_and(|end| demangled[..end].ends_with("..."))
+}
+
+/// The defined functions and variables in the symbol tables of linked object
+/// files, by address and whether they are functions, with C++ names
+/// demangled. None marks addresses several names share. Sections of
+/// relocatable objects all start at 0, so their symbols are left out.
+fn address_symbols(object_files: &[&object::File]) -> HashMap<(u64, bool), Option<String>> {
+    use object::{Object, ObjectKind, ObjectSymbol, SymbolKind};
+
+    let mut symbols: HashMap<(u64, bool), Option<String>> = HashMap::new();
+    for object_file in object_files {
+        if object_file.kind() == ObjectKind::Relocatable {
+            continue;
+        }
+        for symbol in object_file.symbols().chain(object_file.dynamic_symbols()) {
+            let is_function = match symbol.kind() {
+                SymbolKind::Text => true,
+                SymbolKind::Data => false,
+                _ => continue,
+            };
+            let Ok(name) = symbol.name() else {
+                continue;
+            };
+            if symbol.is_undefined() || symbol.address() == 0 || name.is_empty() {
+                continue;
+            }
+            let options = cpp_demangle::DemangleOptions::new().no_params();
+            let name = cpp_demangle::Symbol::new(name.as_bytes())
+                .ok()
+                .and_then(|symbol| symbol.demangle(&options).ok())
+                .unwrap_or_else(|| name.to_string());
+            symbols
+                .entry((symbol.address(), is_function))
+                .and_modify(|known| {
+                    if known.as_ref() != Some(&name) {
+                        *known = None;
+                    }
+                })
+                .or_insert(Some(name));
+        }
+    }
+    symbols
+}
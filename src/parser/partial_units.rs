@@ -0,0 +1,96 @@
+//! Partial unit support
+//!
+//! `dwz` moves DIEs that several compile units share into partial units,
+//! either in the same file or in a supplementary file, and replaces them with
+//! `DW_TAG_imported_unit` entries. The imported units are parsed along with
+//! each compile unit importing them, so that its output is complete.
+
+use crate::error::Result;
+use crate::types::*;
+
+use super::attributes::AttributeExtractor;
+use super::type_units::adopt_file_table;
+use super::DwarfParser;
+
+impl<'a> DwarfParser<'a> {
+    /// Parse the units imported by a compile unit, directly or through other
+    /// imported units. Their file indices are mapped onto the compile unit's
+    /// file table, which gains any files it didn't list.
+    pub(super) fn parse_imported_units_for(
+        &self,
+        unit: &DwarfUnit,
+        file_table: &mut Vec<String>,
+    ) -> Result<Vec<Element>> {
+        let mut elements = Vec::new();
+        let mut visited = vec![unit_base_offset(unit)];
+        self.inline_imported_units(unit, unit.comp_dir, file_table, &mut visited, &mut elements)?;
+        Ok(elements)
+    }
+
+    /// Append the elements of the units `unit` imports to `elements`, each
+    /// after the units it imports in turn
+    fn inline_imported_units(
+        &self,
+        unit: &DwarfUnit,
+        comp_dir: Option<DwarfReader>,
+        file_table: &mut Vec<String>,
+        visited: &mut Vec<usize>,
+        elements: &mut Vec<Element>,
+    ) -> Result<()> {
+        for imported_base in self.imported_units(unit)? {
+            if visited.contains(&imported_base) {
+                continue;
+            }
+            visited.push(imported_base);
+
+            let Some(imported_unit) = self.index.unit_at(imported_base) else {
+                continue;
+            };
+            self.inline_imported_units(&imported_unit, comp_dir, file_table, visited, elements)?;
+
+            let mut entries = imported_unit.entries();
+            let Some((_, root_entry)) = entries.next_dfs()? else {
+                continue;
+            };
+            // Partial units usually have no DW_AT_comp_dir of their own
+            let imported_file_table = self.extract_file_table(
+                &imported_unit,
+                root_entry,
+                imported_unit.comp_dir.or(comp_dir),
+            )?;
+
+            let mut imported_elements = Vec::new();
+            self.parse_children(&imported_unit, &mut entries, &mut imported_elements)?;
+
+            adopt_file_table(file_table, &imported_file_table, &mut imported_elements);
+            elements.extend(imported_elements);
+        }
+
+        Ok(())
+    }
+
+    /// The units a unit imports through its top-level DW_TAG_imported_unit
+    /// entries, by absolute offset
+    fn imported_units(&self, unit: &DwarfUnit) -> Result<Vec<usize>> {
+        let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
+        let mut imported = Vec::new();
+        let mut entries = unit.entries();
+        let mut absolute_depth = 0;
+
+        while let Some((depth_delta, entry)) = entries.next_dfs()? {
+            absolute_depth += depth_delta;
+            if absolute_depth != 1 || entry.tag() != gimli::DW_TAG_imported_unit {
+                continue;
+            }
+            // Units in a supplementary file that wasn't found are skipped
+            if let Some((imported_unit, _)) = attrs
+                .get_ref_attr(unit, entry, gimli::DW_AT_import)
+                .and_then(|offset| self.index.lookup(offset))
+            {
+                imported.push(unit_base_offset(&imported_unit));
+            }
+        }
+
+        Ok(imported)
+    }
+}
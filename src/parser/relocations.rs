@@ -0,0 +1,470 @@
+//! Architecture-aware relocation of DWARF sections
+//!
+//! Relocatable objects (`.o` files and archive members) leave string offsets,
+//! cross-section references and addresses in their DWARF sections for the
+//! linker to fill in. This module applies those relocations using the rules
+//! of the object's architecture, so that the sections can be read as if they
+//! had been linked.
+
+use gimli::Endianity;
+use object::{
+    elf, Architecture, Object, ObjectSection, ObjectSymbol, Relocation, RelocationFlags,
+    RelocationKind, RelocationTarget,
+};
+use std::borrow::Cow;
+use std::fmt;
+
+/// A relocation that could not be applied to a DWARF section
+#[derive(Debug, Clone)]
+pub struct UnappliedRelocation {
+    pub section: String,
+    pub offset: u64,
+    pub reason: String,
+}
+
+impl fmt::Display for UnappliedRelocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at offset 0x{:x}: {}",
+            self.section, self.offset, self.reason
+        )
+    }
+}
+
+/// The effect of a single relocation type on the field at its offset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RelocOp {
+    /// No effect (R_*_NONE, linker relaxation markers)
+    None,
+    /// Store `S + A` in a field of the given width in bytes
+    Absolute(usize),
+    /// Store `S + A`, with `S` taken relative to the start of the symbol's
+    /// section (DTP-relative TLS offsets, COFF SECREL)
+    SectionRelative(usize),
+    /// Add `S + A` to the value already in the field (RISC-V ADD*)
+    Add(usize),
+    /// Subtract `S + A` from the value already in the field (RISC-V SUB*)
+    Sub(usize),
+    /// Store `S + A` in the low 6 bits of a byte (RISC-V SET6)
+    Set6,
+    /// Subtract `S + A` from the low 6 bits of a byte (RISC-V SUB6)
+    Sub6,
+    /// Store `S + A` in an existing ULEB128 field (RISC-V SET_ULEB128)
+    SetUleb128,
+    /// Subtract `S + A` from an existing ULEB128 field (RISC-V SUB_ULEB128)
+    SubUleb128,
+}
+
+impl RelocOp {
+    /// Width of the field the operation reads, used for implicit addends
+    fn width(self) -> usize {
+        match self {
+            RelocOp::Absolute(w)
+            | RelocOp::SectionRelative(w)
+            | RelocOp::Add(w)
+            | RelocOp::Sub(w) => w,
+            RelocOp::Set6 | RelocOp::Sub6 => 1,
+            RelocOp::None | RelocOp::SetUleb128 | RelocOp::SubUleb128 => 0,
+        }
+    }
+}
+
+/// The symbol value a relocation refers to
+#[derive(Debug, Clone, Copy, Default)]
+struct SymbolValue {
+    /// Address of the symbol
+    address: u64,
+    /// Offset of the symbol from the start of its section
+    section_offset: u64,
+}
+
+/// Apply relocations to a DWARF section
+///
+/// Returns the relocated section data along with every relocation that
+/// could not be applied. Unapplied relocations leave the original bytes in place.
+/// The section is only copied once a relocation changes its contents.
+pub fn apply_relocations<'a>(
+    object_file: &object::File,
+    dwarf_section: &object::Section,
+    section_data: &'a [u8],
+    endian: gimli::RunTimeEndian,
+) -> (Cow<'a, [u8]>, Vec<UnappliedRelocation>) {
+    let section_name = dwarf_section.name().unwrap_or_default();
+
+    let mut data = Cow::Borrowed(section_data);
+    let mut unapplied = Vec::new();
+
+    for (offset, relocation) in dwarf_section.relocations() {
+        let result = decode_relocation(object_file, &relocation).and_then(|ops| {
+            apply_relocation(
+                object_file,
+                section_data,
+                &mut data,
+                offset,
+                &relocation,
+                &ops,
+                endian,
+            )
+        });
+
+        if let Err(reason) = result {
+            unapplied.push(UnappliedRelocation {
+                section: section_name.to_string(),
+                offset,
+                reason,
+            });
+        }
+    }
+
+    (data, unapplied)
+}
+
+/// Translate a relocation into the operations it performs.
+///
+/// Most relocations are a single operation. MIPS64 packs up to three types
+/// into one record; they are returned in order, with trailing R_MIPS_NONE
+/// entries dropped.
+fn decode_relocation(
+    object_file: &object::File,
+    relocation: &Relocation,
+) -> std::result::Result<Vec<RelocOp>, String> {
+    let architecture = object_file.architecture();
+    let r_type = match relocation.flags() {
+        RelocationFlags::Elf { r_type } => r_type,
+        _ => return generic_relocation_op(relocation).map(|op| vec![op]),
+    };
+
+    if architecture == Architecture::Mips64 && object_file.is_64() {
+        // r_type holds type, type2 and type3 in its low three bytes
+        let mut ops = Vec::with_capacity(3);
+        for r_type in [r_type & 0xff, (r_type >> 8) & 0xff, (r_type >> 16) & 0xff] {
+            let op = elf_relocation_op(architecture, r_type)
+                .ok_or_else(|| format!("unsupported MIPS64 relocation type {}", r_type))?;
+            ops.push(op);
+        }
+        while ops.len() > 1 && ops.last() == Some(&RelocOp::None) {
+            ops.pop();
+        }
+        return Ok(ops);
+    }
+
+    match elf_relocation_op(architecture, r_type) {
+        Some(op) => Ok(vec![op]),
+        None => generic_relocation_op(relocation)
+            .map_err(|_| {
+                format!(
+                    "unsupported relocation type {} for {:?}",
+                    r_type, architecture
+                )
+            })
+            .map(|op| vec![op]),
+    }
+}
+
+/// Map an ELF relocation type to its operation for architectures whose
+/// DWARF relocations we know about
+fn elf_relocation_op(architecture: Architecture, r_type: u32) -> Option<RelocOp> {
+    let op = match architecture {
+        Architecture::X86_64 | Architecture::X86_64_X32 => match r_type {
+            elf::R_X86_64_NONE => RelocOp::None,
+            elf::R_X86_64_64 => RelocOp::Absolute(8),
+            elf::R_X86_64_32 | elf::R_X86_64_32S => RelocOp::Absolute(4),
+            elf::R_X86_64_DTPOFF32 => RelocOp::SectionRelative(4),
+            elf::R_X86_64_DTPOFF64 => RelocOp::SectionRelative(8),
+            _ => return None,
+        },
+        Architecture::I386 => match r_type {
+            elf::R_386_NONE => RelocOp::None,
+            elf::R_386_32 => RelocOp::Absolute(4),
+            elf::R_386_TLS_LDO_32 => RelocOp::SectionRelative(4),
+            _ => return None,
+        },
+        Architecture::Arm => match r_type {
+            elf::R_ARM_NONE => RelocOp::None,
+            elf::R_ARM_ABS32 | elf::R_ARM_TARGET1 => RelocOp::Absolute(4),
+            elf::R_ARM_TLS_LDO32 => RelocOp::SectionRelative(4),
+            _ => return None,
+        },
+        Architecture::Aarch64 => match r_type {
+            // 256 is the alternative encoding of R_AARCH64_NONE
+            elf::R_AARCH64_NONE | 256 => RelocOp::None,
+            elf::R_AARCH64_ABS64 => RelocOp::Absolute(8),
+            elf::R_AARCH64_ABS32 => RelocOp::Absolute(4),
+            elf::R_AARCH64_ABS16 => RelocOp::Absolute(2),
+            elf::R_AARCH64_TLS_DTPREL => RelocOp::SectionRelative(8),
+            _ => return None,
+        },
+        Architecture::Mips | Architecture::Mips64 | Architecture::Mips64_N32 => match r_type {
+            elf::R_MIPS_NONE => RelocOp::None,
+            elf::R_MIPS_32 => RelocOp::Absolute(4),
+            elf::R_MIPS_64 => RelocOp::Absolute(8),
+            _ => return None,
+        },
+        Architecture::Riscv32 | Architecture::Riscv64 => match r_type {
+            elf::R_RISCV_NONE | elf::R_RISCV_RELAX => RelocOp::None,
+            elf::R_RISCV_32 => RelocOp::Absolute(4),
+            elf::R_RISCV_64 => RelocOp::Absolute(8),
+            elf::R_RISCV_ADD8 => RelocOp::Add(1),
+            elf::R_RISCV_ADD16 => RelocOp::Add(2),
+            elf::R_RISCV_ADD32 => RelocOp::Add(4),
+            elf::R_RISCV_ADD64 => RelocOp::Add(8),
+            elf::R_RISCV_SUB8 => RelocOp::Sub(1),
+            elf::R_RISCV_SUB16 => RelocOp::Sub(2),
+            elf::R_RISCV_SUB32 => RelocOp::Sub(4),
+            elf::R_RISCV_SUB64 => RelocOp::Sub(8),
+            elf::R_RISCV_SET6 => RelocOp::Set6,
+            elf::R_RISCV_SUB6 => RelocOp::Sub6,
+            elf::R_RISCV_SET8 => RelocOp::Absolute(1),
+            elf::R_RISCV_SET16 => RelocOp::Absolute(2),
+            elf::R_RISCV_SET32 => RelocOp::Absolute(4),
+            elf::R_RISCV_SET_ULEB128 => RelocOp::SetUleb128,
+            elf::R_RISCV_SUB_ULEB128 => RelocOp::SubUleb128,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(op)
+}
+
+/// Fall back to object's architecture-independent classification
+fn generic_relocation_op(relocation: &Relocation) -> std::result::Result<RelocOp, String> {
+    let width = match relocation.size() {
+        8 | 16 | 32 | 64 => relocation.size() as usize / 8,
+        size => return Err(format!("unsupported relocation size {}", size)),
+    };
+    match relocation.kind() {
+        RelocationKind::Absolute => Ok(RelocOp::Absolute(width)),
+        RelocationKind::SectionOffset => Ok(RelocOp::SectionRelative(width)),
+        kind => Err(format!("unsupported relocation kind {:?}", kind)),
+    }
+}
+
+/// Resolve the value of a relocation's target
+fn symbol_value(
+    object_file: &object::File,
+    relocation: &Relocation,
+) -> std::result::Result<SymbolValue, String> {
+    match relocation.target() {
+        RelocationTarget::Symbol(symbol_idx) => {
+            let symbol = object_file
+                .symbol_by_index(symbol_idx)
+                .map_err(|_| format!("invalid symbol index {}", symbol_idx.0))?;
+            let section_address = symbol
+                .section_index()
+                .and_then(|index| object_file.section_by_index(index).ok())
+                .map(|section| section.address())
+                .unwrap_or(0);
+            Ok(SymbolValue {
+                address: symbol.address(),
+                section_offset: symbol.address().wrapping_sub(section_address),
+            })
+        }
+        RelocationTarget::Section(section_idx) => {
+            let section = object_file
+                .section_by_index(section_idx)
+                .map_err(|_| format!("invalid section index {}", section_idx.0))?;
+            Ok(SymbolValue {
+                address: section.address(),
+                section_offset: 0,
+            })
+        }
+        RelocationTarget::Absolute => Ok(SymbolValue::default()),
+        _ => Err("unsupported relocation target".to_string()),
+    }
+}
+
+/// Apply a decoded relocation to `data`.
+///
+/// `original` is the unrelocated section, from which implicit (REL) addends
+/// are read so that several relocations at the same offset each see the
+/// addend the assembler wrote.
+fn apply_relocation(
+    object_file: &object::File,
+    original: &[u8],
+    data: &mut Cow<[u8]>,
+    offset: u64,
+    relocation: &Relocation,
+    ops: &[RelocOp],
+    endian: gimli::RunTimeEndian,
+) -> std::result::Result<(), String> {
+    let offset = usize::try_from(offset).map_err(|_| "offset out of range".to_string())?;
+    let Some((&last, leading)) = ops.split_last() else {
+        return Ok(());
+    };
+
+    let mut addend = if relocation.has_implicit_addend() {
+        let width = ops[0].width();
+        let field = original
+            .get(offset..offset + width)
+            .ok_or_else(|| "relocation extends past end of section".to_string())?;
+        read_field(field, endian)
+    } else {
+        relocation.addend() as u64
+    };
+    let mut symbol = symbol_value(object_file, relocation)?;
+
+    // Composed relocations: each result becomes the addend of the next
+    // operation, which applies to a null symbol. Only the last one is written.
+    for &op in leading {
+        addend = match op {
+            RelocOp::None => addend,
+            RelocOp::Absolute(_) => symbol.address.wrapping_add(addend),
+            RelocOp::SectionRelative(_) => symbol.section_offset.wrapping_add(addend),
+            _ => return Err(format!("{:?} cannot be composed", op)),
+        };
+        symbol = SymbolValue::default();
+    }
+
+    let field_width = match last {
+        RelocOp::None => return Ok(()),
+        RelocOp::SetUleb128 | RelocOp::SubUleb128 => {
+            let (_, len) = data
+                .get(offset..)
+                .and_then(read_uleb128)
+                .ok_or_else(|| "malformed ULEB128 field".to_string())?;
+            len
+        }
+        op => op.width(),
+    };
+    let range = offset..offset + field_width;
+    let current_field = data
+        .get(range.clone())
+        .ok_or_else(|| "relocation extends past end of section".to_string())?;
+    let mut new_field = current_field.to_vec();
+    let field = new_field.as_mut_slice();
+
+    let value = symbol.address.wrapping_add(addend);
+    match last {
+        RelocOp::None => {}
+        RelocOp::Absolute(_) => write_field(field, value, endian),
+        RelocOp::SectionRelative(_) => {
+            write_field(field, symbol.section_offset.wrapping_add(addend), endian)
+        }
+        RelocOp::Add(_) => {
+            let current = read_field(field, endian);
+            write_field(field, current.wrapping_add(value), endian);
+        }
+        RelocOp::Sub(_) => {
+            let current = read_field(field, endian);
+            write_field(field, current.wrapping_sub(value), endian);
+        }
+        RelocOp::Set6 => field[0] = (field[0] & 0xc0) | (value as u8 & 0x3f),
+        RelocOp::Sub6 => {
+            let current = field[0] & 0x3f;
+            field[0] = (field[0] & 0xc0) | (current.wrapping_sub(value as u8) & 0x3f);
+        }
+        RelocOp::SetUleb128 => write_uleb128(field, value),
+        RelocOp::SubUleb128 => {
+            let (current, _) = read_uleb128(field).unwrap_or((0, field.len()));
+            write_uleb128(field, current.wrapping_sub(value));
+        }
+    }
+
+    if data[range.clone()] != new_field[..] {
+        data.to_mut()[range].copy_from_slice(&new_field);
+    }
+    Ok(())
+}
+
+/// Read an unsigned integer filling `field`
+fn read_field(field: &[u8], endian: gimli::RunTimeEndian) -> u64 {
+    match field.len() {
+        1 => field[0] as u64,
+        2 => endian.read_u16(field) as u64,
+        4 => endian.read_u32(field) as u64,
+        8 => endian.read_u64(field),
+        _ => 0,
+    }
+}
+
+/// Write `value`, truncated to the width of `field`
+fn write_field(field: &mut [u8], value: u64, endian: gimli::RunTimeEndian) {
+    match field.len() {
+        1 => field[0] = value as u8,
+        2 => endian.write_u16(field, value as u16),
+        4 => endian.write_u32(field, value as u32),
+        8 => endian.write_u64(field, value),
+        _ => {}
+    }
+}
+
+/// Read a ULEB128 value, returning it with its encoded length
+fn read_uleb128(bytes: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, &byte) in bytes.iter().enumerate() {
+        if i < 10 {
+            value |= ((byte & 0x7f) as u64) << (7 * i);
+        }
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// Overwrite a ULEB128 field in place, keeping its encoded length
+fn write_uleb128(field: &mut [u8], mut value: u64) {
+    let len = field.len();
+    for (i, byte) in field.iter_mut().enumerate() {
+        let continuation = if i + 1 < len { 0x80 } else { 0 };
+        *byte = (value & 0x7f) as u8 | continuation;
+        value >>= 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Outcome of relocating one section of the x86-64 sample object
+    struct Relocated {
+        relocation_count: usize,
+        borrowed: bool,
+        changed: bool,
+    }
+
+    fn relocate_sample_section(name: &str) -> Relocated {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/samples/jcphuff.o");
+        let data = std::fs::read(path).unwrap();
+        let object_file = object::File::parse(&*data).unwrap();
+        let section = object_file.section_by_name(name).unwrap();
+        let section_data = section.data().unwrap();
+        let (relocated, unapplied) = apply_relocations(
+            &object_file,
+            &section,
+            section_data,
+            gimli::RunTimeEndian::Little,
+        );
+        assert!(unapplied.is_empty(), "{:?}", unapplied);
+        Relocated {
+            relocation_count: section.relocations().count(),
+            borrowed: matches!(relocated, Cow::Borrowed(_)),
+            changed: relocated[..] != section_data[..],
+        }
+    }
+
+    #[test]
+    fn section_without_relocations_is_borrowed() {
+        let relocated = relocate_sample_section(".debug_abbrev");
+        assert_eq!(relocated.relocation_count, 0);
+        assert!(relocated.borrowed);
+    }
+
+    #[test]
+    fn relocations_writing_the_existing_bytes_keep_the_section_borrowed() {
+        // Each function has its own section, so the line program's addresses
+        // are relocated to offset 0, which the object already holds
+        let relocated = relocate_sample_section(".debug_line");
+        assert!(relocated.relocation_count > 0);
+        assert!(!relocated.changed);
+        assert!(relocated.borrowed);
+    }
+
+    #[test]
+    fn effective_relocations_copy_the_section() {
+        let relocated = relocate_sample_section(".debug_info");
+        assert!(relocated.changed);
+        assert!(!relocated.borrowed);
+    }
+}
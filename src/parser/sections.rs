@@ -0,0 +1,194 @@
+//! Loading DWARF sections from object files
+//!
+//! Sections are decompressed and relocated as needed. Any data that has to be
+//! copied is kept in a caller-provided store of boxed slices, whose heap
+//! addresses stay stable for as long as the store itself is kept alive. Files
+//! are memory-mapped into the same store, so sections that need neither
+//! decompression nor relocation are read in place.
+
+use crate::error::Result;
+use crate::types::DwarfReader;
+use gimli::{Dwarf, DwarfPackage};
+use memmap2::Mmap;
+use object::{Object, ObjectSection};
+use std::borrow::Cow;
+use std::fs::File;
+use std::path::Path;
+
+use super::relocations;
+
+/// Data backing the readers handed out by this module
+#[derive(Default)]
+pub struct SectionStore {
+    owned: Vec<Box<[u8]>>,
+    mapped: Vec<Mmap>,
+}
+
+impl SectionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Pick the gimli byte order matching the object file's header
+pub fn object_endianness(object_file: &object::File) -> gimli::RunTimeEndian {
+    if object_file.is_little_endian() {
+        gimli::RunTimeEndian::Little
+    } else {
+        gimli::RunTimeEndian::Big
+    }
+}
+
+/// Move `data` into `store` and return a slice that lives as long as the store
+pub fn store_data<'a>(store: &mut SectionStore, data: Vec<u8>) -> &'a [u8] {
+    let boxed = data.into_boxed_slice();
+    // SAFETY: We're extending the lifetime of the reference from the Box to 'a.
+    // This is safe because:
+    // 1. The Box gives the data a stable heap address, even when the Box is moved
+    // 2. The store is moved into the DwarfParser that uses the returned slices
+    // 3. The parser drops its readers no later than the store
+    let slice = unsafe { std::slice::from_raw_parts(boxed.as_ptr(), boxed.len()) };
+    store.owned.push(boxed);
+    slice
+}
+
+/// Memory-map a file for reading
+pub fn map_file(path: &Path) -> std::io::Result<Mmap> {
+    let file = File::open(path)?;
+    // SAFETY: The mapping is only read. Like any tool reading build outputs,
+    // we rely on the file not being truncated or rewritten while we run.
+    unsafe { Mmap::map(&file) }
+}
+
+/// Memory-map a file into `store` and return its contents, which live as
+/// long as the store
+pub fn store_mapped_file<'a>(store: &mut SectionStore, path: &Path) -> Result<&'a [u8]> {
+    let mmap = map_file(path)?;
+    // SAFETY: As for store_data; the mapped address doesn't change when the
+    // Mmap is moved, and the mapping lasts until the store drops it
+    let slice = unsafe { std::slice::from_raw_parts(mmap.as_ptr(), mmap.len()) };
+    store.mapped.push(mmap);
+    Ok(slice)
+}
+
+/// Read one section, decompressing and relocating it if needed.
+///
+/// Relocatable objects keep each type unit in a COMDAT group of its own, so
+/// there may be several sections of the same name. They are concatenated,
+/// as the linker would do.
+fn load_section<'a>(
+    object_file: &object::File<'a>,
+    name: &str,
+    store: &mut SectionStore,
+) -> DwarfReader<'a> {
+    let endian = object_endianness(object_file);
+
+    let mut parts: Vec<Cow<'a, [u8]>> = Vec::new();
+    for section in object_file
+        .sections()
+        .filter(|section| section.name() == Ok(name))
+    {
+        let section_data = section.uncompressed_data().unwrap_or(Cow::Borrowed(&[]));
+
+        let (relocated_data, unapplied) =
+            relocations::apply_relocations(object_file, &section, &section_data, endian);
+        for relocation in &unapplied {
+            eprintln!("Warning: Could not apply relocation in {}", relocation);
+        }
+
+        parts.push(if let Cow::Owned(data) = relocated_data {
+            Cow::Owned(data)
+        } else {
+            section_data
+        });
+    }
+
+    let data: &'a [u8] = match parts.len() {
+        0 => &[],
+        1 => match parts.pop().unwrap() {
+            // Section was decompressed or relocated - store the copy
+            Cow::Owned(data) => store_data(store, data),
+            Cow::Borrowed(data) => data,
+        },
+        _ => store_data(store, parts.concat()),
+    };
+
+    gimli::EndianSlice::new(data, endian)
+}
+
+/// Load the DWARF sections of an object file.
+///
+/// With `split` set, the `.dwo` variants of the sections are read, as found
+/// in split DWARF object files.
+pub fn load_dwarf<'a>(
+    object_file: &object::File<'a>,
+    split: bool,
+    store: &mut SectionStore,
+) -> Result<Dwarf<DwarfReader<'a>>> {
+    load_dwarf_from(&[object_file], split, store)
+}
+
+/// Load the DWARF sections of a separate debug file, taking any it lacks
+/// from the stripped binary it belongs to
+pub fn load_separate_dwarf<'a>(
+    debug_file: &object::File<'a>,
+    binary: &object::File<'a>,
+    store: &mut SectionStore,
+) -> Result<Dwarf<DwarfReader<'a>>> {
+    load_dwarf_from(&[debug_file, binary], false, store)
+}
+
+/// Load each DWARF section from the first of `object_files` that has it
+fn load_dwarf_from<'a>(
+    object_files: &[&object::File<'a>],
+    split: bool,
+    store: &mut SectionStore,
+) -> Result<Dwarf<DwarfReader<'a>>> {
+    let endian = object_endianness(object_files[0]);
+    let mut dwarf = Dwarf::load(
+        |id: gimli::SectionId| -> std::result::Result<DwarfReader<'a>, gimli::Error> {
+            let name = if split {
+                id.dwo_name()
+            } else {
+                Some(id.name())
+            };
+            let object_file = name.and_then(|name| {
+                object_files
+                    .iter()
+                    .find(|object_file| object_file.section_by_name(name).is_some())
+            });
+            Ok(match (name, object_file) {
+                (Some(name), Some(object_file)) => load_section(object_file, name, store),
+                _ => gimli::EndianSlice::new(&[], endian),
+            })
+        },
+    )?;
+    if split {
+        dwarf.file_type = gimli::DwarfFileType::Dwo;
+    }
+    Ok(dwarf)
+}
+
+/// Load the sections of a DWARF package (`.dwp`) file
+pub fn load_dwarf_package<'a>(
+    object_file: &object::File<'a>,
+    store: &mut SectionStore,
+) -> Result<DwarfPackage<DwarfReader<'a>>> {
+    let endian = object_endianness(object_file);
+    let package = DwarfPackage::load(
+        |id: gimli::SectionId| -> std::result::Result<DwarfReader<'a>, gimli::Error> {
+            Ok(match id.dwo_name() {
+                Some(name) => load_section(object_file, name, store),
+                None => gimli::EndianSlice::new(&[], endian),
+            })
+        },
+        gimli::EndianSlice::new(&[], endian),
+    )?;
+    Ok(package)
+}
+
+/// Whether an object file only carries split DWARF sections (a `.dwo` file)
+pub fn is_split_dwarf_object(object_file: &object::File) -> bool {
+    object_file.section_by_name(".debug_info").is_none()
+        && object_file.section_by_name(".debug_info.dwo").is_some()
+}
@@ -0,0 +1,195 @@
+//! Split DWARF support
+//!
+//! Objects built with `-gsplit-dwarf` only carry skeleton units in their own
+//! `.debug_info`. The full unit lives in a separate `.dwo` file named by the
+//! skeleton's `DW_AT_dwo_name` (`DW_AT_GNU_dwo_name` before DWARF 5), or in a
+//! `.dwp` package combining the `.dwo` files of a whole program.
+
+use crate::error::Result;
+use crate::types::*;
+use gimli::DwarfPackage;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use super::sections::{self, SectionStore};
+use super::DwarfParser;
+
+/// A DWARF package, loaded once and shared by all parsers of an input
+pub struct PackageFile {
+    // Only reachable through `package`, which ties its readers to a borrow
+    // of `self`. Declared before the store, so it is dropped while the data
+    // is still alive.
+    package: DwarfPackage<DwarfReader<'static>>,
+    _store: SectionStore,
+}
+
+impl PackageFile {
+    /// Map and index the package at `path`
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut store = SectionStore::new();
+        // SAFETY: The readers borrow `store`, which is moved into the
+        // PackageFile with them. They are only handed out by `package`, for
+        // no longer than the PackageFile is borrowed, so `_store` outlives
+        // every reader, including those of the split parsers built over it.
+        let data: &'static [u8] = sections::store_mapped_file(&mut store, path)?;
+        let object = object::File::parse(data)?;
+        let package = sections::load_dwarf_package(&object, &mut store)?;
+        Ok(PackageFile {
+            package,
+            _store: store,
+        })
+    }
+
+    /// The package's sections, valid while it is borrowed
+    fn package(&self) -> &DwarfPackage<DwarfReader<'_>> {
+        &self.package
+    }
+}
+
+impl fmt::Debug for PackageFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PackageFile").finish_non_exhaustive()
+    }
+}
+
+/// Locate a `.dwo` file.
+///
+/// Relative names are resolved against the compilation directory first,
+/// then against each search path, both with the name as given and with
+/// just its final component.
+pub fn find_dwo_file(
+    dwo_name: &str,
+    comp_dir: Option<&str>,
+    search_paths: &[PathBuf],
+) -> Option<PathBuf> {
+    let dwo_path = Path::new(dwo_name);
+    if dwo_path.is_absolute() && dwo_path.is_file() {
+        return Some(dwo_path.to_path_buf());
+    }
+
+    let mut candidates = Vec::new();
+    if let Some(comp_dir) = comp_dir {
+        candidates.push(Path::new(comp_dir).join(dwo_path));
+    }
+    for search_path in search_paths {
+        if dwo_path.is_relative() {
+            candidates.push(search_path.join(dwo_path));
+        }
+        if let Some(file_name) = dwo_path.file_name() {
+            candidates.push(search_path.join(file_name));
+        }
+    }
+
+    candidates.into_iter().find(|candidate| candidate.is_file())
+}
+
+impl<'a> DwarfParser<'a> {
+    /// Whether `unit` is a skeleton whose contents live in a split DWARF file
+    pub(super) fn is_skeleton_unit(&self, unit: &DwarfUnit) -> bool {
+        self.dwarf.file_type == gimli::DwarfFileType::Main && unit.dwo_id.is_some()
+    }
+
+    /// Parse the split unit belonging to a skeleton unit.
+    ///
+    /// Returns `None` (after a warning) if neither the package nor a `.dwo`
+    /// file provides the unit.
+    pub(super) fn parse_split_unit(&self, skeleton: &DwarfUnit<'a>) -> Result<Option<CompileUnit>> {
+        let Some(dwo_id) = skeleton.dwo_id else {
+            return Ok(None);
+        };
+
+        let mut split_parser = match self.load_split_parser(skeleton, dwo_id)? {
+            Some(parser) => parser,
+            None => return Ok(None),
+        };
+
+        // A .dwo may also contain split type units; pick the compile unit
+        let units = split_parser.index.units().to_vec();
+        for unit in &units {
+            if unit.dwo_id != Some(dwo_id) {
+                continue;
+            }
+
+            let metadata = split_parser.unit_metadata(unit)?;
+            split_parser.collect_metadata(unit_base_offset(unit), metadata);
+            let mut cu = split_parser.parse_compile_unit(unit)?;
+            if let Some(cu) = &mut cu {
+                // The split unit may omit its name; the skeleton always has it
+                if cu.name == "unknown" {
+                    if let Some(name) = skeleton.name {
+                        cu.name = String::from_utf8_lossy(name.slice()).to_string();
+                    }
+                }
+            }
+            return Ok(cu);
+        }
+
+        eprintln!(
+            "Warning: Split DWARF unit 0x{:x} not found in its .dwo file",
+            dwo_id.0
+        );
+        Ok(None)
+    }
+
+    /// Build a parser over the split unit's sections, from the package if one
+    /// was given, otherwise from the `.dwo` file the skeleton names.
+    ///
+    /// The parser borrows this one, whose configuration holds the package.
+    fn load_split_parser<'s>(
+        &'s self,
+        skeleton: &DwarfUnit<'s>,
+        dwo_id: gimli::DwoId,
+    ) -> Result<Option<DwarfParser<'s>>> {
+        if let Some(package_file) = &self.config.dwp {
+            if let Some(dwarf) = package_file.package().find_cu(dwo_id, &self.dwarf)? {
+                return Ok(Some(DwarfParser::from_dwarf(
+                    dwarf,
+                    SectionStore::new(),
+                    self.config.clone(),
+                    Some(skeleton),
+                )?));
+            }
+        }
+
+        let dwo_name = match skeleton.dwo_name()? {
+            Some(value) => self
+                .dwarf
+                .attr_string(skeleton, value)
+                .map(|name| String::from_utf8_lossy(name.slice()).to_string())
+                .ok(),
+            None => None,
+        };
+        let Some(dwo_name) = dwo_name else {
+            eprintln!(
+                "Warning: Skeleton unit 0x{:x} has no split DWARF file name",
+                dwo_id.0
+            );
+            return Ok(None);
+        };
+
+        let comp_dir = skeleton
+            .comp_dir
+            .map(|dir| String::from_utf8_lossy(dir.slice()).to_string());
+        let Some(dwo_path) = find_dwo_file(
+            &dwo_name,
+            comp_dir.as_deref(),
+            &self.config.dwo_search_paths,
+        ) else {
+            eprintln!("Warning: Could not find split DWARF file {}", dwo_name);
+            return Ok(None);
+        };
+
+        let mut store = SectionStore::new();
+        let dwo_data = sections::store_mapped_file(&mut store, &dwo_path)?;
+        let object = object::File::parse(dwo_data)?;
+        let mut dwarf = sections::load_dwarf(&object, true, &mut store)?;
+        dwarf.make_dwo(&self.dwarf);
+
+        Ok(Some(DwarfParser::from_dwarf(
+            dwarf,
+            store,
+            self.config.clone(),
+            Some(skeleton),
+        )?))
+    }
+}
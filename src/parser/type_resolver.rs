@@ -4,25 +4,33 @@ use crate::error::Result;
 use crate::types::*;
 use gimli::DebuggingInformationEntry;
 use std::collections::HashMap;
+use std::sync::Mutex;
 
-use super::attributes::AttributeExtractor;
+use super::attributes::{AttributeExtractor, BoundValue};
+use super::die_index::DieIndex;
 
 /// Handles type resolution from DWARF type references
-pub struct TypeResolver<'a> {
+pub struct TypeResolver<'a, 'd> {
     attrs: AttributeExtractor<'a>,
-    type_cache: &'a mut HashMap<usize, TypeInfo>,
+    index: &'a DieIndex<'d>,
+    type_cache: &'a Mutex<HashMap<usize, TypeInfo>>,
+    layout_cache: &'a Mutex<HashMap<usize, Option<TypeLayout>>>,
     typedef_map: &'a HashMap<usize, TypedefInfo>,
 }
 
-impl<'a> TypeResolver<'a> {
+impl<'a, 'd> TypeResolver<'a, 'd> {
     pub fn new(
         attrs: AttributeExtractor<'a>,
-        type_cache: &'a mut HashMap<usize, TypeInfo>,
+        index: &'a DieIndex<'d>,
+        type_cache: &'a Mutex<HashMap<usize, TypeInfo>>,
+        layout_cache: &'a Mutex<HashMap<usize, Option<TypeLayout>>>,
         typedef_map: &'a HashMap<usize, TypedefInfo>,
     ) -> Self {
         Self {
             attrs,
+            index,
             type_cache,
+            layout_cache,
             typedef_map,
         }
     }
@@ -34,36 +42,35 @@ impl<'a> TypeResolver<'a> {
     ) -> Result<TypeInfo> {
         let type_offset = match self.attrs.get_ref_attr(unit, entry, gimli::DW_AT_type) {
             Some(offset) => offset,
-            None => return Ok(TypeInfo::new("void".to_string())),
+            None => return Ok(TypeInfo::void()),
         };
 
-        self.resolve_type_from_offset(unit, type_offset)
+        self.resolve_type_from_offset(type_offset)
     }
 
-    pub fn resolve_type_from_offset(
-        &mut self,
-        unit: &DwarfUnit,
-        offset: usize,
-    ) -> Result<TypeInfo> {
-        // Convert unit-relative offset to absolute offset for caching
-        let absolute_offset = unit_base_offset(unit) + offset;
-
-        // Check cache using absolute offset
-        if let Some(cached) = self.type_cache.get(&absolute_offset) {
+    /// Resolve the type at an absolute `.debug_info` offset, in whichever
+    /// unit it lives
+    pub fn resolve_type_from_offset(&mut self, offset: usize) -> Result<TypeInfo> {
+        // The lock isn't held while resolving, as that recurses
+        if let Some(cached) = self.type_cache.lock().unwrap().get(&offset) {
             return Ok(cached.clone());
         }
 
-        let unit_offset = gimli::UnitOffset(offset);
+        let Some((unit, unit_offset)) = self.index.lookup(offset) else {
+            return Ok(TypeInfo::void());
+        };
         let mut entries = unit.entries_at_offset(unit_offset)?;
 
         if let Some((_, type_entry)) = entries.next_dfs()? {
-            let type_info = self.resolve_type_entry(unit, type_entry)?;
-            // Cache using absolute offset
-            self.type_cache.insert(absolute_offset, type_info.clone());
+            let type_info = self.resolve_type_entry(&unit, type_entry)?;
+            self.type_cache
+                .lock()
+                .unwrap()
+                .insert(offset, type_info.clone());
             return Ok(type_info);
         }
 
-        Ok(TypeInfo::new("void".to_string()))
+        Ok(TypeInfo::void())
     }
 
     pub fn resolve_type_entry(
@@ -84,14 +91,360 @@ impl<'a> TypeResolver<'a> {
         self.resolve_type_entry_impl(unit, entry, false)
     }
 
+    /// The layout of the type at an absolute `.debug_info` offset
+    fn layout_at(&self, offset: usize) -> Result<Option<TypeLayout>> {
+        if let Some(cached) = self.layout_cache.lock().unwrap().get(&offset) {
+            return Ok(*cached);
+        }
+
+        let Some((unit, unit_offset)) = self.index.lookup(offset) else {
+            return Ok(None);
+        };
+        let mut entries = unit.entries_at_offset(unit_offset)?;
+        let layout = match entries.next_dfs()? {
+            Some((_, entry)) => self.entry_layout(&unit, entry)?,
+            None => None,
+        };
+        self.layout_cache.lock().unwrap().insert(offset, layout);
+        Ok(layout)
+    }
+
+    /// The layout of the type a DIE refers to through DW_AT_type
+    fn target_layout(
+        &self,
+        unit: &DwarfUnit,
+        entry: &DebuggingInformationEntry<DwarfReader>,
+    ) -> Result<Option<TypeLayout>> {
+        match self.attrs.get_ref_attr(unit, entry, gimli::DW_AT_type) {
+            Some(offset) => self.layout_at(offset),
+            None => Ok(None),
+        }
+    }
+
+    /// The layout of a type entry, from its DW_AT_byte_size and
+    /// DW_AT_alignment. Pointers don't look at their targets, so that
+    /// self-referential types terminate. Incomplete types have no layout.
+    fn entry_layout(
+        &self,
+        unit: &DwarfUnit,
+        entry: &DebuggingInformationEntry<DwarfReader>,
+    ) -> Result<Option<TypeLayout>> {
+        let byte_size = self.attrs.get_u64_attr(unit, entry, gimli::DW_AT_byte_size);
+        let alignment = self.attrs.get_u64_attr(unit, entry, gimli::DW_AT_alignment);
+        let address_size = unit.header.address_size() as u64;
+
+        let layout = match entry.tag() {
+            gimli::DW_TAG_base_type => byte_size.map(|size| TypeLayout {
+                size,
+                alignment: alignment
+                    .unwrap_or_else(|| base_type_alignment(base_type_encoding(entry), size)),
+            }),
+            gimli::DW_TAG_pointer_type
+            | gimli::DW_TAG_reference_type
+            | gimli::DW_TAG_rvalue_reference_type => {
+                let size = byte_size.unwrap_or(address_size);
+                Some(TypeLayout {
+                    size,
+                    alignment: alignment.unwrap_or(size),
+                })
+            }
+            gimli::DW_TAG_ptr_to_member_type => {
+                // Pointers to methods also hold the `this` adjustment
+                let points_to_method = match self.attrs.get_ref_attr(unit, entry, gimli::DW_AT_type)
+                {
+                    Some(offset) => {
+                        self.index
+                            .lookup(offset)
+                            .is_some_and(|(target_unit, target)| {
+                                target_unit.entry(target).is_ok_and(|target| {
+                                    target.tag() == gimli::DW_TAG_subroutine_type
+                                })
+                            })
+                    }
+                    None => false,
+                };
+                let size = byte_size.unwrap_or(if points_to_method {
+                    address_size * 2
+                } else {
+                    address_size
+                });
+                Some(TypeLayout {
+                    size,
+                    alignment: alignment.unwrap_or(address_size),
+                })
+            }
+            gimli::DW_TAG_typedef
+            | gimli::DW_TAG_const_type
+            | gimli::DW_TAG_volatile_type
+            | gimli::DW_TAG_restrict_type
+            | gimli::DW_TAG_atomic_type => {
+                let target = self.target_layout(unit, entry)?;
+                match alignment {
+                    Some(alignment) => target.map(|target| TypeLayout {
+                        alignment,
+                        ..target
+                    }),
+                    None => target,
+                }
+            }
+            gimli::DW_TAG_array_type => {
+                let Some(element) = self.target_layout(unit, entry)? else {
+                    return Ok(None);
+                };
+                let mut count = 1;
+                let mut entries = unit.entries_at_offset(entry.offset())?;
+                entries.next_dfs()?; // Skip the array type itself
+                let mut absolute_depth = 0;
+                while let Some((depth_delta, child_entry)) = entries.next_dfs()? {
+                    absolute_depth += depth_delta;
+                    if absolute_depth <= 0 {
+                        break;
+                    }
+                    if child_entry.tag() == gimli::DW_TAG_subrange_type {
+                        count *= self.array_bound(unit, child_entry)?.count().unwrap_or(0);
+                    }
+                }
+                let size = byte_size.unwrap_or(element.size * count);
+                // Vectors are aligned to their size
+                let natural_alignment = if self.attrs.get_bool_attr(entry, gimli::DW_AT_GNU_vector)
+                {
+                    base_type_alignment(None, size)
+                } else {
+                    element.alignment
+                };
+                Some(TypeLayout {
+                    size,
+                    alignment: alignment.unwrap_or(natural_alignment),
+                })
+            }
+            gimli::DW_TAG_structure_type
+            | gimli::DW_TAG_class_type
+            | gimli::DW_TAG_union_type
+            | gimli::DW_TAG_enumeration_type => {
+                if let Some(offset) = self.attrs.get_ref_attr(unit, entry, gimli::DW_AT_signature) {
+                    return self.layout_at(offset);
+                }
+                let Some(size) = byte_size else {
+                    return Ok(None);
+                };
+                let alignment = match alignment {
+                    Some(alignment) => alignment,
+                    None if entry.tag() == gimli::DW_TAG_enumeration_type => {
+                        match self.target_layout(unit, entry)? {
+                            Some(underlying) => underlying.alignment,
+                            None => base_type_alignment(None, size),
+                        }
+                    }
+                    None => self.compound_alignment(unit, entry)?.0,
+                };
+                Some(TypeLayout { size, alignment })
+            }
+            _ => None,
+        };
+        Ok(layout)
+    }
+
+    /// The natural alignment of a struct, class or union, that of its most
+    /// aligned non-static data member or base class, and whether it is
+    /// packed: a member sits below its alignment, or the size isn't a
+    /// multiple of it. Packed types are 1 byte aligned.
+    pub fn compound_alignment(
+        &self,
+        unit: &DwarfUnit,
+        entry: &DebuggingInformationEntry<DwarfReader>,
+    ) -> Result<(u64, bool)> {
+        let address_size = unit.header.address_size() as u64;
+        let mut alignment = 1;
+        let mut required_alignment = 1;
+        let mut is_packed = false;
+
+        let mut entries = unit.entries_at_offset(entry.offset())?;
+        entries.next_dfs()?; // Skip the compound type itself
+        let mut absolute_depth = 0;
+        while let Some((depth_delta, child_entry)) = entries.next_dfs()? {
+            absolute_depth += depth_delta;
+            if absolute_depth <= 0 {
+                break;
+            }
+            let is_data_member = matches!(
+                child_entry.tag(),
+                gimli::DW_TAG_member | gimli::DW_TAG_inheritance
+            ) && !self.attrs.is_static_member(child_entry);
+            if absolute_depth != 1 || !is_data_member {
+                continue;
+            }
+            let Some(member) = self.target_layout(unit, child_entry)? else {
+                continue;
+            };
+            let explicit = self
+                .attrs
+                .get_u64_attr(unit, child_entry, gimli::DW_AT_alignment);
+            alignment = alignment.max(explicit.unwrap_or(member.alignment));
+
+            // Bitfields share their storage unit with other members, and only
+            // cross into the next one in packed structs. Types larger than a
+            // pointer may use smaller units, as on i386.
+            if let Some(bit_size) =
+                self.attrs
+                    .get_u64_attr(unit, child_entry, gimli::DW_AT_bit_size)
+            {
+                if member.size <= address_size {
+                    if let Some(position) =
+                        self.attrs
+                            .get_bit_position(unit, child_entry, bit_size, Some(member.size))
+                    {
+                        is_packed |= straddles_storage_unit(position, bit_size, member.size);
+                    }
+                }
+                continue;
+            }
+            // Some targets align types larger than a pointer to less inside
+            // structs, as i386 does doubles
+            let required = explicit.unwrap_or(member.alignment.min(address_size));
+            required_alignment = required_alignment.max(required);
+            if let Some(offset) = self.attrs.get_member_offset(unit, child_entry) {
+                is_packed |= offset % required != 0;
+            }
+        }
+
+        if let Some(size) = self.attrs.get_u64_attr(unit, entry, gimli::DW_AT_byte_size) {
+            is_packed |= size % required_alignment != 0;
+        }
+        Ok((if is_packed { 1 } else { alignment }, is_packed))
+    }
+
+    /// The bound of an array dimension from its subrange entry
+    fn array_bound(
+        &self,
+        unit: &DwarfUnit,
+        subrange: &DebuggingInformationEntry<DwarfReader>,
+    ) -> Result<ArrayBound> {
+        let lower = match self
+            .attrs
+            .get_bound_attr(unit, subrange, gimli::DW_AT_lower_bound)
+        {
+            None => 0,
+            Some(BoundValue::Constant(lower)) => lower,
+            Some(_) => return Ok(ArrayBound::Dynamic(DynamicBound::Unknown)),
+        };
+        let count = if let Some(count) =
+            self.attrs
+                .get_bound_attr(unit, subrange, gimli::DW_AT_count)
+        {
+            match count {
+                BoundValue::Constant(count) => count,
+                dynamic => return self.dynamic_bound(unit, dynamic, 0),
+            }
+        } else if let Some(upper) =
+            self.attrs
+                .get_bound_attr(unit, subrange, gimli::DW_AT_upper_bound)
+        {
+            match upper {
+                BoundValue::Constant(upper) => upper.saturating_sub(lower).saturating_add(1),
+                dynamic => return self.dynamic_bound(unit, dynamic, 1),
+            }
+        } else {
+            return Ok(ArrayBound::Unknown);
+        };
+        Ok(ArrayBound::Fixed {
+            count: count.max(0) as u64,
+            lower,
+        })
+    }
+
+    /// A bound computed at run time, from the variable holding it or an
+    /// expression reading the register holding the number of elements. The
+    /// variable or expression yields that number less `adjustment`.
+    fn dynamic_bound(
+        &self,
+        unit: &DwarfUnit,
+        value: BoundValue,
+        adjustment: u64,
+    ) -> Result<ArrayBound> {
+        let encoding = unit.header.encoding();
+        let traced = match value {
+            BoundValue::Reference(offset) => {
+                let Some((unit, unit_offset)) = self.index.lookup(offset) else {
+                    return Ok(ArrayBound::Dynamic(DynamicBound::Unknown));
+                };
+                let mut entries = unit.entries_at_offset(unit_offset)?;
+                let Some((_, entry)) = entries.next_dfs()? else {
+                    return Ok(ArrayBound::Dynamic(DynamicBound::Unknown));
+                };
+                let is_artificial = matches!(
+                    entry.attr_value(gimli::DW_AT_artificial)?,
+                    Some(gimli::AttributeValue::Flag(true))
+                );
+                if let Some(name) = self
+                    .attrs
+                    .get_string_attr(&unit, entry, gimli::DW_AT_name)
+                    .filter(|_| !is_artificial)
+                {
+                    return Ok(ArrayBound::Dynamic(DynamicBound::Named(name)));
+                }
+                // Compilers hold the bound in an artificial variable, which
+                // optimized code computes from the parameter's register.
+                // Entry values name the parameter wherever its register has
+                // been reused, so they are tried first.
+                let mut values: Vec<RegisterValue> = self
+                    .attrs
+                    .get_location_expressions(&unit, entry)
+                    .into_iter()
+                    .filter_map(|(_, expression)| register_value(expression, encoding, true))
+                    .collect();
+                values.sort_by_key(|value| !value.at_entry);
+                values
+            }
+            BoundValue::Expression(expression) => register_value(expression, encoding, false)
+                .into_iter()
+                .collect(),
+            BoundValue::Constant(_) => Vec::new(),
+        };
+        let bound = if traced.is_empty() {
+            DynamicBound::Unknown
+        } else {
+            DynamicBound::Registers(
+                traced
+                    .into_iter()
+                    .map(|value| RegisterValue {
+                        addend: value.addend + adjustment as i64,
+                        ..value
+                    })
+                    .collect(),
+            )
+        };
+        Ok(ArrayBound::Dynamic(bound))
+    }
+
     fn resolve_type_entry_impl(
         &mut self,
         unit: &DwarfUnit,
         entry: &DebuggingInformationEntry<DwarfReader>,
         use_typedef_substitution: bool,
+    ) -> Result<TypeInfo> {
+        let mut type_info = self.resolve_type_kind(unit, entry, use_typedef_substitution)?;
+        type_info.layout = self.entry_layout(unit, entry)?;
+        Ok(type_info)
+    }
+
+    fn resolve_type_kind(
+        &mut self,
+        unit: &DwarfUnit,
+        entry: &DebuggingInformationEntry<DwarfReader>,
+        use_typedef_substitution: bool,
     ) -> Result<TypeInfo> {
         match entry.tag() {
             gimli::DW_TAG_base_type => {
+                let name = self.attrs.get_string_attr(unit, entry, gimli::DW_AT_name);
+                let byte_size = self.attrs.get_u64_attr(unit, entry, gimli::DW_AT_byte_size);
+                Ok(TypeInfo::new(base_type_spelling(
+                    name,
+                    base_type_encoding(entry),
+                    byte_size,
+                )))
+            }
+            // `decltype(nullptr)` in C++, `nullptr_t` in C23
+            gimli::DW_TAG_unspecified_type => {
                 let name = self
                     .attrs
                     .get_string_attr(unit, entry, gimli::DW_AT_name)
@@ -99,30 +452,14 @@ impl<'a> TypeResolver<'a> {
                 Ok(TypeInfo::new(name))
             }
             gimli::DW_TAG_pointer_type => {
-                if let Some(pointed_offset) =
-                    self.attrs.get_ref_attr(unit, entry, gimli::DW_AT_type)
-                {
-                    let mut type_info = self.resolve_type_from_offset(unit, pointed_offset)?;
-                    // Special case: pointer to subroutine is already a function pointer,
-                    // don't increment pointer_count
-                    if !type_info.is_function_pointer {
-                        type_info.pointer_count += 1;
-                    }
-                    Ok(type_info)
-                } else {
-                    let mut type_info = TypeInfo::new("void".to_string());
-                    type_info.pointer_count = 1;
-                    Ok(type_info)
-                }
+                let target = self.resolve_type(unit, entry)?;
+                Ok(TypeInfo::pointer_to(target))
             }
             gimli::DW_TAG_array_type => {
-                let base_offset = self
-                    .attrs
-                    .get_ref_attr(unit, entry, gimli::DW_AT_type)
-                    .unwrap_or(0);
-                let mut type_info = self.resolve_type_from_offset(unit, base_offset)?;
+                let element = self.resolve_type(unit, entry)?;
 
-                // Get array dimensions from subrange children
+                // Get array dimensions from subrange children, outermost first
+                let mut bounds = Vec::new();
                 let mut entries = unit.entries_at_offset(entry.offset())?;
                 entries.next_dfs()?; // Skip the array type itself
                 let mut absolute_depth = 0;
@@ -133,76 +470,62 @@ impl<'a> TypeResolver<'a> {
                         break;
                     }
                     if child_entry.tag() == gimli::DW_TAG_subrange_type {
-                        let size = if let Some(count) =
-                            self.attrs.get_u64_attr(child_entry, gimli::DW_AT_count)
-                        {
-                            count as usize
-                        } else if let Some(upper) = self
-                            .attrs
-                            .get_u64_attr(child_entry, gimli::DW_AT_upper_bound)
-                        {
-                            (upper + 1) as usize
-                        } else {
-                            0
-                        };
-                        type_info.array_sizes.push(size);
+                        bounds.push(self.array_bound(unit, child_entry)?);
                     }
                 }
 
-                Ok(type_info)
-            }
-            gimli::DW_TAG_const_type => {
-                if let Some(base_offset) = self.attrs.get_ref_attr(unit, entry, gimli::DW_AT_type) {
-                    let mut type_info = self.resolve_type_from_offset(unit, base_offset)?;
-                    type_info.is_const = true;
-                    Ok(type_info)
-                } else {
-                    Ok(TypeInfo::new("const void".to_string()))
-                }
-            }
-            gimli::DW_TAG_volatile_type => {
-                if let Some(base_offset) = self.attrs.get_ref_attr(unit, entry, gimli::DW_AT_type) {
-                    let mut type_info = self.resolve_type_from_offset(unit, base_offset)?;
-                    type_info.is_volatile = true;
-                    Ok(type_info)
-                } else {
-                    let mut type_info = TypeInfo::new("void".to_string());
-                    type_info.is_volatile = true;
-                    Ok(type_info)
+                // A SIMD vector is described as a one-dimensional array
+                if self.attrs.get_bool_attr(entry, gimli::DW_AT_GNU_vector) {
+                    let count = bounds.first().and_then(ArrayBound::count).unwrap_or(0);
+                    let size = self
+                        .attrs
+                        .get_u64_attr(unit, entry, gimli::DW_AT_byte_size)
+                        .or_else(|| Some(element.layout?.size * count))
+                        .unwrap_or(0);
+                    return Ok(TypeInfo::with_kind(TypeKind::Vector {
+                        element: Box::new(element),
+                        size,
+                    }));
                 }
+
+                // `int a[2][3]` is an array of 2 arrays of 3 ints
+                Ok(bounds.into_iter().rev().fold(element, TypeInfo::array_of))
             }
-            gimli::DW_TAG_restrict_type => {
-                if let Some(base_offset) = self.attrs.get_ref_attr(unit, entry, gimli::DW_AT_type) {
-                    let mut type_info = self.resolve_type_from_offset(unit, base_offset)?;
-                    type_info.is_restrict = true;
-                    Ok(type_info)
-                } else {
-                    let mut type_info = TypeInfo::new("void".to_string());
-                    type_info.is_restrict = true;
-                    Ok(type_info)
-                }
+            gimli::DW_TAG_const_type
+            | gimli::DW_TAG_volatile_type
+            | gimli::DW_TAG_restrict_type
+            | gimli::DW_TAG_atomic_type => {
+                let mut type_info = self.resolve_type(unit, entry)?;
+                type_info.add_qualifiers(Qualifiers {
+                    is_const: entry.tag() == gimli::DW_TAG_const_type,
+                    is_volatile: entry.tag() == gimli::DW_TAG_volatile_type,
+                    is_restrict: entry.tag() == gimli::DW_TAG_restrict_type,
+                    is_atomic: entry.tag() == gimli::DW_TAG_atomic_type,
+                });
+                Ok(type_info)
             }
             gimli::DW_TAG_reference_type => {
-                if let Some(ref_offset) = self.attrs.get_ref_attr(unit, entry, gimli::DW_AT_type) {
-                    let mut type_info = self.resolve_type_from_offset(unit, ref_offset)?;
-                    type_info.is_reference = true;
-                    Ok(type_info)
-                } else {
-                    let mut type_info = TypeInfo::new("void".to_string());
-                    type_info.is_reference = true;
-                    Ok(type_info)
-                }
+                let target = self.resolve_type(unit, entry)?;
+                Ok(TypeInfo::with_kind(TypeKind::Reference(Box::new(target))))
             }
             gimli::DW_TAG_rvalue_reference_type => {
-                if let Some(ref_offset) = self.attrs.get_ref_attr(unit, entry, gimli::DW_AT_type) {
-                    let mut type_info = self.resolve_type_from_offset(unit, ref_offset)?;
-                    type_info.is_rvalue_reference = true;
-                    Ok(type_info)
-                } else {
-                    let mut type_info = TypeInfo::new("void".to_string());
-                    type_info.is_rvalue_reference = true;
-                    Ok(type_info)
-                }
+                let target = self.resolve_type(unit, entry)?;
+                Ok(TypeInfo::with_kind(TypeKind::RvalueReference(Box::new(
+                    target,
+                ))))
+            }
+            gimli::DW_TAG_ptr_to_member_type => {
+                let target = self.resolve_type(unit, entry)?;
+                let class = self
+                    .attrs
+                    .get_ref_attr(unit, entry, gimli::DW_AT_containing_type)
+                    .map(|class_offset| self.resolve_type_from_offset(class_offset))
+                    .transpose()?
+                    .unwrap_or_else(TypeInfo::void);
+                Ok(TypeInfo::with_kind(TypeKind::MemberPointer {
+                    class: Box::new(class),
+                    target: Box::new(target),
+                }))
             }
             gimli::DW_TAG_typedef => {
                 let name = self
@@ -215,6 +538,23 @@ impl<'a> TypeResolver<'a> {
             | gimli::DW_TAG_class_type
             | gimli::DW_TAG_union_type
             | gimli::DW_TAG_enumeration_type => {
+                // A declaration standing in for a type defined in a type unit
+                if let Some(location) = self
+                    .attrs
+                    .get_ref_attr(unit, entry, gimli::DW_AT_signature)
+                    .and_then(|offset| self.index.lookup(offset))
+                {
+                    let (type_unit, unit_offset) = location;
+                    let mut entries = type_unit.entries_at_offset(unit_offset)?;
+                    if let Some((_, type_entry)) = entries.next_dfs()? {
+                        return self.resolve_type_entry_impl(
+                            &type_unit,
+                            type_entry,
+                            use_typedef_substitution,
+                        );
+                    }
+                }
+
                 let name = self.attrs.get_string_attr(unit, entry, gimli::DW_AT_name);
 
                 if let Some(n) = name {
@@ -242,22 +582,16 @@ impl<'a> TypeResolver<'a> {
                             return Ok(TypeInfo::new(typedef_info.name.clone()));
                         }
                     }
-                    Ok(TypeInfo::new("void".to_string()))
+                    Ok(TypeInfo::void())
                 }
             }
             gimli::DW_TAG_subroutine_type => {
-                // Function pointer
-                let mut func_type = TypeInfo::new("void".to_string());
-                func_type.is_function_pointer = true;
-
-                // Get return type
-                if let Some(ret_offset) = self.attrs.get_ref_attr(unit, entry, gimli::DW_AT_type) {
-                    if let Ok(ret_type) = self.resolve_type_from_offset(unit, ret_offset) {
-                        func_type.function_return_type = Some(Box::new(ret_type));
-                    }
-                }
+                let return_type = self.resolve_type(unit, entry)?;
 
                 // Get parameters
+                let mut params = Vec::new();
+                let mut qualifiers = Qualifiers::default();
+                let mut is_variadic = false;
                 let mut entries = unit.entries_at_offset(entry.offset())?;
                 entries.next_dfs()?; // Skip the subroutine type itself
                 let mut absolute_depth = 0;
@@ -267,35 +601,262 @@ impl<'a> TypeResolver<'a> {
                     if absolute_depth <= 0 {
                         break;
                     }
-                    if child_entry.tag() == gimli::DW_TAG_formal_parameter {
-                        // Try to get the parameter type
-                        if let Some(param_offset) =
-                            self.attrs
-                                .get_ref_attr(unit, child_entry, gimli::DW_AT_type)
+                    if absolute_depth == 1
+                        && child_entry.tag() == gimli::DW_TAG_unspecified_parameters
+                    {
+                        is_variadic = true;
+                    }
+                    if absolute_depth == 1 && child_entry.tag() == gimli::DW_TAG_formal_parameter {
+                        // Parameters without a resolvable type are taken to be void*
+                        let param_type = self
+                            .attrs
+                            .get_ref_attr(unit, child_entry, gimli::DW_AT_type)
+                            .and_then(|param_offset| {
+                                self.resolve_type_from_offset(param_offset).ok()
+                            })
+                            .unwrap_or_else(|| TypeInfo::pointer_to(TypeInfo::void()));
+
+                        // The `this` parameter of a method type is artificial;
+                        // it points to a const object for a const method
+                        if self
+                            .attrs
+                            .get_bool_attr(child_entry, gimli::DW_AT_artificial)
                         {
-                            if let Ok(param_type) =
-                                self.resolve_type_from_offset(unit, param_offset)
-                            {
-                                func_type.function_params.push(param_type);
-                            } else {
-                                // Cross-unit or unresolvable reference - use void* as fallback
-                                let mut void_ptr = TypeInfo::new("void".to_string());
-                                void_ptr.pointer_count = 1;
-                                func_type.function_params.push(void_ptr);
+                            if let TypeKind::Pointer(object) = &param_type.kind {
+                                qualifiers = object.qualifiers;
                             }
-                        } else {
-                            // No type attribute or unresolvable - check if there's a name
-                            // For unnamed parameters with no type, assume void* if formal_parameter exists
-                            let mut void_ptr = TypeInfo::new("void".to_string());
-                            void_ptr.pointer_count = 1;
-                            func_type.function_params.push(void_ptr);
+                            continue;
                         }
+                        params.push(param_type);
                     }
                 }
 
-                Ok(func_type)
+                let is_prototyped = self.attrs.get_bool_attr(entry, gimli::DW_AT_prototyped);
+                let mut function = TypeInfo::with_kind(TypeKind::Function {
+                    return_type: Box::new(return_type),
+                    is_variadic: is_variadic_function(
+                        is_variadic,
+                        is_prototyped || self.attrs.unit_has_prototypes(unit),
+                        params.len(),
+                    ),
+                    params,
+                    is_prototyped,
+                });
+                function.qualifiers = qualifiers;
+                Ok(function)
+            }
+            _ => Ok(TypeInfo::void()),
+        }
+    }
+}
+
+/// The DW_AT_encoding of a base type
+fn base_type_encoding(entry: &DebuggingInformationEntry<DwarfReader>) -> Option<gimli::DwAte> {
+    match entry.attr_value(gimli::DW_AT_encoding).ok()?? {
+        gimli::AttributeValue::Encoding(encoding) => Some(encoding),
+        _ => None,
+    }
+}
+
+/// GCC's encoding of `_Complex int` and other complex integer types
+const DW_ATE_GNU_COMPLEX_INT: gimli::DwAte = gimli::DW_ATE_lo_user;
+
+/// How a base type is written in source. GCC names complex types
+/// `complex float` and `unsigned __int128` `__int128 unsigned`; types without
+/// a name are spelled from their encoding and size.
+fn base_type_spelling(
+    name: Option<String>,
+    encoding: Option<gimli::DwAte>,
+    byte_size: Option<u64>,
+) -> String {
+    if let Some(name) = name {
+        if let Some(part) = name.strip_prefix("complex ") {
+            return format!("_Complex {}", part);
+        }
+        if let Some(int) = name.strip_suffix(" unsigned") {
+            return format!("unsigned {}", int);
+        }
+        return name;
+    }
+
+    let size = byte_size.unwrap_or(0);
+    let spelling = match encoding {
+        Some(gimli::DW_ATE_boolean) => Some("bool".to_string()),
+        Some(gimli::DW_ATE_signed) => integer_spelling(true, size),
+        Some(gimli::DW_ATE_unsigned) => integer_spelling(false, size),
+        Some(gimli::DW_ATE_signed_char) if size == 1 => Some("char".to_string()),
+        Some(gimli::DW_ATE_unsigned_char) if size == 1 => Some("unsigned char".to_string()),
+        Some(gimli::DW_ATE_float) => float_spelling(size),
+        Some(gimli::DW_ATE_complex_float) => {
+            float_spelling(size / 2).map(|part| format!("_Complex {}", part))
+        }
+        Some(DW_ATE_GNU_COMPLEX_INT) => {
+            integer_spelling(true, size / 2).map(|part| format!("_Complex {}", part))
+        }
+        Some(gimli::DW_ATE_UTF) => match size {
+            1 => Some("char8_t".to_string()),
+            2 => Some("char16_t".to_string()),
+            4 => Some("char32_t".to_string()),
+            _ => None,
+        },
+        _ => None,
+    };
+    spelling.unwrap_or_else(|| "void".to_string())
+}
+
+/// The integer type of `size` bytes
+fn integer_spelling(signed: bool, size: u64) -> Option<String> {
+    let name = match size {
+        1 => "char",
+        2 => "short",
+        4 => "int",
+        8 => "long long",
+        16 => "__int128",
+        _ => return None,
+    };
+    Some(match (signed, size) {
+        (true, 1) => "signed char".to_string(),
+        (true, _) => name.to_string(),
+        (false, _) => format!("unsigned {}", name),
+    })
+}
+
+/// The floating point type of `size` bytes
+fn float_spelling(size: u64) -> Option<String> {
+    let name = match size {
+        2 => "_Float16",
+        4 => "float",
+        8 => "double",
+        10 | 12 | 16 => "long double",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+/// The alignment of a base type of `size` bytes: that of its parts for
+/// complex numbers, and otherwise the largest power of two dividing its size,
+/// as a 12 byte x87 `long double` is 4 byte aligned
+fn base_type_alignment(encoding: Option<gimli::DwAte>, size: u64) -> u64 {
+    let size = match encoding {
+        Some(gimli::DW_ATE_complex_float) => size / 2,
+        _ => size,
+    };
+    if size == 0 {
+        1
+    } else {
+        1 << size.trailing_zeros()
+    }
+}
+
+/// Whether a function with unspecified parameters is variadic. Without a
+/// prototype and named parameters they only mean that the parameters of a
+/// C function are unknown, as in `int f()`. C++ functions always have one.
+pub fn is_variadic_function(
+    has_unspecified_parameters: bool,
+    is_prototyped: bool,
+    param_count: usize,
+) -> bool {
+    has_unspecified_parameters && (is_prototyped || param_count > 0)
+}
+
+/// The register read by a bound expression of the form GCC emits when
+/// optimizing: a register's value or entry value, possibly sign or zero
+/// extended from a narrower parameter, plus or minus constants. Location
+/// expressions must compute a value or name the register holding it.
+fn register_value(
+    expression: gimli::Expression<DwarfReader>,
+    encoding: gimli::Encoding,
+    is_location: bool,
+) -> Option<RegisterValue> {
+    enum Value {
+        Constant(i64),
+        Register(RegisterValue),
+    }
+
+    let mut reader = expression.0;
+    let mut stack = Vec::new();
+    let mut is_value = !is_location;
+    let mut shift = None;
+    while !reader.is_empty() {
+        match gimli::Operation::parse(&mut reader, encoding).ok()? {
+            gimli::Operation::Register { register } if is_location && stack.is_empty() => {
+                if !reader.is_empty() {
+                    return None;
+                }
+                stack.push(Value::Register(RegisterValue {
+                    register: register.0,
+                    at_entry: false,
+                    addend: 0,
+                }));
+                is_value = true;
             }
-            _ => Ok(TypeInfo::new("void".to_string())),
+            gimli::Operation::RegisterOffset {
+                register,
+                offset,
+                base_type,
+            } if base_type.0 == 0 => stack.push(Value::Register(RegisterValue {
+                register: register.0,
+                at_entry: false,
+                addend: offset,
+            })),
+            gimli::Operation::EntryValue { expression } => {
+                let mut inner = expression;
+                let gimli::Operation::Register { register } =
+                    gimli::Operation::parse(&mut inner, encoding).ok()?
+                else {
+                    return None;
+                };
+                if !inner.is_empty() {
+                    return None;
+                }
+                stack.push(Value::Register(RegisterValue {
+                    register: register.0,
+                    at_entry: true,
+                    addend: 0,
+                }));
+            }
+            gimli::Operation::UnsignedConstant { value } => {
+                stack.push(Value::Constant(value as i64))
+            }
+            gimli::Operation::SignedConstant { value } => stack.push(Value::Constant(value)),
+            gimli::Operation::PlusConstant { value } => match stack.last_mut()? {
+                Value::Register(register) => register.addend += value as i64,
+                Value::Constant(constant) => *constant += value as i64,
+            },
+            operation @ (gimli::Operation::Plus
+            | gimli::Operation::Minus
+            | gimli::Operation::Shl
+            | gimli::Operation::Shr
+            | gimli::Operation::Shra) => {
+                let Value::Constant(constant) = stack.pop()? else {
+                    return None;
+                };
+                match (operation, stack.last_mut()?) {
+                    (gimli::Operation::Plus, Value::Register(register)) => {
+                        register.addend += constant
+                    }
+                    (gimli::Operation::Minus, Value::Register(register)) => {
+                        register.addend -= constant
+                    }
+                    // Shifting left then right extends the low bits of a
+                    // narrower parameter, leaving its value as it was
+                    (gimli::Operation::Shl, Value::Register(_)) if shift.is_none() => {
+                        shift = Some(constant)
+                    }
+                    (gimli::Operation::Shr | gimli::Operation::Shra, Value::Register(_))
+                        if shift == Some(constant) =>
+                    {
+                        shift = None
+                    }
+                    _ => return None,
+                }
+            }
+            gimli::Operation::StackValue if is_location && reader.is_empty() => is_value = true,
+            _ => return None,
         }
     }
+    match (is_value && shift.is_none(), stack.as_slice()) {
+        (true, [Value::Register(register)]) => Some(*register),
+        _ => None,
+    }
 }
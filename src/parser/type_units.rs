@@ -0,0 +1,253 @@
+//! Type unit support
+//!
+//! With `-fdebug-types-section`, type definitions are moved out of the
+//! compile units into type units (in `.debug_types` for DWARF 4, and in
+//! `.debug_info` for DWARF 5), which compile units refer to by signature.
+//! Each type unit a compile unit uses is parsed along with it, so that its
+//! definitions end up in the header (or source file) declaring them.
+
+use crate::error::Result;
+use crate::types::*;
+use gimli::AttributeValue;
+use std::collections::HashMap;
+
+use super::attributes::AttributeExtractor;
+use super::DwarfParser;
+
+/// Enclosing namespaces, outermost first, with their declaration lines
+type NamespacePath = Vec<(String, Option<u64>)>;
+
+impl<'a> DwarfParser<'a> {
+    /// The type units an entry refers to by signature, by absolute offset
+    pub(super) fn referenced_type_units(
+        &self,
+        entry: &gimli::DebuggingInformationEntry<DwarfReader>,
+    ) -> Result<Vec<usize>> {
+        let mut type_units = Vec::new();
+        let mut attrs = entry.attrs();
+        while let Some(attr) = attrs.next()? {
+            let AttributeValue::DebugTypesRef(signature) = attr.value() else {
+                continue;
+            };
+            let type_unit = self
+                .index
+                .signatures()
+                .get(&signature)
+                .and_then(|&type_offset| self.index.lookup(type_offset));
+            if let Some((type_unit, _)) = type_unit {
+                type_units.push(unit_base_offset(&type_unit));
+            }
+        }
+        Ok(type_units)
+    }
+
+    /// Parse the type units used by a compile unit, directly or through other
+    /// type units. Their file indices are mapped onto the compile unit's
+    /// file table, which gains any files it didn't list.
+    pub(super) fn parse_type_units_for(
+        &self,
+        unit: &DwarfUnit,
+        file_table: &mut Vec<String>,
+    ) -> Result<Vec<Element>> {
+        // Dependencies first, so types are defined before they are used
+        let root = unit_base_offset(unit);
+        let mut order = Vec::new();
+        self.type_unit_order(root, &mut Vec::new(), &mut order);
+        order.retain(|&type_unit_base| type_unit_base != root);
+
+        let mut elements = Vec::new();
+        for type_unit_base in order {
+            let Some(type_unit) = self.index.unit_at(type_unit_base) else {
+                continue;
+            };
+            let mut entries = type_unit.entries();
+            let Some((_, root_entry)) = entries.next_dfs()? else {
+                continue;
+            };
+            // Type units have no DW_AT_comp_dir; they share the compile unit's
+            let type_file_table = self.extract_file_table(&type_unit, root_entry, unit.comp_dir)?;
+
+            let mut type_elements = self.parse_type_unit_children(&type_unit, &mut entries)?;
+
+            adopt_file_table(file_table, &type_file_table, &mut type_elements);
+            elements.extend(type_elements);
+        }
+
+        Ok(elements)
+    }
+
+    /// Parse the top-level entries of a type unit.
+    ///
+    /// GCC places a C++ type's definition at the top level, with a
+    /// DW_AT_specification pointing at a declaration nested in declaration-only
+    /// namespaces. Those namespaces are skipped, and the definition is wrapped
+    /// in the namespaces enclosing its declaration instead.
+    fn parse_type_unit_children(
+        &self,
+        type_unit: &DwarfUnit,
+        entries: &mut gimli::EntriesCursor<DwarfReader>,
+    ) -> Result<Vec<Element>> {
+        let namespace_paths = self.declaration_namespaces(type_unit)?;
+        let mut elements = Vec::new();
+        let mut absolute_depth = 0;
+
+        while let Some((depth_delta, entry)) = entries.next_dfs()? {
+            absolute_depth += depth_delta;
+            if absolute_depth <= 0 {
+                break;
+            }
+            if absolute_depth != 1 {
+                continue;
+            }
+
+            let offset = entry.offset();
+            let (is_declaration, specification) = {
+                let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
+                (
+                    attrs.get_bool_attr(entry, gimli::DW_AT_declaration),
+                    attrs.get_ref_attr(type_unit, entry, gimli::DW_AT_specification),
+                )
+            };
+
+            let element = match entry.tag() {
+                gimli::DW_TAG_namespace if !is_declaration => self
+                    .parse_namespace_at(type_unit, offset)?
+                    .map(Element::Namespace),
+                gimli::DW_TAG_structure_type => self
+                    .parse_compound_at(type_unit, offset, "struct")?
+                    .map(Element::Compound),
+                gimli::DW_TAG_class_type => self
+                    .parse_compound_at(type_unit, offset, "class")?
+                    .map(Element::Compound),
+                gimli::DW_TAG_union_type => self
+                    .parse_compound_at(type_unit, offset, "union")?
+                    .map(Element::Compound),
+                gimli::DW_TAG_enumeration_type => self
+                    .parse_enum_at(type_unit, offset)?
+                    .map(Element::Compound),
+                _ => None,
+            };
+
+            let Some(mut element) = element else {
+                continue;
+            };
+            if let Some(path) = specification.and_then(|offset| namespace_paths.get(&offset)) {
+                for (name, line) in path.iter().rev() {
+                    element = Element::Namespace(Namespace {
+                        name: name.clone(),
+                        line: *line,
+                        children: vec![element],
+                    });
+                }
+            }
+            elements.push(element);
+        }
+
+        Ok(elements)
+    }
+
+    /// Names and lines of the namespaces enclosing each entry of a unit, by
+    /// absolute offset
+    fn declaration_namespaces(&self, unit: &DwarfUnit) -> Result<HashMap<usize, NamespacePath>> {
+        let attrs = AttributeExtractor::new(&self.dwarf, self.index.signatures());
+        let unit_base = unit_base_offset(unit);
+        let mut paths = HashMap::new();
+        // The namespace (if any) opened by each ancestor of the current entry
+        let mut scopes: Vec<Option<(String, Option<u64>)>> = Vec::new();
+        let mut absolute_depth: isize = 0;
+
+        let mut entries = unit.entries();
+        while let Some((depth_delta, entry)) = entries.next_dfs()? {
+            absolute_depth += depth_delta;
+            scopes.truncate(absolute_depth.max(0) as usize);
+
+            let namespaces: NamespacePath = scopes.iter().flatten().cloned().collect();
+            if !namespaces.is_empty() {
+                paths.insert(unit_base + entry.offset().0, namespaces);
+            }
+
+            scopes.push(if entry.tag() == gimli::DW_TAG_namespace {
+                attrs
+                    .get_string_attr(unit, entry, gimli::DW_AT_name)
+                    .map(|name| {
+                        let line = attrs.get_u64_attr(unit, entry, gimli::DW_AT_decl_line);
+                        (name, line)
+                    })
+            } else {
+                None
+            });
+        }
+
+        Ok(paths)
+    }
+
+    /// Append the type units reachable from `unit_base` to `order`, each after
+    /// the type units it refers to
+    fn type_unit_order(&self, unit_base: usize, visiting: &mut Vec<usize>, order: &mut Vec<usize>) {
+        visiting.push(unit_base);
+        for &referenced in self.type_unit_refs.get(&unit_base).into_iter().flatten() {
+            if !visiting.contains(&referenced) && !order.contains(&referenced) {
+                self.type_unit_order(referenced, visiting, order);
+            }
+        }
+        visiting.pop();
+        order.push(unit_base);
+    }
+}
+
+/// Map the file indices of elements parsed from another unit onto
+/// `file_table`, which gains any files of `unit_file_table` it didn't list
+pub(super) fn adopt_file_table(
+    file_table: &mut Vec<String>,
+    unit_file_table: &[String],
+    elements: &mut [Element],
+) {
+    let mut file_map = HashMap::new();
+    for (index, path) in unit_file_table.iter().enumerate() {
+        let mapped = match file_table.iter().position(|known| known == path) {
+            Some(position) => position,
+            None => {
+                file_table.push(path.clone());
+                file_table.len() - 1
+            }
+        };
+        file_map.insert(index as u64 + 1, mapped as u64 + 1);
+    }
+    remap_decl_files(elements, &file_map);
+}
+
+/// Rewrite the file indices of parsed elements using `file_map`
+fn remap_decl_files(elements: &mut [Element], file_map: &HashMap<u64, u64>) {
+    let remap = |decl_file: &mut Option<u64>| {
+        if let Some(mapped) = decl_file.and_then(|file| file_map.get(&file)) {
+            *decl_file = Some(*mapped);
+        }
+    };
+
+    fn remap_compound(compound: &mut Compound, remap: &dyn Fn(&mut Option<u64>)) {
+        remap(&mut compound.decl_file);
+        for member in compound
+            .members
+            .iter_mut()
+            .chain(&mut compound.static_members)
+        {
+            remap(&mut member.decl_file);
+        }
+        for method in &mut compound.methods {
+            remap(&mut method.decl_file);
+        }
+        for nested in &mut compound.nested_types {
+            remap_compound(nested, remap);
+        }
+    }
+
+    for element in elements {
+        match element {
+            Element::Compound(compound) => remap_compound(compound, &remap),
+            Element::Function(function) => remap(&mut function.decl_file),
+            Element::Variable(variable) => remap(&mut variable.decl_file),
+            Element::TypedefAlias(alias) => remap(&mut alias.decl_file),
+            Element::Namespace(namespace) => remap_decl_files(&mut namespace.children, file_map),
+        }
+    }
+}
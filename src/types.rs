@@ -1,16 +1,30 @@
 //! Type definitions for DWARF C reconstructor
 
-pub type DwarfReader<'a> = gimli::EndianSlice<'a, gimli::LittleEndian>;
+use std::collections::HashMap;
+
+pub type DwarfReader<'a> = gimli::EndianSlice<'a, gimli::RunTimeEndian>;
 pub type DwarfUnit<'a> = gimli::Unit<DwarfReader<'a>>;
 
+/// Added to the offsets of units in `.debug_types` (DWARF 4 type units) so
+/// that their absolute offsets don't collide with those in `.debug_info`
+pub const DEBUG_TYPES_OFFSET_BASE: usize = 1 << (usize::BITS - 1);
+
+/// Units of a supplementary (dwz) file are placed at this base offset, below
+/// those of `.debug_types`
+pub const SUPPLEMENTARY_OFFSET_BASE: usize = 1 << (usize::BITS - 2);
+
 /// Get the base offset for a compilation unit.
 /// Used to convert relative offsets to absolute offsets for consistent lookups.
 pub fn unit_base_offset(unit: &DwarfUnit) -> usize {
-    unit.header
-        .offset()
-        .as_debug_info_offset()
-        .map(|o| o.0)
-        .unwrap_or(0)
+    match unit.header.offset() {
+        gimli::UnitSectionOffset::DebugInfoOffset(offset) => offset.0,
+        gimli::UnitSectionOffset::DebugTypesOffset(offset) => DEBUG_TYPES_OFFSET_BASE + offset.0,
+    }
+}
+
+/// Whether a unit comes from the supplementary file rather than the input
+pub fn is_supplementary_unit(unit: &DwarfUnit) -> bool {
+    (SUPPLEMENTARY_OFFSET_BASE..DEBUG_TYPES_OFFSET_BASE).contains(&unit_base_offset(unit))
 }
 
 /// Check if two decl_file values indicate the same source file.
@@ -23,6 +37,15 @@ pub fn is_same_decl_file(a: Option<u64>, b: Option<u64>) -> bool {
     }
 }
 
+/// The name of the template a class or function name instantiates
+/// (e.g. `Buffer` for `Buffer<int, 4>`), or the name itself
+pub fn template_name(name: &str) -> &str {
+    match name.find('<') {
+        Some(index) if index > 0 && !name.starts_with("operator") => &name[..index],
+        _ => name,
+    }
+}
+
 /// Information about a typedef collected during metadata pass.
 /// Maps a type offset to its typedef name and source location.
 #[derive(Debug, Clone)]
@@ -44,6 +67,8 @@ pub struct CompoundMetadata {
     pub typedef_line: Option<u64>,
     pub compound_type: String,
     pub decl_file: Option<u64>,
+    pub alignment: Option<u64>,
+    pub is_packed: bool,
 }
 
 /// Metadata for parsing function/method definitions.
@@ -68,6 +93,7 @@ pub struct FunctionMetadata {
     pub is_artificial: bool,
     pub specification_offset: Option<usize>,
     pub decl_offset: Option<usize>,
+    pub is_prototyped: bool,
 }
 
 /// Data collected from a method definition that can be applied to declarations.
@@ -79,11 +105,13 @@ pub struct MethodDefinition {
     pub lexical_blocks: Vec<LexicalBlock>,
     pub inlined_calls: Vec<InlinedSubroutine>,
     pub labels: Vec<Label>,
+    pub local_types: Vec<Element>,
     pub has_body: bool,
     pub low_pc: Option<u64>,
     pub high_pc: Option<u64>,
     pub line: Option<u64>,
     pub namespace_path: Vec<String>,
+    pub template_params: Vec<TemplateParameter>,
 }
 
 impl MethodDefinition {
@@ -95,11 +123,13 @@ impl MethodDefinition {
             lexical_blocks: func.lexical_blocks.clone(),
             inlined_calls: func.inlined_calls.clone(),
             labels: func.labels.clone(),
+            local_types: func.local_types.clone(),
             has_body: func.has_body,
             low_pc: func.low_pc,
             high_pc: func.high_pc,
             line: func.line,
             namespace_path: func.namespace_path.clone(),
+            template_params: func.template_params.clone(),
         }
     }
 
@@ -110,6 +140,7 @@ impl MethodDefinition {
         method.lexical_blocks = self.lexical_blocks.clone();
         method.inlined_calls = self.inlined_calls.clone();
         method.labels = self.labels.clone();
+        method.local_types = self.local_types.clone();
         method.has_body = self.has_body;
         method.low_pc = self.low_pc;
         method.high_pc = self.high_pc;
@@ -121,150 +152,594 @@ impl MethodDefinition {
         if method.namespace_path.is_empty() && !self.namespace_path.is_empty() {
             method.namespace_path = self.namespace_path.clone();
         }
+        if method.template_params.is_empty() {
+            method.template_params = self.template_params.clone();
+        }
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct TypeInfo {
-    pub base_type: String,
-    pub pointer_count: usize,
-    pub array_sizes: Vec<usize>,
+/// Qualifiers applying to one level of a type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Qualifiers {
     pub is_const: bool,
     pub is_volatile: bool,
     pub is_restrict: bool,
-    pub is_static: bool,
-    pub is_extern: bool,
-    pub is_reference: bool,        // C++ lvalue reference (T&)
-    pub is_rvalue_reference: bool, // C++ rvalue reference (T&&)
-    // For function pointers
-    pub is_function_pointer: bool,
-    pub function_return_type: Option<Box<TypeInfo>>,
-    pub function_params: Vec<TypeInfo>,
+    /// C11 `_Atomic`
+    pub is_atomic: bool,
+}
+
+impl Qualifiers {
+    pub fn is_empty(&self) -> bool {
+        !self.is_const && !self.is_volatile && !self.is_restrict && !self.is_atomic
+    }
+
+    /// The qualifier keywords, separated by spaces
+    pub fn keywords(&self) -> String {
+        let mut keywords = Vec::new();
+        if self.is_const {
+            keywords.push("const");
+        }
+        if self.is_volatile {
+            keywords.push("volatile");
+        }
+        if self.is_restrict {
+            keywords.push("restrict");
+        }
+        if self.is_atomic {
+            keywords.push("_Atomic");
+        }
+        keywords.join(" ")
+    }
+
+    fn add(&mut self, other: Qualifiers) {
+        self.is_const |= other.is_const;
+        self.is_volatile |= other.is_volatile;
+        self.is_restrict |= other.is_restrict;
+        self.is_atomic |= other.is_atomic;
+    }
+}
+
+/// The number of elements of one array dimension
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArrayBound {
+    /// A constant number of elements, indexed from `lower` (0 in C)
+    Fixed { count: u64, lower: i64 },
+    /// No bound, as for flexible array members and `extern int x[];`
+    Unknown,
+    /// A bound computed at run time, as for VLAs
+    Dynamic(DynamicBound),
+    /// A value parameter of a primary template, by name
+    TemplateParameter(String),
+}
+
+/// Where the number of elements of a variable length array comes from
+#[derive(Debug, Clone, PartialEq)]
+pub enum DynamicBound {
+    /// The variable or parameter holding it
+    Named(String),
+    /// Values of registers, as optimized code computes the bound from the
+    /// parameter in one, tried in turn
+    Registers(Vec<RegisterValue>),
+    /// An expression or artificial variable that can't be traced back to
+    /// the source
+    Unknown,
+}
+
+/// A parameter's value plus `addend`, read from the register it is in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RegisterValue {
+    /// DWARF register number
+    pub register: u16,
+    /// Read through DW_OP_entry_value, so the register held the value on
+    /// entry to the function rather than at this point
+    pub at_entry: bool,
+    pub addend: i64,
+}
+
+/// The parameters of a function passed or kept in registers, by DWARF
+/// register number
+#[derive(Debug, Clone, Default)]
+pub struct ParameterRegisters {
+    /// The register each parameter arrives in
+    pub at_entry: HashMap<u16, String>,
+    /// The registers each parameter is held in at some point, leaving out
+    /// those several parameters share
+    pub held: HashMap<u16, String>,
+}
+
+impl ParameterRegisters {
+    /// The parameter a register value is computed from, with its addend
+    fn name(&self, value: &RegisterValue) -> Option<String> {
+        let registers = if value.at_entry {
+            &self.at_entry
+        } else {
+            &self.held
+        };
+        let name = registers.get(&value.register)?;
+        Some(match value.addend {
+            0 => name.clone(),
+            1.. => format!("{} + {}", name, value.addend),
+            _ => format!("{} - {}", name, value.addend.unsigned_abs()),
+        })
+    }
+}
+
+impl ArrayBound {
+    /// The number of elements, if known at compile time
+    pub fn count(&self) -> Option<u64> {
+        match self {
+            ArrayBound::Fixed { count, .. } => Some(*count),
+            ArrayBound::Unknown | ArrayBound::Dynamic(_) | ArrayBound::TemplateParameter(_) => None,
+        }
+    }
+
+    /// The bound as written between the brackets of a declarator. C can't
+    /// express other lower bounds, so those are noted in a comment.
+    fn declarator(&self) -> String {
+        match self {
+            ArrayBound::Fixed { count, lower: 0 } => count.to_string(),
+            ArrayBound::Fixed { count, lower } => format!("{} /* from {} */", count, lower),
+            ArrayBound::Unknown => String::new(),
+            ArrayBound::Dynamic(DynamicBound::Named(name))
+            | ArrayBound::TemplateParameter(name) => name.clone(),
+            // `[*]` is only allowed in prototypes; a dummy bound keeps
+            // function bodies compilable
+            ArrayBound::Dynamic(_) => "/* run-time bound */ 1".to_string(),
+        }
+    }
+}
+
+/// How a type is built from the types it refers to
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeKind {
+    /// A base type, typedef or struct/class/union/enum, by name
+    Named(String),
+    Pointer(Box<TypeInfo>),
+    /// C++ lvalue reference (T&)
+    Reference(Box<TypeInfo>),
+    /// C++ rvalue reference (T&&)
+    RvalueReference(Box<TypeInfo>),
+    /// C++ pointer to a data member or method of a class (T Class::*)
+    MemberPointer {
+        class: Box<TypeInfo>,
+        target: Box<TypeInfo>,
+    },
+    Array {
+        element: Box<TypeInfo>,
+        bound: ArrayBound,
+    },
+    /// GCC SIMD vector of `size` bytes
+    /// (`float __attribute__((vector_size(16)))`)
+    Vector {
+        element: Box<TypeInfo>,
+        size: u64,
+    },
+    Function {
+        return_type: Box<TypeInfo>,
+        params: Vec<TypeInfo>,
+        /// Takes further arguments after `params` (`...`)
+        is_variadic: bool,
+        /// Declared with a prototype, so that no parameters is `(void)`
+        is_prototyped: bool,
+    },
+}
+
+/// The size and alignment of a type in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypeLayout {
+    pub size: u64,
+    pub alignment: u64,
+}
+
+/// A type, as a tree of pointer, reference, array and function types over
+/// a named type, with the qualifiers of each level
+#[derive(Debug, Clone)]
+pub struct TypeInfo {
+    pub kind: TypeKind,
+    pub qualifiers: Qualifiers,
+    /// The layout from the type's DIE, if it is complete
+    pub layout: Option<TypeLayout>,
+}
+
+/// Types are compared as written; their layout follows from them
+impl PartialEq for TypeInfo {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind && self.qualifiers == other.qualifiers
+    }
 }
 
 impl TypeInfo {
     pub fn new(base_type: String) -> Self {
+        Self::with_kind(TypeKind::Named(base_type))
+    }
+
+    pub fn void() -> Self {
+        Self::new("void".to_string())
+    }
+
+    pub fn with_kind(kind: TypeKind) -> Self {
         TypeInfo {
-            base_type,
-            pointer_count: 0,
-            array_sizes: Vec::new(),
-            is_const: false,
-            is_volatile: false,
-            is_restrict: false,
-            is_static: false,
-            is_extern: false,
-            is_reference: false,
-            is_rvalue_reference: false,
-            is_function_pointer: false,
-            function_return_type: None,
-            function_params: Vec::new(),
+            kind,
+            qualifiers: Qualifiers::default(),
+            layout: None,
         }
     }
 
-    pub fn to_string(&self, var_name: &str) -> String {
-        let mut result = String::new();
-
-        if self.is_extern {
-            result.push_str("extern ");
-        }
-        if self.is_static {
-            result.push_str("static ");
-        }
-
-        if self.is_function_pointer {
-            // Function pointer: return_type (*var_name)(params)
-            // Get return type, defaulting to void if not specified
-            let ret_base_type = self
-                .function_return_type
-                .as_ref()
-                .map(|t| t.base_type.as_str())
-                .unwrap_or("void");
-            let ret_is_const = self
-                .function_return_type
-                .as_ref()
-                .map(|t| t.is_const)
-                .unwrap_or(false);
-
-            // Add const for return type if present
-            if ret_is_const {
-                result.push_str("const ");
-            }
-            result.push_str(ret_base_type);
-            result.push_str(" (");
-            // Function pointers always need at least one asterisk
-            result.push('*');
-            result.push_str(&"*".repeat(self.pointer_count));
-            result.push_str(var_name);
-            result.push_str(")(");
-
-            for (i, param) in self.function_params.iter().enumerate() {
-                if i > 0 {
-                    result.push_str(", ");
-                }
-                // Add const for parameter if present
-                if param.is_const {
-                    result.push_str("const ");
+    pub fn pointer_to(target: TypeInfo) -> Self {
+        Self::with_kind(TypeKind::Pointer(Box::new(target)))
+    }
+
+    /// An array of `element`. Flexible and variable length arrays take no
+    /// space in the type enclosing them.
+    pub fn array_of(element: TypeInfo, bound: ArrayBound) -> Self {
+        let layout = element.layout.map(|element| TypeLayout {
+            size: element.size * bound.count().unwrap_or(0),
+            alignment: element.alignment,
+        });
+        TypeInfo {
+            layout,
+            ..Self::with_kind(TypeKind::Array {
+                element: Box::new(element),
+                bound,
+            })
+        }
+    }
+
+    /// Name the run-time array bounds held in registers after the
+    /// parameters passed in them, by DWARF register number
+    pub fn name_register_bounds(&mut self, parameters: &ParameterRegisters) {
+        match &mut self.kind {
+            TypeKind::Array { element, bound } => {
+                if let ArrayBound::Dynamic(DynamicBound::Registers(values)) = bound {
+                    if let Some(name) = values.iter().find_map(|value| parameters.name(value)) {
+                        *bound = ArrayBound::Dynamic(DynamicBound::Named(name));
+                    }
                 }
-                result.push_str(&param.base_type);
-                if param.pointer_count > 0 {
-                    result.push_str(&"*".repeat(param.pointer_count));
+                element.name_register_bounds(parameters);
+            }
+            TypeKind::Pointer(target)
+            | TypeKind::Reference(target)
+            | TypeKind::RvalueReference(target)
+            | TypeKind::MemberPointer { target, .. } => target.name_register_bounds(parameters),
+            TypeKind::Vector { element, .. } => element.name_register_bounds(parameters),
+            TypeKind::Function {
+                return_type,
+                params,
+                ..
+            } => {
+                return_type.name_register_bounds(parameters);
+                for param in params {
+                    param.name_register_bounds(parameters);
                 }
             }
+            TypeKind::Named(_) => {}
+        }
+    }
 
-            result.push(')');
-        } else {
-            // Add type qualifiers before base type
-            if self.is_const {
-                result.push_str("const ");
-            }
-            if self.is_volatile {
-                result.push_str("volatile ");
+    /// Add qualifiers to this level of the type. Qualifiers of an array or
+    /// vector apply to its elements, as in C.
+    pub fn add_qualifiers(&mut self, qualifiers: Qualifiers) {
+        match &mut self.kind {
+            TypeKind::Array { element, .. } | TypeKind::Vector { element, .. } => {
+                element.add_qualifiers(qualifiers)
             }
-            if self.is_restrict {
-                result.push_str("restrict ");
+            _ => self.qualifiers.add(qualifiers),
+        }
+    }
+
+    /// The named type at the bottom of the tree, reached through pointers,
+    /// references, arrays, vectors and return types
+    pub fn base(&self) -> &TypeInfo {
+        match &self.kind {
+            TypeKind::Named(_) => self,
+            TypeKind::Pointer(target)
+            | TypeKind::Reference(target)
+            | TypeKind::RvalueReference(target)
+            | TypeKind::MemberPointer { target, .. } => target.base(),
+            TypeKind::Array { element, .. } | TypeKind::Vector { element, .. } => element.base(),
+            TypeKind::Function { return_type, .. } => return_type.base(),
+        }
+    }
+
+    /// The name of the named type at the bottom of the tree
+    pub fn base_name(&self) -> &str {
+        match &self.base().kind {
+            TypeKind::Named(name) => name,
+            _ => unreachable!("base() always returns a named type"),
+        }
+    }
+
+    /// Whether the type is or refers to a function type, as function
+    /// pointers do
+    pub fn contains_function(&self) -> bool {
+        match &self.kind {
+            TypeKind::Named(_) => false,
+            TypeKind::Pointer(target)
+            | TypeKind::Reference(target)
+            | TypeKind::RvalueReference(target)
+            | TypeKind::MemberPointer { target, .. } => target.contains_function(),
+            TypeKind::Array { element, .. } | TypeKind::Vector { element, .. } => {
+                element.contains_function()
             }
-            result.push_str(&self.base_type);
-            result.push(' ');
-
-            // References and pointers (attached to variable name)
-            if self.is_rvalue_reference {
-                result.push_str("&&");
-            } else if self.is_reference {
-                result.push('&');
-            } else if self.pointer_count > 0 {
-                result.push_str(&"*".repeat(self.pointer_count));
+            TypeKind::Function { .. } => true,
+        }
+    }
+
+    /// Apply `f` to every named type in the tree, including those of
+    /// function parameters
+    pub fn map_names(&self, f: &dyn Fn(&str) -> String) -> TypeInfo {
+        self.map(f, &|bound| bound.clone())
+    }
+
+    /// This type with its type names replaced by `f` and its array bounds
+    /// by `bounds`
+    pub fn map(
+        &self,
+        f: &dyn Fn(&str) -> String,
+        bounds: &dyn Fn(&ArrayBound) -> ArrayBound,
+    ) -> TypeInfo {
+        let kind = match &self.kind {
+            TypeKind::Named(name) => TypeKind::Named(f(name)),
+            TypeKind::Pointer(target) => TypeKind::Pointer(Box::new(target.map(f, bounds))),
+            TypeKind::Reference(target) => TypeKind::Reference(Box::new(target.map(f, bounds))),
+            TypeKind::RvalueReference(target) => {
+                TypeKind::RvalueReference(Box::new(target.map(f, bounds)))
             }
+            TypeKind::MemberPointer { class, target } => TypeKind::MemberPointer {
+                class: Box::new(class.map(f, bounds)),
+                target: Box::new(target.map(f, bounds)),
+            },
+            TypeKind::Array { element, bound } => TypeKind::Array {
+                element: Box::new(element.map(f, bounds)),
+                bound: bounds(bound),
+            },
+            TypeKind::Vector { element, size } => TypeKind::Vector {
+                element: Box::new(element.map(f, bounds)),
+                size: *size,
+            },
+            TypeKind::Function {
+                return_type,
+                params,
+                is_variadic,
+                is_prototyped,
+            } => TypeKind::Function {
+                return_type: Box::new(return_type.map(f, bounds)),
+                params: params.iter().map(|param| param.map(f, bounds)).collect(),
+                is_variadic: *is_variadic,
+                is_prototyped: *is_prototyped,
+            },
+        };
+        TypeInfo {
+            kind,
+            qualifiers: self.qualifiers,
+            layout: self.layout,
+        }
+    }
 
-            result.push_str(var_name);
+    /// The specifiers of a declaration of this type: the named type with its
+    /// qualifiers, as in `const char`, and the attribute of a vector of it
+    pub fn specifiers(&self) -> String {
+        let base = self.base();
+        let keywords = base.qualifiers.keywords();
+        let mut specifiers = if keywords.is_empty() {
+            self.base_name().to_string()
+        } else {
+            format!("{} {}", keywords, self.base_name())
+        };
+        if let Some(size) = self.vector_size() {
+            specifiers.push_str(&format!(" __attribute__((vector_size({})))", size));
+        }
+        specifiers
+    }
+
+    /// The size of the vector the base type is an element of, if any
+    fn vector_size(&self) -> Option<u64> {
+        match &self.kind {
+            TypeKind::Named(_) | TypeKind::Function { .. } => None,
+            TypeKind::Vector { size, .. } => Some(*size),
+            TypeKind::Pointer(target)
+            | TypeKind::Reference(target)
+            | TypeKind::RvalueReference(target)
+            | TypeKind::MemberPointer { target, .. } => target.vector_size(),
+            TypeKind::Array { element, .. } => element.vector_size(),
+        }
+    }
+
+    /// The parts of a declarator written before and after the declared
+    /// name, following C's inside-out rules: pointers are written before the
+    /// name, arrays and parameter lists after it, and parentheses group a
+    /// pointer with the name when it points to an array or function.
+    ///
+    /// `named` tells whether a name will be written between the parts.
+    pub fn declarator_parts(&self, named: bool) -> (String, String) {
+        let mut before = String::new();
+        let mut after = String::new();
+        let mut current = self;
+
+        loop {
+            let (mut prefix, target) = match &current.kind {
+                TypeKind::Named(_) => break,
+                TypeKind::Pointer(target) => ("*".to_string(), target),
+                TypeKind::Reference(target) => ("&".to_string(), target),
+                TypeKind::RvalueReference(target) => ("&&".to_string(), target),
+                TypeKind::MemberPointer { class, target } => (
+                    format!("{}::*", class_scope_name(class.base_name())),
+                    target,
+                ),
+                TypeKind::Array { element, bound } => {
+                    after.push_str(&format!("[{}]", bound.declarator()));
+                    current = element;
+                    continue;
+                }
+                // The vector's attribute is written with the specifiers
+                TypeKind::Vector { element, .. } => {
+                    current = element;
+                    continue;
+                }
+                TypeKind::Function {
+                    return_type,
+                    params,
+                    is_variadic,
+                    is_prototyped,
+                } => {
+                    let params: Vec<String> =
+                        params.iter().map(|param| param.to_string("")).collect();
+                    after.push_str(&format!(
+                        "({})",
+                        parameter_list(params, *is_variadic, *is_prototyped)
+                    ));
+                    // Qualifiers of a method type, as in `int (Foo::*)() const`
+                    if !current.qualifiers.is_empty() {
+                        after.push(' ');
+                        after.push_str(&current.qualifiers.keywords());
+                    }
+                    current = return_type;
+                    continue;
+                }
+            };
 
-            for size in &self.array_sizes {
-                result.push_str(&format!("[{}]", size));
+            if !current.qualifiers.is_empty() {
+                prefix.push_str(&current.qualifiers.keywords());
+                if named || !before.is_empty() {
+                    prefix.push(' ');
+                }
+            }
+            before.insert_str(0, &prefix);
+            if matches!(
+                target.kind,
+                TypeKind::Array { .. } | TypeKind::Function { .. }
+            ) {
+                before.insert(0, '(');
+                after.push(')');
             }
+            current = target;
         }
 
-        result
+        (before, after)
+    }
+
+    /// Format a declaration of `var_name` with this type. An empty name
+    /// gives an abstract declarator, as used for parameter types.
+    pub fn to_string(&self, var_name: &str) -> String {
+        let (before, after) = self.declarator_parts(!var_name.is_empty());
+        let declarator = format!("{}{}{}", before, var_name, after);
+        let specifiers = self.specifiers();
+
+        // Abstract pointers are attached to the type, as in `char*`
+        if declarator.is_empty() || (var_name.is_empty() && attaches_to_specifiers(&before)) {
+            format!("{}{}", specifiers, declarator)
+        } else {
+            format!("{} {}", specifiers, declarator)
+        }
     }
 }
 
+/// Join the parameters of a function, adding `...` for a variadic function
+/// and `void` for a prototyped function without parameters
+pub fn parameter_list(mut params: Vec<String>, is_variadic: bool, is_prototyped: bool) -> String {
+    if is_variadic {
+        params.push("...".to_string());
+    } else if params.is_empty() && is_prototyped {
+        params.push("void".to_string());
+    }
+    params.join(", ")
+}
+
+/// Whether the start of a declarator can be written right after the
+/// specifiers, as `*` is in `char*`
+pub fn attaches_to_specifiers(before: &str) -> bool {
+    before.is_empty() || before.starts_with(['*', '&'])
+}
+
+/// Whether a bitfield of `bit_size` bits at bit `position` crosses a
+/// boundary between the `unit_size`-byte storage units of its type, which
+/// only packed structs allow
+pub fn straddles_storage_unit(position: u64, bit_size: u64, unit_size: u64) -> bool {
+    let unit_bits = unit_size * 8;
+    unit_bits != 0 && bit_size != 0 && position / unit_bits != (position + bit_size - 1) / unit_bits
+}
+
+/// The name of a class as written before `::`, without its
+/// struct/class/union keyword
+fn class_scope_name(name: &str) -> &str {
+    ["struct ", "class ", "union "]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .unwrap_or(name)
+}
+
 #[derive(Debug, Clone)]
 pub struct Variable {
     pub name: String,
     pub type_info: TypeInfo,
+    pub is_extern: bool,
     pub line: Option<u64>,
     pub accessibility: Option<String>,
     pub offset: Option<u64>,
     pub bit_size: Option<u64>,
+    /// Position of a bitfield's first bit from the start of its struct
     pub bit_offset: Option<u64>,
     pub const_value: Option<ConstValue>,
     pub decl_file: Option<u64>, // File index from DWARF file table
+    /// Alignment requested beyond that of the type (`_Alignas(N)`)
+    pub alignment: Option<u64>,
+    /// In a primary template, the instantiation whose type is shown, as it
+    /// can't be written in terms of the template's parameters
+    pub instantiation: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ConstValue {
     Signed(i64),
     Unsigned(u64),
 }
 
+impl ConstValue {
+    /// The value's bits, truncated to `byte_size` bytes
+    pub fn bits(&self, byte_size: u64) -> u64 {
+        let bits = match *self {
+            ConstValue::Signed(v) => v as u64,
+            ConstValue::Unsigned(v) => v,
+        };
+        if byte_size >= 8 {
+            bits
+        } else {
+            bits & ((1u64 << (byte_size * 8)) - 1)
+        }
+    }
+}
+
+impl std::fmt::Display for ConstValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ConstValue::Signed(v) => write!(f, "{}", v),
+            // Too large for any signed type, which C would warn about
+            ConstValue::Unsigned(v) if *v > i64::MAX as u64 => write!(f, "{}ULL", v),
+            ConstValue::Unsigned(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+/// A C++ template parameter of a class or function instantiation
+#[derive(Debug, Clone)]
+pub struct TemplateParameter {
+    pub name: Option<String>,
+    pub argument: TemplateArgument,
+}
+
+/// The argument a template parameter was instantiated with
+#[derive(Debug, Clone)]
+pub enum TemplateArgument {
+    Type(TypeInfo),
+    Value {
+        type_info: TypeInfo,
+        value: Option<ConstValue>,
+        /// The variable or function a pointer argument points to
+        symbol: Option<String>,
+    },
+    Pack(Vec<TemplateArgument>),
+}
+
 #[derive(Debug, Clone)]
 pub struct Parameter {
     pub name: String,
@@ -285,10 +760,26 @@ pub struct LexicalBlock {
     pub nested_blocks: Vec<LexicalBlock>,
     pub inlined_calls: Vec<InlinedSubroutine>,
     pub labels: Vec<Label>,
+    /// Structs, unions, enums and typedefs defined in the block
+    pub local_types: Vec<Element>,
     pub line: Option<u64>,
 }
 
 impl LexicalBlock {
+    /// Name the run-time array bounds of the block's variables held in
+    /// registers after the parameters passed in them
+    pub fn name_register_bounds(&mut self, parameters: &ParameterRegisters) {
+        for var in &mut self.variables {
+            var.type_info.name_register_bounds(parameters);
+        }
+        for block in &mut self.nested_blocks {
+            block.name_register_bounds(parameters);
+        }
+        for local_type in &mut self.local_types {
+            local_type.name_register_bounds(parameters);
+        }
+    }
+
     /// Calculate the minimum line number from all contents of this block (recursively)
     pub fn min_content_line(&self) -> Option<u64> {
         let mut min_line: Option<u64> = self.line;
@@ -314,6 +805,13 @@ impl LexicalBlock {
             }
         }
 
+        // Check local types
+        for local_type in &self.local_types {
+            if let Some(line) = local_type.line() {
+                min_line = Some(min_line.map_or(line, |m| m.min(line)));
+            }
+        }
+
         // Check nested blocks recursively
         for nested in &self.nested_blocks {
             if let Some(line) = nested.min_content_line() {
@@ -341,6 +839,8 @@ pub struct Function {
     pub lexical_blocks: Vec<LexicalBlock>,
     pub inlined_calls: Vec<InlinedSubroutine>,
     pub labels: Vec<Label>,
+    /// Structs, unions, enums and typedefs defined in the body
+    pub local_types: Vec<Element>,
     pub line: Option<u64>,
     pub is_method: bool,
     pub class_name: Option<String>,
@@ -359,6 +859,9 @@ pub struct Function {
     pub decl_file: Option<u64>, // File index from DWARF file table
     pub specification_offset: Option<usize>, // Absolute offset of the declaration this definition refers to
     pub decl_offset: Option<usize>,          // Absolute offset of this declaration (for matching)
+    pub template_params: Vec<TemplateParameter>,
+    pub is_variadic: bool, // Takes further arguments after its parameters (`...`)
+    pub is_prototyped: bool, // Declared with a prototype (C), so no parameters is `(void)`
 }
 
 #[derive(Debug, Clone)]
@@ -374,9 +877,13 @@ pub struct Compound {
     pub name: Option<String>,
     pub compound_type: String, // "struct", "union", "enum", "class"
     pub members: Vec<Variable>,
+    /// Static data members, which take no space in instances
+    pub static_members: Vec<Variable>,
     pub methods: Vec<Function>,
     pub nested_types: Vec<Compound>, // Nested structs, classes, unions, enums
-    pub enum_values: Vec<(String, Option<i64>)>,
+    pub enum_values: Vec<(String, Option<ConstValue>)>,
+    pub enum_underlying_type: Option<TypeInfo>, // Underlying type of an enum, if known
+    pub is_enum_class: bool,                    // C++ scoped enum (enum class)
     pub line: Option<u64>,
     pub is_typedef: bool,
     pub typedef_name: Option<String>,
@@ -385,6 +892,37 @@ pub struct Compound {
     pub base_classes: Vec<BaseClass>,
     pub is_virtual: bool,
     pub decl_file: Option<u64>, // File index from DWARF file table
+    pub template_params: Vec<TemplateParameter>,
+    /// Alignment requested beyond the natural one (`aligned(N)`)
+    pub alignment: Option<u64>,
+    /// Members sit below their natural alignment (`packed`)
+    pub is_packed: bool,
+}
+
+impl Compound {
+    /// Name the run-time array bounds of the members held in registers
+    /// after the parameters passed in them
+    pub fn name_register_bounds(&mut self, parameters: &ParameterRegisters) {
+        for member in &mut self.members {
+            member.type_info.name_register_bounds(parameters);
+        }
+        for nested in &mut self.nested_types {
+            nested.name_register_bounds(parameters);
+        }
+    }
+
+    /// An anonymous enum's definition as written in a declaration using it
+    pub fn inline_enum_definition(&self) -> String {
+        let enumerators: Vec<String> = self
+            .enum_values
+            .iter()
+            .map(|(name, value)| match value {
+                Some(value) => format!("{} = {}", name, value),
+                None => name.clone(),
+            })
+            .collect();
+        format!("enum {{ {} }}", enumerators.join(", "))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -402,6 +940,8 @@ pub struct TypedefAlias {
     pub target_type: TypeInfo,
     pub line: Option<u64>,
     pub decl_file: Option<u64>,
+    /// Alignment requested beyond that of the target type (`aligned(N)`)
+    pub alignment: Option<u64>,
 }
 
 #[derive(Debug, Clone)]
@@ -413,6 +953,29 @@ pub enum Element {
     TypedefAlias(TypedefAlias),
 }
 
+impl Element {
+    /// The line an element is ordered by: a typedef'd compound's typedef
+    pub fn line(&self) -> Option<u64> {
+        match self {
+            Element::Compound(c) => c.typedef_line.or(c.line),
+            Element::Function(f) => f.line,
+            Element::Variable(v) => v.line,
+            Element::Namespace(ns) => ns.line,
+            Element::TypedefAlias(t) => t.line,
+        }
+    }
+
+    /// Name the run-time array bounds of a local type held in registers
+    /// after the parameters passed in them
+    pub fn name_register_bounds(&mut self, parameters: &ParameterRegisters) {
+        match self {
+            Element::Compound(c) => c.name_register_bounds(parameters),
+            Element::TypedefAlias(t) => t.target_type.name_register_bounds(parameters),
+            Element::Function(_) | Element::Variable(_) | Element::Namespace(_) => {}
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct CompileUnit {
     pub name: String,
@@ -2,6 +2,31 @@ use std::fs;
 use std::path::Path;
 use std::process::Command;
 
+/// Every file below an output directory, by path relative to it, in sorted
+/// order
+fn read_tree(root: &Path) -> Vec<(String, Vec<u8>)> {
+    fn read_dir(dir: &Path, root: &Path, files: &mut Vec<(String, Vec<u8>)>) {
+        let mut entries: Vec<_> = fs::read_dir(dir)
+            .expect("Failed to read output directory")
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .collect();
+        entries.sort();
+        for path in entries {
+            if path.is_dir() {
+                read_dir(&path, root, files);
+            } else {
+                let relative = path.strip_prefix(root).unwrap().display().to_string();
+                files.push((relative, fs::read(&path).unwrap()));
+            }
+        }
+    }
+
+    let mut files = Vec::new();
+    read_dir(root, root, &mut files);
+    files
+}
+
 #[test]
 fn test_jcphuff_object_file() {
     let sample_path = "samples/jcphuff.o";
@@ -347,3 +372,2657 @@ void MyClass::setData(const char *name, int size) {
     let _ = fs::remove_file(test_obj);
     let _ = fs::remove_dir_all(output_dir);
 }
+
+#[test]
+fn test_big_endian_archives_match_little_endian() {
+    // The big-endian samples carry the same DWARF as their little-endian
+    // counterparts, re-encoded for big-endian MIPS targets.
+    let pairs = [
+        ("samples/libjpeg_mips.a", "samples/libjpeg_mips_be.a"),
+        ("samples/libjpeg_mips64.a", "samples/libjpeg_mips64_be.a"),
+    ];
+
+    for (idx, (le_path, be_path)) in pairs.iter().enumerate() {
+        if !Path::new(le_path).exists() || !Path::new(be_path).exists() {
+            eprintln!("Sample {} or {} not found, skipping", le_path, be_path);
+            continue;
+        }
+
+        let le_output = format!("/tmp/test_endian_le{}", idx);
+        let be_output = format!("/tmp/test_endian_be{}", idx);
+
+        for (sample_path, output_dir) in [(le_path, &le_output), (be_path, &be_output)] {
+            let _ = fs::remove_dir_all(output_dir);
+            let output = Command::new("cargo")
+                .args(["run", "--", sample_path, "-o", output_dir])
+                .output()
+                .expect("Failed to execute dwarf-c-reconstructor");
+
+            assert!(
+                output.status.success(),
+                "dwarf-c-reconstructor failed for {}:\n{}",
+                sample_path,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+
+        for file in ["src/jpeglib/jcapimin.c", "src/jpeglib/jcphuff.c"] {
+            let le_content = fs::read_to_string(format!("{}/{}", le_output, file))
+                .unwrap_or_else(|_| panic!("{} missing for {}", file, le_path));
+            let be_content = fs::read_to_string(format!("{}/{}", be_output, file))
+                .unwrap_or_else(|_| panic!("{} missing for {}", file, be_path));
+            assert!(
+                be_content.contains("//"),
+                "Big-endian output for {} should not be empty",
+                file
+            );
+            assert_eq!(
+                le_content, be_content,
+                "{} differs between {} and {}",
+                file, le_path, be_path
+            );
+        }
+
+        let _ = fs::remove_dir_all(&le_output);
+        let _ = fs::remove_dir_all(&be_output);
+    }
+}
+
+#[test]
+fn test_relocations_applied_for_all_architectures() {
+    // Every archive sample is a relocatable build of libjpeg. Their DWARF
+    // sections only read correctly once relocations (including REL implicit
+    // addends on 32-bit ARM and MIPS) have been applied.
+    let samples = [
+        "samples/libjpeg_arm64-v8a.a",
+        "samples/libjpeg_armeabi-v7a.a",
+        "samples/libjpeg_armeabi.a",
+        "samples/libjpeg_mips.a",
+        "samples/libjpeg_mips64.a",
+        "samples/libjpeg_x86.a",
+        "samples/libjpeg_x86_64.a",
+    ];
+
+    for (idx, sample_path) in samples.iter().enumerate() {
+        if !Path::new(sample_path).exists() {
+            eprintln!("Sample {} not found, skipping", sample_path);
+            continue;
+        }
+
+        let output_dir = format!("/tmp/test_relocations{}", idx);
+        let _ = fs::remove_dir_all(&output_dir);
+
+        let output = Command::new("cargo")
+            .args(["run", "--", sample_path, "-o", &output_dir])
+            .output()
+            .expect("Failed to execute dwarf-c-reconstructor");
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            output.status.success(),
+            "dwarf-c-reconstructor failed for {}:\n{}",
+            sample_path,
+            stderr
+        );
+        assert!(
+            !stderr.contains("Could not apply relocation"),
+            "Unapplied relocations in {}:\n{}",
+            sample_path,
+            stderr
+        );
+
+        let content = fs::read_to_string(format!("{}/src/jpeglib/jcapimin.c", output_dir))
+            .unwrap_or_else(|_| panic!("jcapimin.c missing for {}", sample_path));
+        assert!(
+            content.contains(
+                "void jpeg_CreateCompress(j_compress_ptr cinfo, int version, size_t structsize)"
+            ),
+            "String attributes were not relocated correctly for {}:\n{}",
+            sample_path,
+            content
+        );
+
+        let _ = fs::remove_dir_all(&output_dir);
+    }
+}
+
+#[test]
+fn test_archive_members_parsed_in_place() {
+    // Archive members are parsed straight out of the mapped archive; the
+    // result must match parsing each member from a file of its own
+    let sample_path = "samples/libsynth.a";
+    if !Path::new(sample_path).exists() {
+        eprintln!("Sample archive {} not found, skipping test", sample_path);
+        return;
+    }
+
+    let work_dir = "/tmp/test_archive_in_place";
+    let archive_output = "/tmp/test_archive_in_place_archive";
+    let members_output = "/tmp/test_archive_in_place_members";
+    let _ = fs::remove_dir_all(work_dir);
+    fs::create_dir_all(work_dir).expect("Failed to create work dir");
+
+    let archive_path = fs::canonicalize(sample_path).expect("Failed to resolve sample path");
+    let extract = Command::new("ar")
+        .current_dir(work_dir)
+        .arg("x")
+        .arg(&archive_path)
+        .output();
+    let list = Command::new("ar").arg("t").arg(&archive_path).output();
+    let (Ok(extract), Ok(list)) = (extract, list) else {
+        eprintln!("ar not available, skipping test");
+        let _ = fs::remove_dir_all(work_dir);
+        return;
+    };
+    assert!(extract.status.success() && list.status.success());
+
+    // Members are passed in archive order, so units are merged in the same order
+    let members: Vec<String> = String::from_utf8_lossy(&list.stdout)
+        .lines()
+        .map(|member| format!("{}/{}", work_dir, member))
+        .collect();
+
+    for (output_dir, inputs) in [
+        (archive_output, vec![sample_path.to_string()]),
+        (members_output, members),
+    ] {
+        let _ = fs::remove_dir_all(output_dir);
+        let output = Command::new("cargo")
+            .args(["run", "--"])
+            .args(&inputs)
+            .args(["-o", output_dir])
+            .output()
+            .expect("Failed to execute dwarf-c-reconstructor");
+        assert!(
+            output.status.success(),
+            "dwarf-c-reconstructor failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let archive_files = read_tree(Path::new(archive_output));
+    let member_files = read_tree(Path::new(members_output));
+
+    assert!(!archive_files.is_empty(), "No files were generated");
+    let archive_paths: Vec<&String> = archive_files.iter().map(|(path, _)| path).collect();
+    let member_paths: Vec<&String> = member_files.iter().map(|(path, _)| path).collect();
+    assert_eq!(archive_paths, member_paths, "Different files generated");
+    for ((path, archive_content), (_, member_content)) in archive_files.iter().zip(&member_files) {
+        assert!(
+            archive_content == member_content,
+            "{} differs between the archive and its extracted members",
+            path
+        );
+    }
+
+    let _ = fs::remove_dir_all(work_dir);
+    let _ = fs::remove_dir_all(archive_output);
+    let _ = fs::remove_dir_all(members_output);
+}
+
+#[test]
+fn test_split_dwarf() {
+    // Build with -gsplit-dwarf so the object only carries a skeleton unit
+    let work_dir = "/tmp/test_split_dwarf";
+    let output_dir = "/tmp/test_split_dwarf_output";
+    let _ = fs::remove_dir_all(work_dir);
+    fs::create_dir_all(format!("{}/dwo", work_dir)).expect("Failed to create work dir");
+
+    let header_code = r#"
+struct split_point {
+    int x;
+    int y;
+};
+"#;
+    let c_code = r#"
+#include "split_shapes.h"
+int split_sum(struct split_point *p) { return p->x + p->y; }
+"#;
+    fs::write(format!("{}/split_shapes.h", work_dir), header_code)
+        .expect("Failed to write test header");
+    fs::write(format!("{}/split.c", work_dir), c_code).expect("Failed to write test file");
+
+    for (dwarf_version, dwo_lookup) in [("-gdwarf-5", "comp_dir"), ("-gdwarf-4", "dwo_dir")] {
+        // Compile with split debug info
+        let compile = Command::new("gcc")
+            .current_dir(work_dir)
+            .args(["-g", dwarf_version, "-gsplit-dwarf", "-c", "split.c"])
+            .output();
+
+        if compile.is_err() || !compile.as_ref().unwrap().status.success() {
+            eprintln!("gcc not available or compilation failed, skipping test");
+            let _ = fs::remove_dir_all(work_dir);
+            return;
+        }
+
+        let object_path = format!("{}/split.o", work_dir);
+        let mut args = vec!["run", "--", &object_path, "-o", output_dir];
+        let dwo_dir = format!("{}/dwo", work_dir);
+        if dwo_lookup == "dwo_dir" {
+            // Move the .dwo away from the compilation directory
+            fs::rename(
+                format!("{}/split.dwo", work_dir),
+                format!("{}/split.dwo", dwo_dir),
+            )
+            .expect("Failed to move .dwo file");
+            args.extend(["--dwo-dir", &dwo_dir]);
+        }
+
+        let _ = fs::remove_dir_all(output_dir);
+        let output = Command::new("cargo")
+            .args(&args)
+            .output()
+            .expect("Failed to execute dwarf-c-reconstructor");
+
+        assert!(
+            output.status.success(),
+            "dwarf-c-reconstructor failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let header = fs::read_to_string(format!("{}{}/split_shapes.h", output_dir, work_dir))
+            .unwrap_or_else(|_| panic!("Header missing for {}", dwarf_version));
+        assert!(
+            header.contains("struct split_point {") && header.contains("int y;"),
+            "Split unit types missing for {}:\n{}",
+            dwarf_version,
+            header
+        );
+
+        let source = fs::read_to_string(format!("{}{}/split.c", output_dir, work_dir))
+            .unwrap_or_else(|_| panic!("Source missing for {}", dwarf_version));
+        assert!(
+            source.contains("int split_sum(struct split_point *p)"),
+            "Split unit functions missing for {}:\n{}",
+            dwarf_version,
+            source
+        );
+    }
+
+    // Package the .dwo, so the split unit can only come from the .dwp
+    let object_path = format!("{}/split.o", work_dir);
+    let dwp_path = format!("{}/split.dwp", work_dir);
+    let package = Command::new("dwp")
+        .current_dir(work_dir)
+        .args(["-o", "split.dwp", "dwo/split.dwo"])
+        .output();
+    if package.is_ok_and(|package| package.status.success()) {
+        fs::remove_dir_all(format!("{}/dwo", work_dir)).expect("Failed to remove .dwo files");
+
+        let _ = fs::remove_dir_all(output_dir);
+        let output = Command::new("cargo")
+            .args([
+                "run",
+                "--",
+                &object_path,
+                "--dwp",
+                &dwp_path,
+                "-o",
+                output_dir,
+            ])
+            .output()
+            .expect("Failed to execute dwarf-c-reconstructor");
+        assert!(
+            output.status.success(),
+            "dwarf-c-reconstructor failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let header = fs::read_to_string(format!("{}{}/split_shapes.h", output_dir, work_dir))
+            .expect("Header missing for the package");
+        assert!(
+            header.contains("struct split_point {") && header.contains("int y;"),
+            "Split unit types missing from the package:\n{}",
+            header
+        );
+    } else {
+        eprintln!("dwp not available or failed, skipping package check");
+    }
+
+    // A package belongs to one program, so it can't be given for several inputs
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            &object_path,
+            &object_path,
+            "--dwp",
+            &dwp_path,
+            "-o",
+            output_dir,
+        ])
+        .output()
+        .expect("Failed to execute dwarf-c-reconstructor");
+    assert!(
+        !output.status.success(),
+        "--dwp with several inputs should be rejected"
+    );
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("--dwp"),
+        "Error should name --dwp:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Cleanup
+    let _ = fs::remove_dir_all(work_dir);
+    let _ = fs::remove_dir_all(output_dir);
+}
+
+#[test]
+fn test_separate_debug_file() {
+    // Strip a binary and move its DWARF into a separate file linked via .gnu_debuglink
+    let work_dir = "/tmp/test_debuglink";
+    let output_dir = "/tmp/test_debuglink_output";
+    let _ = fs::remove_dir_all(work_dir);
+    fs::create_dir_all(work_dir).expect("Failed to create work dir");
+
+    let c_code = r#"
+struct debuglink_record {
+    int id;
+    char tag;
+};
+int debuglink_id(struct debuglink_record *r) { return r->id; }
+int main(void) { struct debuglink_record r = {1, 'a'}; return debuglink_id(&r); }
+"#;
+    fs::write(format!("{}/debuglink.c", work_dir), c_code).expect("Failed to write test file");
+
+    // The line table stays in the binary, so the two files must be combined
+    let steps: [&[&str]; 8] = [
+        &[
+            "gcc",
+            "-g",
+            "-Wl,--build-id=0x0123456789abcdef",
+            "debuglink.c",
+            "-o",
+            "prog",
+        ],
+        &[
+            "gcc",
+            "-g",
+            "-Wl,--build-id=0xfedcba9876543210",
+            "debuglink.c",
+            "-o",
+            "other",
+        ],
+        &["objcopy", "--only-keep-debug", "prog", "prog.debug"],
+        &["objcopy", "--remove-section=.debug_line", "prog.debug"],
+        &[
+            "objcopy",
+            "--strip-debug",
+            "--keep-section=.debug_line",
+            "prog",
+        ],
+        &["objcopy", "--add-gnu-debuglink=prog.debug", "prog"],
+        &["mkdir", "-p", "debug/.build-id/01"],
+        // A debug file of another build, under the binary's build ID
+        &["cp", "other", "debug/.build-id/01/23456789abcdef.debug"],
+    ];
+    for step in steps {
+        let result = Command::new(step[0])
+            .current_dir(work_dir)
+            .args(&step[1..])
+            .output();
+        if result.is_err() || !result.as_ref().unwrap().status.success() {
+            eprintln!("{} not available or failed, skipping test", step[0]);
+            let _ = fs::remove_dir_all(work_dir);
+            return;
+        }
+    }
+
+    let binary = format!("{}/prog", work_dir);
+    let _ = fs::remove_dir_all(output_dir);
+    let output = Command::new("cargo")
+        .args(["run", "--", &binary, "--find-debug-file", "-o", output_dir])
+        .output()
+        .expect("Failed to execute dwarf-c-reconstructor");
+
+    assert!(
+        output.status.success(),
+        "dwarf-c-reconstructor failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("prog.debug"),
+        "Debug file should have been found through .gnu_debuglink"
+    );
+
+    let content = fs::read_to_string(format!("{}{}/debuglink.c", output_dir, work_dir))
+        .expect("Failed to read generated file");
+    assert!(
+        content.contains("struct debuglink_record {"),
+        "Types should come from the separate debug file:\n{}",
+        content
+    );
+    assert!(
+        content.contains("int debuglink_id(struct debuglink_record *r)"),
+        "Functions should come from the separate debug file:\n{}",
+        content
+    );
+
+    // A debug file under the build ID must carry the binary's build ID
+    let debug_dir = format!("{}/debug", work_dir);
+    let run_with_debug_dir = || {
+        let _ = fs::remove_dir_all(output_dir);
+        let output = Command::new("cargo")
+            .args([
+                "run",
+                "--",
+                &binary,
+                "--debug-dir",
+                &debug_dir,
+                "-o",
+                output_dir,
+            ])
+            .output()
+            .expect("Failed to execute dwarf-c-reconstructor");
+        assert!(
+            output.status.success(),
+            "dwarf-c-reconstructor failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        output
+    };
+    let output = run_with_debug_dir();
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("build ID does not match"),
+        "Debug file of another build should be rejected:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("prog.debug"),
+        "Debug link should be used after the build ID:\n{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    fs::copy(
+        format!("{}/prog.debug", work_dir),
+        format!("{}/.build-id/01/23456789abcdef.debug", debug_dir),
+    )
+    .expect("Failed to copy debug file");
+    let output = run_with_debug_dir();
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains(".build-id/01/23456789abcdef.debug"),
+        "Debug file should have been found through the build ID:\n{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    // A debug file whose CRC doesn't match the link must be rejected
+    fs::write(format!("{}/prog.debug", work_dir), b"stale").expect("Failed to overwrite");
+    let output = Command::new("cargo")
+        .args(["run", "--", &binary, "--find-debug-file", "-o", output_dir])
+        .output()
+        .expect("Failed to execute dwarf-c-reconstructor");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("CRC does not match"),
+        "Stale debug file should be rejected:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Cleanup
+    let _ = fs::remove_dir_all(work_dir);
+    let _ = fs::remove_dir_all(output_dir);
+}
+
+#[test]
+fn test_dwarf5_string_and_address_forms() {
+    // Split DWARF 5 units use DW_FORM_strx and DW_FORM_addrx throughout, while
+    // a regular DWARF 5 build uses DW_FORM_strp/line_strp and DW_FORM_addr.
+    // Both must produce the same output.
+    let work_dir = "/tmp/test_dwarf5_forms";
+    let _ = fs::remove_dir_all(work_dir);
+    fs::create_dir_all(work_dir).expect("Failed to create work dir");
+
+    let c_code = r#"
+struct forms_pair {
+    int first;
+    int second;
+};
+int forms_first(struct forms_pair *p) { return p->first; }
+int forms_second(struct forms_pair *p) { return p->second * 2 + p->first; }
+"#;
+    fs::write(format!("{}/forms.c", work_dir), c_code).expect("Failed to write test file");
+
+    let mut outputs = Vec::new();
+    for (object_name, extra_flag) in [("plain.o", None), ("split.o", Some("-gsplit-dwarf"))] {
+        let mut args = vec!["-g", "-gdwarf-5", "-c", "forms.c", "-o", object_name];
+        args.extend(extra_flag);
+        let compile = Command::new("gcc")
+            .current_dir(work_dir)
+            .args(&args)
+            .output();
+
+        if compile.is_err() || !compile.as_ref().unwrap().status.success() {
+            eprintln!("gcc not available or compilation failed, skipping test");
+            let _ = fs::remove_dir_all(work_dir);
+            return;
+        }
+
+        let object_path = format!("{}/{}", work_dir, object_name);
+        let output_dir = format!("{}/output_{}", work_dir, object_name);
+        let output = Command::new("cargo")
+            .args(["run", "--", &object_path, "-o", &output_dir])
+            .output()
+            .expect("Failed to execute dwarf-c-reconstructor");
+
+        assert!(
+            output.status.success(),
+            "dwarf-c-reconstructor failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let content = fs::read_to_string(format!("{}{}/forms.c", output_dir, work_dir))
+            .unwrap_or_else(|_| panic!("forms.c missing for {}", object_name));
+        outputs.push((object_name, content));
+    }
+
+    let (_, plain) = &outputs[0];
+    assert!(
+        plain.contains("struct forms_pair {") && plain.contains("int forms_second("),
+        "Declarations missing:\n{}",
+        plain
+    );
+    // Both functions live in .text, so the second one doesn't start at 0
+    let ranges: Vec<&str> = plain.lines().filter(|l| l.starts_with("// @ 0x")).collect();
+    assert_eq!(ranges.len(), 2, "Expected two address ranges:\n{}", plain);
+    assert!(
+        !ranges[1].starts_with("// @ 0x0-"),
+        "Second function should start after the first:\n{}",
+        plain
+    );
+
+    for (object_name, content) in &outputs[1..] {
+        assert_eq!(plain, content, "{} differs from plain.o", object_name);
+    }
+
+    // Cleanup
+    let _ = fs::remove_dir_all(work_dir);
+}
+
+#[test]
+fn test_lto_cross_unit_references() {
+    // GCC's LTO output describes the generated code in an <artificial> unit
+    // whose DIEs refer to the early-debug units through DW_FORM_ref_addr
+    let work_dir = "/tmp/test_lto_refs";
+    let output_dir = "/tmp/test_lto_refs_output";
+    let _ = fs::remove_dir_all(work_dir);
+    fs::create_dir_all(work_dir).expect("Failed to create work dir");
+
+    let header_code = r#"
+struct lto_point { int x; int y; };
+typedef enum { LTO_RED, LTO_GREEN } lto_color;
+int lto_area(struct lto_point *p);
+"#;
+    let a_code = r#"
+#include "lto_shapes.h"
+__attribute__((noinline)) int lto_area(struct lto_point *p) { return p->x * p->y; }
+"#;
+    let b_code = r#"
+#include "lto_shapes.h"
+lto_color lto_paint(struct lto_point *p, lto_color c) { return lto_area(p) > 10 ? c : LTO_RED; }
+int main(void) { struct lto_point p = {4, 5}; return lto_paint(&p, LTO_GREEN); }
+"#;
+    fs::write(format!("{}/lto_shapes.h", work_dir), header_code)
+        .expect("Failed to write test header");
+    fs::write(format!("{}/a.c", work_dir), a_code).expect("Failed to write test file");
+    fs::write(format!("{}/b.c", work_dir), b_code).expect("Failed to write test file");
+
+    let compile = Command::new("gcc")
+        .current_dir(work_dir)
+        .args(["-g", "-O2", "-flto", "a.c", "b.c", "-o", "prog"])
+        .output();
+
+    if compile.is_err() || !compile.as_ref().unwrap().status.success() {
+        eprintln!("gcc with LTO not available or compilation failed, skipping test");
+        let _ = fs::remove_dir_all(work_dir);
+        return;
+    }
+
+    let binary = format!("{}/prog", work_dir);
+    let _ = fs::remove_dir_all(output_dir);
+    let output = Command::new("cargo")
+        .args(["run", "--", &binary, "-o", output_dir])
+        .output()
+        .expect("Failed to execute dwarf-c-reconstructor");
+
+    assert!(
+        output.status.success(),
+        "dwarf-c-reconstructor failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let header = fs::read_to_string(format!("{}{}/lto_shapes.h", output_dir, work_dir))
+        .expect("Failed to read generated header");
+    assert!(
+        header.contains("struct lto_point {") && header.contains("} lto_color;"),
+        "Types from the early-debug units missing:\n{}",
+        header
+    );
+
+    // The code addresses come from the <artificial> unit's concrete instances
+    for (file, function) in [
+        ("a.c", "int lto_area(struct lto_point *p)"),
+        ("b.c", "int main(void)"),
+    ] {
+        let content = fs::read_to_string(format!("{}{}/{}", output_dir, work_dir, file))
+            .unwrap_or_else(|_| panic!("{} missing", file));
+        let lines: Vec<&str> = content.lines().collect();
+        let index = lines
+            .iter()
+            .position(|line| line.starts_with(function))
+            .unwrap_or_else(|| panic!("{} missing from {}:\n{}", function, file, content));
+        assert!(
+            index > 0 && lines[index - 1].starts_with("// @ 0x"),
+            "{} should have the address range of its concrete instance:\n{}",
+            function,
+            content
+        );
+    }
+
+    // Cleanup
+    let _ = fs::remove_dir_all(work_dir);
+    let _ = fs::remove_dir_all(output_dir);
+}
+
+#[test]
+fn test_type_units() {
+    // With -fdebug-types-section, struct definitions move into type units that
+    // the compile unit refers to by signature. The output must be the same as
+    // for a regular build, for both DWARF 4 (.debug_types) and DWARF 5.
+    let work_dir = "/tmp/test_type_units";
+    let _ = fs::remove_dir_all(work_dir);
+    fs::create_dir_all(work_dir).expect("Failed to create work dir");
+
+    let header_code = r#"
+struct tu_point { int x; int y; };
+typedef struct tu_rect { struct tu_point min; struct tu_point max; } tu_rect;
+int tu_area(tu_rect *r);
+"#;
+    let c_code = r#"
+#include "tu_shapes.h"
+int tu_area(tu_rect *r) { return (r->max.x - r->min.x) * (r->max.y - r->min.y); }
+"#;
+    fs::write(format!("{}/tu_shapes.h", work_dir), header_code)
+        .expect("Failed to write test header");
+    fs::write(format!("{}/tu.c", work_dir), c_code).expect("Failed to write test file");
+
+    let mut outputs = Vec::new();
+    for (object_name, flags) in [
+        ("plain.o", vec!["-gdwarf-4"]),
+        ("types4.o", vec!["-gdwarf-4", "-fdebug-types-section"]),
+        ("types5.o", vec!["-gdwarf-5", "-fdebug-types-section"]),
+    ] {
+        let mut args = vec!["-g", "-c", "tu.c", "-o", object_name];
+        args.extend(flags);
+        let compile = Command::new("gcc")
+            .current_dir(work_dir)
+            .args(&args)
+            .output();
+
+        if compile.is_err() || !compile.as_ref().unwrap().status.success() {
+            eprintln!("gcc not available or compilation failed, skipping test");
+            let _ = fs::remove_dir_all(work_dir);
+            return;
+        }
+
+        let object_path = format!("{}/{}", work_dir, object_name);
+        let output_dir = format!("{}/output_{}", work_dir, object_name);
+        let output = Command::new("cargo")
+            .args(["run", "--", &object_path, "-o", &output_dir])
+            .output()
+            .expect("Failed to execute dwarf-c-reconstructor");
+
+        assert!(
+            output.status.success(),
+            "dwarf-c-reconstructor failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let mut content = String::new();
+        for file in ["tu_shapes.h", "tu.c"] {
+            let generated = fs::read_to_string(format!("{}{}/{}", output_dir, work_dir, file))
+                .unwrap_or_else(|_| panic!("{} missing for {}", file, object_name));
+            // The producer line records the differing compiler flags
+            for line in generated.lines().filter(|l| !l.starts_with("// Compiler:")) {
+                content.push_str(line);
+                content.push('\n');
+            }
+        }
+        outputs.push((object_name, content));
+    }
+
+    let (_, plain) = &outputs[0];
+    assert!(
+        plain.contains("struct tu_point {") && plain.contains("} tu_rect;"),
+        "Declarations missing:\n{}",
+        plain
+    );
+
+    for (object_name, content) in &outputs[1..] {
+        assert_eq!(plain, content, "{} differs from plain.o", object_name);
+    }
+
+    // Cleanup
+    let _ = fs::remove_dir_all(work_dir);
+}
+
+#[test]
+fn test_dwz_supplementary_file() {
+    // dwz moves shared DIEs into partial units, both in the file itself and in
+    // a supplementary file named by .gnu_debugaltlink. The imported units must
+    // be inlined, and the DW_FORM_GNU_ref_alt and DW_FORM_GNU_strp_alt
+    // references resolved against the supplementary file.
+    let work_dir = "/tmp/test_dwz";
+    let output_dir = "/tmp/test_dwz_output";
+    let _ = fs::remove_dir_all(work_dir);
+    fs::create_dir_all(work_dir).expect("Failed to create work dir");
+
+    // Supplementary file: a partial unit with `int` at 0x10 and
+    // `struct dwz_point` at 0x17; "dwz_counter" is at 0xa in its .debug_str
+    let alt_asm = r#"
+	.section .debug_line,"",@progbits
+.Lline:
+	.file 1 "/tmp/test_dwz/dwz_types.h"
+	.section .debug_info,"",@progbits
+	.long .Linfo_end - .Linfo_start
+.Linfo_start:
+	.value 4
+	.long .Labbrev
+	.byte 8
+	.uleb128 1
+	.long .Lline
+	.uleb128 4
+	.byte 4, 5
+	.string "int"
+	.uleb128 2
+	.long .Lstr_point
+	.byte 8, 1, 1
+	.uleb128 3
+	.string "x"
+	.long 0x10
+	.byte 0, 1, 2
+	.uleb128 3
+	.string "y"
+	.long 0x10
+	.byte 4, 1, 3
+	.byte 0
+	.byte 0
+.Linfo_end:
+	.section .debug_abbrev,"",@progbits
+.Labbrev:
+	.uleb128 1, 0x3c
+	.byte 1
+	.uleb128 0x10, 0x17, 0, 0
+	.uleb128 2, 0x13
+	.byte 1
+	.uleb128 0x03, 0x0e, 0x0b, 0x0b, 0x3a, 0x0b, 0x3b, 0x0b, 0, 0
+	.uleb128 3, 0x0d
+	.byte 0
+	.uleb128 0x03, 0x08, 0x49, 0x13, 0x38, 0x0b, 0x3a, 0x0b, 0x3b, 0x0b, 0, 0
+	.uleb128 4, 0x24
+	.byte 0
+	.uleb128 0x0b, 0x0b, 0x3e, 0x0b, 0x03, 0x08, 0, 0
+	.byte 0
+	.section .debug_str,"MS",@progbits,1
+.Lstr_point:
+	.string "dwz_point"
+	.string "dwz_counter"
+	.section .note.gnu.build-id,"a",@note
+	.long 4, 20, 3
+	.string "GNU"
+	.byte 1,2,3,4,5,6,7,8,9,10,11,12,13,14,15,16,17,18,19,20
+"#;
+    // Main file: a compile unit importing the supplementary partial unit and a
+    // local one, whose member refers to the supplementary file's `int`
+    let main_asm = r#"
+	.section .debug_line,"",@progbits
+.Lline:
+	.file 1 "/tmp/test_dwz/dwz_main.c"
+	.file 2 "/tmp/test_dwz/dwz_local.h"
+	.section .debug_info,"",@progbits
+.Linfo:
+	.long .Lcu_end - .Lcu_start
+.Lcu_start:
+	.value 4
+	.long .Labbrev
+	.byte 8
+	.uleb128 1
+	.string "hand-written dwz"
+	.byte 0x0c
+	.string "dwz_main.c"
+	.string "/tmp/test_dwz"
+	.long .Lline
+	.uleb128 2
+	.long 0xb
+	.uleb128 3
+	.long .Lpu_die - .Linfo
+	.uleb128 4
+	.long 0xa
+	.byte 1, 5
+	.long 0x17
+	.byte 0
+.Lcu_end:
+	.long .Lpu_end - .Lpu_start
+.Lpu_start:
+	.value 4
+	.long .Labbrev
+	.byte 8
+.Lpu_die:
+	.uleb128 5
+	.long .Lline
+	.uleb128 6
+	.string "dwz_local"
+	.byte 4, 2, 1
+	.uleb128 7
+	.string "v"
+	.long 0x10
+	.byte 0, 2, 2
+	.byte 0
+	.byte 0
+.Lpu_end:
+	.section .debug_abbrev,"",@progbits
+.Labbrev:
+	.uleb128 1, 0x11
+	.byte 1
+	.uleb128 0x25, 0x08, 0x13, 0x0b, 0x03, 0x08, 0x1b, 0x08, 0x10, 0x17, 0, 0
+	.uleb128 2, 0x3d
+	.byte 0
+	.uleb128 0x18, 0x1f20, 0, 0
+	.uleb128 3, 0x3d
+	.byte 0
+	.uleb128 0x18, 0x10, 0, 0
+	.uleb128 4, 0x34
+	.byte 0
+	.uleb128 0x03, 0x1f21, 0x3a, 0x0b, 0x3b, 0x0b, 0x49, 0x1f20, 0x3f, 0x19, 0, 0
+	.uleb128 5, 0x3c
+	.byte 1
+	.uleb128 0x10, 0x17, 0, 0
+	.uleb128 6, 0x13
+	.byte 1
+	.uleb128 0x03, 0x08, 0x0b, 0x0b, 0x3a, 0x0b, 0x3b, 0x0b, 0, 0
+	.uleb128 7, 0x0d
+	.byte 0
+	.uleb128 0x03, 0x08, 0x49, 0x1f20, 0x38, 0x0b, 0x3a, 0x0b, 0x3b, 0x0b, 0, 0
+	.byte 0
+	.section .gnu_debugaltlink,"",@progbits
+	.string "dwz_alt.o"
+	.byte 1,2,3,4,5,6,7,8,9,10,11,12,13,14,15,16,17,18,19,20
+"#;
+    fs::write(format!("{}/alt.s", work_dir), alt_asm).expect("Failed to write test file");
+    fs::write(format!("{}/main.s", work_dir), main_asm).expect("Failed to write test file");
+
+    for (source, object_name) in [("alt.s", "dwz_alt.o"), ("main.s", "dwz_main.o")] {
+        let assemble = Command::new("gcc")
+            .current_dir(work_dir)
+            .args(["-c", source, "-o", object_name])
+            .output();
+
+        if assemble.is_err() || !assemble.as_ref().unwrap().status.success() {
+            eprintln!("gcc not available or assembly failed, skipping test");
+            let _ = fs::remove_dir_all(work_dir);
+            return;
+        }
+    }
+
+    let _ = fs::remove_dir_all(output_dir);
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            &format!("{}/dwz_main.o", work_dir),
+            "-o",
+            output_dir,
+        ])
+        .output()
+        .expect("Failed to execute dwarf-c-reconstructor");
+
+    assert!(
+        output.status.success(),
+        "dwarf-c-reconstructor failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let read_output = |file: &str| {
+        fs::read_to_string(format!("{}{}/{}", output_dir, work_dir, file))
+            .unwrap_or_else(|_| panic!("{} missing", file))
+    };
+
+    // The supplementary partial unit, with its own file table
+    let types_header = read_output("dwz_types.h");
+    assert!(
+        types_header.contains("struct dwz_point {")
+            && types_header.contains("int x; //2 @ offset 0")
+            && types_header.contains("int y; //3 @ offset 4"),
+        "Supplementary partial unit not inlined:\n{}",
+        types_header
+    );
+
+    // The local partial unit, referring into the supplementary file
+    let local_header = read_output("dwz_local.h");
+    assert!(
+        local_header.contains("struct dwz_local {") && local_header.contains("int v; //2"),
+        "Local partial unit not inlined:\n{}",
+        local_header
+    );
+
+    // A variable named and typed through the supplementary file
+    let main_file = read_output("dwz_main.c");
+    assert!(
+        main_file.contains("extern struct dwz_point dwz_counter; //5"),
+        "Alternate references not resolved:\n{}",
+        main_file
+    );
+
+    // Cleanup
+    let _ = fs::remove_dir_all(work_dir);
+    let _ = fs::remove_dir_all(output_dir);
+}
+
+#[test]
+fn test_multiple_inputs() {
+    // Several inputs, or a directory of them, produce one output tree: shared
+    // headers are merged and methods are matched across inputs, just as for
+    // the members of an archive
+    let work_dir = "/tmp/test_multiple_inputs";
+    let _ = fs::remove_dir_all(work_dir);
+    fs::create_dir_all(format!("{}/objs", work_dir)).expect("Failed to create work dir");
+
+    let header_code = r#"
+namespace multi {
+class Counter {
+public:
+    int Get() const;
+    void Add(int n);
+private:
+    int value;
+};
+}
+"#;
+    let counter_code = r#"
+#include "counter.h"
+namespace multi {
+int Counter::Get() const { return value; }
+void Counter::Add(int n) { value += n; }
+}
+"#;
+    let main_code = r#"
+#include "counter.h"
+int main() { multi::Counter c; c.Add(2); return c.Get(); }
+"#;
+    fs::write(format!("{}/counter.h", work_dir), header_code).expect("Failed to write test header");
+    fs::write(format!("{}/counter.cpp", work_dir), counter_code)
+        .expect("Failed to write test file");
+    fs::write(format!("{}/main.cpp", work_dir), main_code).expect("Failed to write test file");
+
+    // Absolute source paths, so each unit's own file is recognized as such
+    for name in ["counter", "main"] {
+        let compile = Command::new("g++")
+            .current_dir(work_dir)
+            .args([
+                "-g",
+                "-c",
+                &format!("{}/{}.cpp", work_dir, name),
+                "-o",
+                &format!("objs/{}.o", name),
+            ])
+            .output();
+
+        if compile.is_err() || !compile.as_ref().unwrap().status.success() {
+            eprintln!("g++ not available or compilation failed, skipping test");
+            let _ = fs::remove_dir_all(work_dir);
+            return;
+        }
+    }
+    let archive = Command::new("ar")
+        .current_dir(work_dir)
+        .args(["rcs", "lib.a", "objs/counter.o", "objs/main.o"])
+        .output();
+    if archive.is_err() || !archive.as_ref().unwrap().status.success() {
+        eprintln!("ar not available, skipping test");
+        let _ = fs::remove_dir_all(work_dir);
+        return;
+    }
+
+    let run = |inputs: &[&str], output_name: &str, extra_args: &[&str]| {
+        let output_dir = format!("{}/{}", work_dir, output_name);
+        let mut args = vec!["run", "--"];
+        args.extend(inputs);
+        args.extend(["-o", &output_dir]);
+        args.extend(extra_args);
+        let output = Command::new("cargo")
+            .args(&args)
+            .output()
+            .expect("Failed to execute dwarf-c-reconstructor");
+        assert!(
+            output.status.success(),
+            "dwarf-c-reconstructor failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        output_dir
+    };
+    let read =
+        |path: String| fs::read_to_string(&path).unwrap_or_else(|_| panic!("{} missing", path));
+
+    let counter_object = format!("{}/objs/counter.o", work_dir);
+    let main_object = format!("{}/objs/main.o", work_dir);
+    let files_output = run(&[&counter_object, &main_object], "output_files", &[]);
+    let archive_output = run(&[&format!("{}/lib.a", work_dir)], "output_archive", &[]);
+    let dir_output = run(
+        &[&format!("{}/objs", work_dir)],
+        "output_dir",
+        &["--per-input-dirs"],
+    );
+
+    for file in ["counter.h", "counter.cpp", "main.cpp"] {
+        assert_eq!(
+            read(format!("{}{}/{}", files_output, work_dir, file)),
+            read(format!("{}{}/{}", archive_output, work_dir, file)),
+            "{} differs between separate inputs and an archive",
+            file
+        );
+    }
+
+    let header = read(format!("{}{}/counter.h", files_output, work_dir));
+    assert!(
+        header.contains("class Counter {") && header.contains("void Add(int n);"),
+        "Shared header not merged:\n{}",
+        header
+    );
+
+    // Headers stay shared, while source files go to a directory per input
+    assert_eq!(
+        read(format!("{}{}/counter.h", dir_output, work_dir)),
+        header
+    );
+    for (input, file) in [("counter.o", "counter.cpp"), ("main.o", "main.cpp")] {
+        assert_eq!(
+            read(format!("{}/{}{}/{}", dir_output, input, work_dir, file)),
+            read(format!("{}{}/{}", files_output, work_dir, file)),
+            "{} differs in the per-input directory",
+            file
+        );
+    }
+
+    // Inputs sharing a source file would overwrite each other's copy, so
+    // each gets its own directory
+    let shared_output = run(
+        &[&counter_object, &format!("{}/lib.a", work_dir)],
+        "output_shared",
+        &[],
+    );
+    for input in ["counter.o", "lib.a"] {
+        assert_eq!(
+            read(format!(
+                "{}/{}{}/counter.cpp",
+                shared_output, input, work_dir
+            )),
+            read(format!("{}{}/counter.cpp", files_output, work_dir)),
+            "counter.cpp of {} differs",
+            input
+        );
+    }
+    assert!(!Path::new(&format!("{}{}/counter.cpp", shared_output, work_dir)).exists());
+
+    // Cleanup
+    let _ = fs::remove_dir_all(work_dir);
+}
+
+#[test]
+fn test_parallel_parsing_matches_single_threaded() {
+    // Archive members and compile units are parsed on a thread pool; the
+    // output must not depend on how many threads there are
+    let sample_path = "samples/libjpeg_x86_64.a";
+    if !Path::new(sample_path).exists() {
+        eprintln!("Sample archive {} not found, skipping test", sample_path);
+        return;
+    }
+
+    let single_output = "/tmp/test_parallel_jobs1";
+    let parallel_output = "/tmp/test_parallel_jobs4";
+    for (output_dir, jobs) in [(single_output, "1"), (parallel_output, "4")] {
+        let _ = fs::remove_dir_all(output_dir);
+        let output = Command::new("cargo")
+            .args(["run", "--", sample_path, "-o", output_dir, "--jobs", jobs])
+            .output()
+            .expect("Failed to execute dwarf-c-reconstructor");
+        assert!(
+            output.status.success(),
+            "dwarf-c-reconstructor failed with --jobs {}:\n{}",
+            jobs,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let single_files = read_tree(Path::new(single_output));
+    let parallel_files = read_tree(Path::new(parallel_output));
+    assert!(!single_files.is_empty(), "No files were generated");
+    let single_paths: Vec<&String> = single_files.iter().map(|(path, _)| path).collect();
+    let parallel_paths: Vec<&String> = parallel_files.iter().map(|(path, _)| path).collect();
+    assert_eq!(
+        single_paths, parallel_paths,
+        "Different files generated with --jobs 4"
+    );
+    for ((path, single), (_, parallel)) in single_files.iter().zip(&parallel_files) {
+        assert!(single == parallel, "{} differs with --jobs 4", path);
+    }
+
+    let _ = fs::remove_dir_all(single_output);
+    let _ = fs::remove_dir_all(parallel_output);
+}
+
+#[test]
+fn test_output_is_reproducible() {
+    // Repeated runs must produce byte-identical files and console output, so
+    // reconstructions can be checked in without noisy diffs. The libjpeg
+    // archive has unions whose members all sit at offset 0.
+    let sample_path = "samples/libjpeg_x86_64.a";
+    if !Path::new(sample_path).exists() {
+        eprintln!("Sample archive {} not found, skipping test", sample_path);
+        return;
+    }
+
+    let output_dir = "/tmp/test_reproducible_output";
+    let mut runs = Vec::new();
+    for _ in 0..3 {
+        let _ = fs::remove_dir_all(output_dir);
+        let output = Command::new("cargo")
+            .args(["run", "--", sample_path, "-o", output_dir, "--verbose"])
+            .output()
+            .expect("Failed to execute dwarf-c-reconstructor");
+        assert!(
+            output.status.success(),
+            "dwarf-c-reconstructor failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let files = read_tree(Path::new(output_dir));
+        runs.push((String::from_utf8_lossy(&output.stdout).to_string(), files));
+    }
+
+    let (first_stdout, first_files) = &runs[0];
+    assert!(!first_files.is_empty(), "No files were generated");
+    for (stdout, files) in &runs[1..] {
+        assert_eq!(first_stdout, stdout, "Console output differs between runs");
+        assert_eq!(
+            first_files.len(),
+            files.len(),
+            "Different files generated between runs"
+        );
+        for ((first_path, first_content), (path, content)) in first_files.iter().zip(files) {
+            assert_eq!(first_path, path, "Different files generated between runs");
+            assert!(
+                first_content == content,
+                "{} differs between runs",
+                first_path
+            );
+        }
+    }
+
+    let _ = fs::remove_dir_all(output_dir);
+}
+
+#[test]
+fn test_nested_declarators() {
+    // Qualifiers at each level, pointers to arrays, arrays of function
+    // pointers and functions returning function pointers must be written
+    // with C's inside-out declarator syntax
+    let work_dir = "/tmp/test_nested_declarators";
+    let _ = fs::remove_dir_all(work_dir);
+    fs::create_dir_all(work_dir).expect("Failed to create work dir");
+
+    let c_code = r#"
+const char *const *messages;
+int (*row_ptr)[4];
+void (*handlers[3])(int);
+char *const names[2] = {0, 0};
+volatile int *const status_reg = 0;
+static int twice(int n) { return n * 2; }
+int (*pick_handler(int n))(int) { return n ? twice : 0; }
+typedef void (*callback_t)(int, const char *);
+callback_t registered;
+struct decl_table {
+    int (*coef_bits)[64];
+    void *(*alloc)(int, unsigned long);
+    const char *const *table;
+    int (*(*factory)(void))(int);
+};
+struct decl_table decl_instance;
+"#;
+    fs::write(format!("{}/decls.c", work_dir), c_code).expect("Failed to write test file");
+
+    let compile = Command::new("gcc")
+        .current_dir(work_dir)
+        .args(["-g", "-c", "decls.c", "-o", "decls.o"])
+        .output();
+    if compile.is_err() || !compile.as_ref().unwrap().status.success() {
+        eprintln!("gcc not available or compilation failed, skipping test");
+        let _ = fs::remove_dir_all(work_dir);
+        return;
+    }
+
+    let output_dir = format!("{}/output", work_dir);
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            &format!("{}/decls.o", work_dir),
+            "-o",
+            &output_dir,
+        ])
+        .output()
+        .expect("Failed to execute dwarf-c-reconstructor");
+    assert!(
+        output.status.success(),
+        "dwarf-c-reconstructor failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let content =
+        fs::read_to_string(format!("{}{}/decls.c", output_dir, work_dir)).expect("decls.c missing");
+    for declaration in [
+        "const char *const *messages;",
+        "int (*row_ptr)[4];",
+        "void (*handlers[3])(int);",
+        "char *const names[2];",
+        "volatile int *const status_reg;",
+        "int (*pick_handler(int n))(int);",
+        "typedef void (*callback_t)(int, const char*);",
+        "int (*coef_bits)[64];",
+        "void *(*alloc)(int, long unsigned int);",
+        "const char *const *table;",
+        "int (*(*factory)(void))(int);",
+    ] {
+        assert!(
+            content.contains(declaration),
+            "Missing `{}`:\n{}",
+            declaration,
+            content
+        );
+    }
+
+    // The libjpeg sample's declarators, as written in jpeglib.h
+    let sample_path = "samples/libjpeg_x86_64.a";
+    if Path::new(sample_path).exists() {
+        let sample_output = format!("{}/output_libjpeg", work_dir);
+        let output = Command::new("cargo")
+            .args(["run", "--", sample_path, "-o", &sample_output])
+            .output()
+            .expect("Failed to execute dwarf-c-reconstructor");
+        assert!(output.status.success());
+
+        let header = fs::read_to_string(format!("{}/src/jpeglib/jpeglib.h", sample_output))
+            .expect("jpeglib.h missing");
+        for declaration in [
+            "const char *const *jpeg_message_table;",
+            "const jpeg_scan_info *scan_info;",
+            "int (*coef_bits)[64];",
+            "void *(*alloc_small)(j_common_ptr, int, size_t);",
+            "JSAMPARRAY (*alloc_sarray)(j_common_ptr, int, JDIMENSION, JDIMENSION);",
+        ] {
+            assert!(
+                header.contains(declaration),
+                "Missing `{}` in jpeglib.h:\n{}",
+                declaration,
+                header
+            );
+        }
+    }
+
+    // Cleanup
+    let _ = fs::remove_dir_all(work_dir);
+}
+
+#[test]
+fn test_template_instantiations() {
+    // Instantiations of class and function templates are written as explicit
+    // specializations with their template arguments, or, with
+    // --group-templates, as one primary template per class template
+    let work_dir = "/tmp/test_template_instantiations";
+    let _ = fs::remove_dir_all(work_dir);
+    fs::create_dir_all(work_dir).expect("Failed to create work dir");
+
+    let cpp_code = r#"
+template <typename T, int N>
+class Buffer {
+public:
+    Buffer() : count(0) {}
+    T data[N];
+    int count;
+    int tag[sizeof(T)];
+    T get(int i) const { return data[i]; }
+};
+
+template <typename T>
+T maximum(T a, T b) { return a > b ? a : b; }
+
+template <bool B, char C = 'x'>
+struct Flags { int v; };
+
+Buffer<int, 4> ib;
+Buffer<char, 16> cb;
+Flags<true> flags;
+
+template <typename T>
+struct Outer { static T shared; T own; };
+template <typename T> T Outer<T>::shared;
+template struct Outer<double>;
+Outer<double> outer;
+
+int counter;
+template <int *P>
+struct Ref { int z; };
+Ref<&counter> ref;
+
+int use() {
+    return ib.get(0) + cb.get(1) + maximum(1, 2) + flags.v;
+}
+"#;
+    fs::write(format!("{}/templates.cpp", work_dir), cpp_code).expect("Failed to write test file");
+
+    let compile = Command::new("g++")
+        .current_dir(work_dir)
+        .args(["-g", "-c", "templates.cpp", "-o", "templates.o"])
+        .output();
+    if compile.is_err() || !compile.as_ref().unwrap().status.success() {
+        eprintln!("g++ not available or compilation failed, skipping test");
+        let _ = fs::remove_dir_all(work_dir);
+        return;
+    }
+
+    let run = |output_dir: &str, extra_args: &[&str]| {
+        let mut args = vec![
+            "run".to_string(),
+            "--".to_string(),
+            format!("{}/templates.o", work_dir),
+            "-o".to_string(),
+            output_dir.to_string(),
+        ];
+        args.extend(extra_args.iter().map(|arg| arg.to_string()));
+        let output = Command::new("cargo")
+            .args(&args)
+            .output()
+            .expect("Failed to execute dwarf-c-reconstructor");
+        assert!(
+            output.status.success(),
+            "dwarf-c-reconstructor failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        fs::read_to_string(format!("{}{}/templates.cpp", output_dir, work_dir))
+            .expect("templates.cpp missing")
+    };
+
+    let content = run(&format!("{}/output", work_dir), &[]);
+    for expected in [
+        "template<> // T = int, N = 4\nclass Buffer<int, 4> {",
+        "template<> // T = char, N = 16\nclass Buffer<char, 16> {",
+        "template<> // B = true, C = 'x'\nstruct Flags<true, 'x'> {",
+        "template<> // T = int\nint maximum<int>(int a, int b);",
+        // Specializations need the primary template declared
+        "template<typename T> int maximum(int, int);",
+        "template<typename T, int N> class Buffer;\ntemplate<> // T = int, N = 4",
+        "template<typename T> struct Outer;\ntemplate<> // T = double",
+        // Pointer arguments name what they point to
+        "template<int *P> struct Ref;\ntemplate<> // P = &counter",
+        // Members of an explicit specialization are defined as usual
+        "\ndouble Outer<double>::shared; //",
+    ] {
+        assert!(
+            content.contains(expected),
+            "Missing `{}`:\n{}",
+            expected,
+            content
+        );
+    }
+    // The constructor of an instantiation is named after the template
+    assert!(
+        content.contains("    Buffer();") && !content.contains("void Buffer();"),
+        "Constructor not detected:\n{}",
+        content
+    );
+
+    let grouped = run(
+        &format!("{}/output_grouped", work_dir),
+        &["--group-templates"],
+    );
+    for expected in [
+        "// Instantiations: Buffer<int, 4>, Buffer<char, 16>\ntemplate<typename T, int N>\nclass Buffer {",
+        "T get(int i);",
+        "int count;",
+        "T data[N]; //6",
+        "int tag[4] /* type of Buffer<int, 4> */; //8",
+        "// Instantiations: Flags<true, 'x'>\ntemplate<bool B, char C>\nstruct Flags {",
+        // Members of an instantiation are specialized
+        "template<> double Outer<double>::shared; //",
+    ] {
+        assert!(
+            grouped.contains(expected),
+            "Missing `{}`:\n{}",
+            expected,
+            grouped
+        );
+    }
+    assert!(
+        !grouped.contains("class Buffer<char, 16> {"),
+        "Instantiation not grouped:\n{}",
+        grouped
+    );
+    // Offsets and sizes belong to a single instantiation
+    let primary = &grouped[grouped.find("class Buffer {").unwrap()..];
+    let primary = &primary[..primary.find("};").unwrap()];
+    assert!(
+        !primary.contains("offset") && !primary.contains("sizeof"),
+        "Primary template should have no layout comments:\n{}",
+        grouped
+    );
+
+    // Both reconstructions compile
+    for output_dir in ["output", "output_grouped"] {
+        let generated = format!("{}/{}{}/templates.cpp", work_dir, output_dir, work_dir);
+        let recompile = Command::new("g++")
+            .args(["-fsyntax-only", "-x", "c++", &generated])
+            .output()
+            .expect("Failed to run g++");
+        assert!(
+            recompile.status.success(),
+            "{} doesn't compile:\n{}",
+            generated,
+            String::from_utf8_lossy(&recompile.stderr)
+        );
+    }
+
+    // Cleanup
+    let _ = fs::remove_dir_all(work_dir);
+}
+
+#[test]
+fn test_pointer_to_member_types() {
+    // Pointers to data members and methods are written with their class,
+    // including the const of a const method
+    let work_dir = "/tmp/test_pointer_to_member_types";
+    let _ = fs::remove_dir_all(work_dir);
+    fs::create_dir_all(work_dir).expect("Failed to create work dir");
+
+    let cpp_code = r#"
+class Foo {
+public:
+    int value;
+    double ratio;
+    void on_click(int x) {}
+    int compute(int a, char b) const { return a + b; }
+};
+
+typedef void (Foo::*Handler)(int);
+
+struct CallbackEntry {
+    const char *name;
+    void (Foo::*handler)(int);
+    int Foo::*field;
+};
+
+CallbackEntry table[2];
+Handler default_handler = &Foo::on_click;
+int (Foo::*const_method)(int, char) const = &Foo::compute;
+double Foo::*ratio_ptr = &Foo::ratio;
+
+int dispatch(Foo *obj, void (Foo::*fn)(int), int Foo::*member) {
+    int Foo::*local = member;
+    (obj->*fn)(obj->*local);
+    return 0;
+}
+"#;
+    fs::write(format!("{}/members.cpp", work_dir), cpp_code).expect("Failed to write test file");
+
+    let compile = Command::new("g++")
+        .current_dir(work_dir)
+        .args(["-g", "-c", "members.cpp", "-o", "members.o"])
+        .output();
+    if compile.is_err() || !compile.as_ref().unwrap().status.success() {
+        eprintln!("g++ not available or compilation failed, skipping test");
+        let _ = fs::remove_dir_all(work_dir);
+        return;
+    }
+
+    let output_dir = format!("{}/output", work_dir);
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            &format!("{}/members.o", work_dir),
+            "-o",
+            &output_dir,
+        ])
+        .output()
+        .expect("Failed to execute dwarf-c-reconstructor");
+    assert!(
+        output.status.success(),
+        "dwarf-c-reconstructor failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let content = fs::read_to_string(format!("{}{}/members.cpp", output_dir, work_dir))
+        .expect("members.cpp missing");
+    for declaration in [
+        "typedef void (Foo::*Handler)(int);",
+        "void (Foo::*handler)(int); //14 @ offset 8",
+        "int Foo::*field; //15 @ offset 24",
+        "int (Foo::*const_method)(int, char) const;",
+        "double Foo::*ratio_ptr;",
+        "int dispatch(Foo *obj, void (Foo::*fn)(int), int Foo::*member)",
+        "int Foo::*local;",
+    ] {
+        assert!(
+            content.contains(declaration),
+            "Missing `{}`:\n{}",
+            declaration,
+            content
+        );
+    }
+
+    // Cleanup
+    let _ = fs::remove_dir_all(work_dir);
+}
+
+#[test]
+fn test_enum_underlying_types() {
+    // Scoped enums keep their underlying type, enumerator values keep their
+    // sign, and enums that aren't 4 bytes keep their size
+    let work_dir = "/tmp/test_enum_underlying_types";
+    let _ = fs::remove_dir_all(work_dir);
+    fs::create_dir_all(work_dir).expect("Failed to create work dir");
+
+    let cpp_code = r#"
+#include <stdint.h>
+enum class Color : uint8_t { Red, Green = 200, Blue };
+enum class Big : uint64_t { Max = 0xffffffffffffffffULL, One = 1 };
+enum class Plain { A, B };
+enum Neg : int16_t { Low = -5, High = 300 };
+enum Word : uint32_t { Zero, Full = 0xffffffff };
+enum UBig : uint64_t { UMax = 0xffffffffffffffffULL };
+Color color; Big big; Plain plain; Neg neg; Word word; UBig ubig;
+"#;
+    let c_code = r#"
+enum __attribute__((packed)) small { S0, S1 = 200 };
+enum wide { W0, W1 = 0x100000000ULL };
+struct holder { enum small s; char c; enum wide w; };
+struct holder h;
+"#;
+    fs::write(format!("{}/enums.cpp", work_dir), cpp_code).expect("Failed to write test file");
+    fs::write(format!("{}/enums.c", work_dir), c_code).expect("Failed to write test file");
+
+    let compile_cpp = Command::new("g++")
+        .current_dir(work_dir)
+        .args(["-g", "-c", "enums.cpp", "-o", "enums_cpp.o"])
+        .output();
+    let compile_c = Command::new("gcc")
+        .current_dir(work_dir)
+        .args(["-g", "-c", "enums.c", "-o", "enums_c.o"])
+        .output();
+    if compile_cpp.is_err()
+        || !compile_cpp.as_ref().unwrap().status.success()
+        || compile_c.is_err()
+        || !compile_c.as_ref().unwrap().status.success()
+    {
+        eprintln!("gcc/g++ not available or compilation failed, skipping test");
+        let _ = fs::remove_dir_all(work_dir);
+        return;
+    }
+
+    let run = |output_dir: &str, extra_args: &[&str]| {
+        let mut args = vec![
+            "run".to_string(),
+            "--".to_string(),
+            format!("{}/enums_cpp.o", work_dir),
+            format!("{}/enums_c.o", work_dir),
+            "-o".to_string(),
+            output_dir.to_string(),
+        ];
+        args.extend(extra_args.iter().map(|arg| arg.to_string()));
+        let output = Command::new("cargo")
+            .args(&args)
+            .output()
+            .expect("Failed to execute dwarf-c-reconstructor");
+        assert!(
+            output.status.success(),
+            "dwarf-c-reconstructor failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let read = |name: &str| {
+            fs::read_to_string(format!("{}{}/{}", output_dir, work_dir, name))
+                .unwrap_or_else(|_| panic!("{} missing", name))
+        };
+        (read("enums.cpp"), read("enums.c"))
+    };
+
+    let (cpp, c) = run(&format!("{}/output", work_dir), &[]);
+    for expected in [
+        "enum class Color : uint8_t {",
+        "Green = 200, // 0xc8",
+        "enum class Big : uint64_t {",
+        "Max = 18446744073709551615ULL, // 0xffffffffffffffff",
+        "enum class Plain {",
+        "enum __attribute__((packed)) Neg {",
+        "Low = -5, // 0xfffb",
+    ] {
+        assert!(cpp.contains(expected), "Missing `{}`:\n{}", expected, cpp);
+    }
+    for expected in [
+        "enum __attribute__((packed)) small {",
+        "enum wide {",
+        "W1 = 4294967296, // 0x100000000",
+    ] {
+        assert!(c.contains(expected), "Missing `{}`:\n{}", expected, c);
+    }
+
+    // C++ style gives unscoped enums their underlying type instead, even
+    // where it is the size the compiler would pick
+    let (cpp, _) = run(
+        &format!("{}/output_cpp", work_dir),
+        &["--code-style", "c++"],
+    );
+    for expected in [
+        "enum Neg : int16_t {",
+        "enum Word : uint32_t {",
+        "enum UBig : uint64_t {",
+        "UMax = 18446744073709551615ULL, // 0xffffffffffffffff",
+    ] {
+        assert!(
+            cpp.contains(expected),
+            "Missing underlying type `{}`:\n{}",
+            expected,
+            cpp
+        );
+    }
+
+    // Cleanup
+    let _ = fs::remove_dir_all(work_dir);
+}
+
+#[test]
+fn test_variadic_and_unprototyped_functions() {
+    // Variadic functions and function pointers keep their `...`, and
+    // prototyped C functions without parameters are written as `(void)`
+    let work_dir = "/tmp/test_variadic_and_unprototyped_functions";
+    let _ = fs::remove_dir_all(work_dir);
+    fs::create_dir_all(work_dir).expect("Failed to create work dir");
+
+    let c_code = r#"#include <stdarg.h>
+int log_msg(const char *fmt, ...) { va_list ap; va_start(ap, fmt); int r = fmt[0]; va_end(ap); return r; }
+int no_args(void) { return 1; }
+int old_style() { return 2; }
+static int (*printer)(const char *, ...) = log_msg;
+int (*getter)(void) = no_args;
+int (*unproto)() = old_style;
+struct ops { void (*vlog)(int level, const char *fmt, ...); int (*count)(void); };
+struct ops table;
+"#;
+    let cpp_code = r#"int sum(int n, ...) { return n; }
+int (*fp)(int, ...) = sum;
+int none() { return 0; }
+int anyargs(...) { return 1; }
+int (*anyfp)(...) = anyargs;
+"#;
+    fs::write(format!("{}/variadic.c", work_dir), c_code).expect("Failed to write test file");
+    fs::write(format!("{}/variadic.cpp", work_dir), cpp_code).expect("Failed to write test file");
+
+    let compile_c = Command::new("gcc")
+        .current_dir(work_dir)
+        .args(["-g", "-c", "variadic.c", "-o", "variadic_c.o"])
+        .output();
+    let compile_cpp = Command::new("g++")
+        .current_dir(work_dir)
+        .args(["-g", "-c", "variadic.cpp", "-o", "variadic_cpp.o"])
+        .output();
+    if compile_c.is_err()
+        || !compile_c.as_ref().unwrap().status.success()
+        || compile_cpp.is_err()
+        || !compile_cpp.as_ref().unwrap().status.success()
+    {
+        eprintln!("gcc/g++ not available or compilation failed, skipping test");
+        let _ = fs::remove_dir_all(work_dir);
+        return;
+    }
+
+    let output_dir = format!("{}/output", work_dir);
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            &format!("{}/variadic_c.o", work_dir),
+            &format!("{}/variadic_cpp.o", work_dir),
+            "-o",
+            &output_dir,
+        ])
+        .output()
+        .expect("Failed to execute dwarf-c-reconstructor");
+    assert!(
+        output.status.success(),
+        "dwarf-c-reconstructor failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let read = |name: &str| {
+        fs::read_to_string(format!("{}{}/{}", output_dir, work_dir, name))
+            .unwrap_or_else(|_| panic!("{} missing", name))
+    };
+    let c = read("variadic.c");
+    for expected in [
+        "int log_msg(const char *fmt, ...)",
+        "int no_args(void);",
+        "int old_style();",
+        "int (*printer)(const char*, ...);",
+        "extern int (*getter)(void);",
+        "extern int (*unproto)();",
+        "void (*vlog)(int, const char*, ...);",
+        "int (*count)(void);",
+    ] {
+        assert!(c.contains(expected), "Missing `{}`:\n{}", expected, c);
+    }
+
+    // C++ functions are always prototyped, but an empty list stays `()`
+    let cpp = read("variadic.cpp");
+    for expected in [
+        "int sum(int n, ...);",
+        "extern int (*fp)(int, ...);",
+        "int none();",
+        "int anyargs(...);",
+        "extern int (*anyfp)(...);",
+    ] {
+        assert!(cpp.contains(expected), "Missing `{}`:\n{}", expected, cpp);
+    }
+
+    // Cleanup
+    let _ = fs::remove_dir_all(work_dir);
+}
+
+#[test]
+fn test_flexible_and_variable_length_arrays() {
+    // Arrays without a bound keep `[]`, zero-length arrays keep `[0]` and
+    // VLAs are named after the parameter holding their bound, or given a
+    // placeholder one
+    let work_dir = "/tmp/test_flexible_and_variable_length_arrays";
+    let _ = fs::remove_dir_all(work_dir);
+    fs::create_dir_all(work_dir).expect("Failed to create work dir");
+
+    let c_code = r#"struct msg { int len; char data[]; };
+struct old { int len; char data[0]; };
+struct msg m;
+struct old o;
+extern int later[];
+int *use_later(void) { return later; }
+int vla(int n, int m2) {
+    int buf[n];
+    double grid[n][m2];
+    buf[0] = 1; grid[0][0] = 2;
+    if (n > 2) {
+        typedef int row[n + 1];
+        row r;
+        r[0] = 3;
+        buf[0] += r[n];
+    }
+    return buf[0] + (int)grid[0][0] + sizeof(grid);
+}
+"#;
+    fs::write(format!("{}/arrays.c", work_dir), c_code).expect("Failed to write test file");
+
+    // Unoptimized code keeps bounds in stack slots nothing ties to the
+    // parameters; optimized code computes them from the parameters'
+    // registers and entry values
+    for (optimization, buf, grid, row) in [
+        (
+            "-O0",
+            "int buf[/* run-time bound */ 1]; //8",
+            "double grid[/* run-time bound */ 1][/* run-time bound */ 1]; //9",
+            "typedef int row[/* run-time bound */ 1]; //12",
+        ),
+        (
+            "-Og",
+            "int buf[n]; //8",
+            "double grid[n][m2]; //9",
+            "typedef int row[n + 1]; //12",
+        ),
+        (
+            "-O2",
+            "int buf[n]; //8",
+            "double grid[n][m2]; //9",
+            "typedef int row[n + 1]; //12",
+        ),
+    ] {
+        let object = format!("arrays{}.o", optimization);
+        let compile = Command::new("gcc")
+            .current_dir(work_dir)
+            .args(["-g", optimization, "-c", "arrays.c", "-o", &object])
+            .output();
+        if compile.is_err() || !compile.as_ref().unwrap().status.success() {
+            eprintln!("gcc not available or compilation failed, skipping test");
+            let _ = fs::remove_dir_all(work_dir);
+            return;
+        }
+
+        let output_dir = format!("{}/output{}", work_dir, optimization);
+        let output = Command::new("cargo")
+            .args([
+                "run",
+                "--",
+                &format!("{}/{}", work_dir, object),
+                "-o",
+                &output_dir,
+            ])
+            .output()
+            .expect("Failed to execute dwarf-c-reconstructor");
+        assert!(
+            output.status.success(),
+            "dwarf-c-reconstructor failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let generated = format!("{}{}/arrays.c", output_dir, work_dir);
+        let content = fs::read_to_string(&generated).expect("Failed to read generated file");
+        for expected in [
+            "    char data[]; //1 @ offset 4\n}; // sizeof: 4",
+            "    char data[0]; //2 @ offset 4\n}; // sizeof: 4",
+            "extern int later[]; //5",
+            buf,
+            grid,
+            row,
+        ] {
+            assert!(
+                content.contains(expected),
+                "Missing `{}`:\n{}",
+                expected,
+                content
+            );
+        }
+
+        // The function bodies compile
+        let recompile = Command::new("gcc")
+            .args(["-fsyntax-only", "-x", "c", &generated])
+            .output()
+            .expect("Failed to run gcc");
+        assert!(
+            recompile.status.success(),
+            "Reconstructed arrays don't compile:\n{}",
+            String::from_utf8_lossy(&recompile.stderr)
+        );
+    }
+
+    // Cleanup
+    let _ = fs::remove_dir_all(work_dir);
+}
+
+#[test]
+fn test_padding_uses_dwarf_sizes() {
+    // Member sizes come from each type's own DIE, so typedefs of small types
+    // and same-named structs of different sizes give the right padding
+    let work_dir = "/tmp/test_padding_uses_dwarf_sizes";
+    let _ = fs::remove_dir_all(work_dir);
+    fs::create_dir_all(work_dir).expect("Failed to create work dir");
+
+    let a_code = r#"typedef unsigned char u8;
+struct item { long long value; };
+struct wrap {
+    struct item it;
+    char after;
+    int next;
+};
+struct hdr {
+    u8 a;
+    unsigned short c;
+    u8 d;
+    int e;
+};
+struct wrap wa;
+struct hdr h;
+"#;
+    let b_code = r#"struct item { short value; };
+struct wrap {
+    struct item it;
+    char after;
+    int next;
+};
+struct wrap wb;
+"#;
+    fs::write(format!("{}/a.c", work_dir), a_code).expect("Failed to write test file");
+    fs::write(format!("{}/b.c", work_dir), b_code).expect("Failed to write test file");
+
+    for name in ["a", "b"] {
+        let compile = Command::new("gcc")
+            .current_dir(work_dir)
+            .args([
+                "-g",
+                "-c",
+                &format!("{}.c", name),
+                "-o",
+                &format!("{}.o", name),
+            ])
+            .output();
+        if compile.is_err() || !compile.as_ref().unwrap().status.success() {
+            eprintln!("gcc not available or compilation failed, skipping test");
+            let _ = fs::remove_dir_all(work_dir);
+            return;
+        }
+    }
+
+    let output_dir = format!("{}/output", work_dir);
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            &format!("{}/a.o", work_dir),
+            &format!("{}/b.o", work_dir),
+            "-o",
+            &output_dir,
+        ])
+        .output()
+        .expect("Failed to execute dwarf-c-reconstructor");
+    assert!(
+        output.status.success(),
+        "dwarf-c-reconstructor failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let read = |name: &str| {
+        fs::read_to_string(format!("{}{}/{}", output_dir, work_dir, name))
+            .unwrap_or_else(|_| panic!("{} missing", name))
+    };
+    let a = read("a.c");
+    for expected in [
+        "    char after; //5 @ offset 8\n    // [3 bytes padding for alignment]\n    int next;",
+        "    u8 a; //9 @ offset 0\n    // [1 byte padding for alignment]\n    short unsigned int c;",
+        "    u8 d; //11 @ offset 4\n    // [3 bytes padding for alignment]\n    int e;",
+    ] {
+        assert!(a.contains(expected), "Missing `{}`:\n{}", expected, a);
+    }
+    let b = read("b.c");
+    let expected =
+        "    char after; //4 @ offset 2\n    // [1 byte padding for alignment]\n    int next;";
+    assert!(b.contains(expected), "Missing `{}`:\n{}", expected, b);
+
+    // Cleanup
+    let _ = fs::remove_dir_all(work_dir);
+}
+
+#[test]
+fn test_base_type_encodings() {
+    // SIMD vectors, complex, 128-bit and character types are written as
+    // they are spelled in source
+    let work_dir = "/tmp/test_base_type_encodings";
+    let _ = fs::remove_dir_all(work_dir);
+    fs::create_dir_all(work_dir).expect("Failed to create work dir");
+
+    let c_code = r#"typedef float v4sf __attribute__((vector_size(16)));
+v4sf vf;
+int vec8 __attribute__((vector_size(8)));
+_Complex float cf;
+_Complex int ci;
+unsigned __int128 u128;
+_Float16 f16;
+"#;
+    let cpp_code = r#"char16_t a16;
+char32_t a32;
+decltype(nullptr) *np;
+"#;
+    fs::write(format!("{}/types.c", work_dir), c_code).expect("Failed to write test file");
+    fs::write(format!("{}/types.cpp", work_dir), cpp_code).expect("Failed to write test file");
+
+    let compile_c = Command::new("gcc")
+        .current_dir(work_dir)
+        .args(["-g", "-c", "types.c", "-o", "types_c.o"])
+        .output();
+    let compile_cpp = Command::new("g++")
+        .current_dir(work_dir)
+        .args(["-g", "-c", "types.cpp", "-o", "types_cpp.o"])
+        .output();
+    if compile_c.is_err()
+        || !compile_c.as_ref().unwrap().status.success()
+        || compile_cpp.is_err()
+        || !compile_cpp.as_ref().unwrap().status.success()
+    {
+        eprintln!("gcc/g++ not available or compilation failed, skipping test");
+        let _ = fs::remove_dir_all(work_dir);
+        return;
+    }
+
+    let output_dir = format!("{}/output", work_dir);
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            &format!("{}/types_c.o", work_dir),
+            &format!("{}/types_cpp.o", work_dir),
+            "-o",
+            &output_dir,
+        ])
+        .output()
+        .expect("Failed to execute dwarf-c-reconstructor");
+    assert!(
+        output.status.success(),
+        "dwarf-c-reconstructor failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let read = |name: &str| {
+        fs::read_to_string(format!("{}{}/{}", output_dir, work_dir, name))
+            .unwrap_or_else(|_| panic!("{} missing", name))
+    };
+    let c = read("types.c");
+    for expected in [
+        "typedef float __attribute__((vector_size(16))) v4sf; //1",
+        "extern int __attribute__((vector_size(8))) vec8; //3",
+        "extern _Complex float cf; //4",
+        "extern _Complex int ci; //5",
+        "extern unsigned __int128 u128; //6",
+        "extern _Float16 f16; //7",
+    ] {
+        assert!(c.contains(expected), "Missing `{}`:\n{}", expected, c);
+    }
+    let cpp = read("types.cpp");
+    for expected in [
+        "extern char16_t a16; //1",
+        "extern char32_t a32; //2",
+        "extern decltype(nullptr) *np; //3",
+    ] {
+        assert!(cpp.contains(expected), "Missing `{}`:\n{}", expected, cpp);
+    }
+
+    // Cleanup
+    let _ = fs::remove_dir_all(work_dir);
+}
+
+#[test]
+fn test_atomic_alignment_and_packed_attributes() {
+    // _Atomic, alignment requests and packing are kept, so that the
+    // reconstructed structs have the original layout when recompiled
+    let work_dir = "/tmp/test_atomic_alignment_and_packed_attributes";
+    let _ = fs::remove_dir_all(work_dir);
+    fs::create_dir_all(work_dir).expect("Failed to create work dir");
+
+    let c_code = r#"_Atomic int counter;
+int *_Atomic aptr;
+struct packed_s { char c; int i; short s; } __attribute__((packed));
+struct aligned_s { char c; } __attribute__((aligned(16)));
+struct both_s { char c; int i; } __attribute__((packed, aligned(4)));
+struct members { char c; _Alignas(16) int a; double d; };
+typedef int aligned_int __attribute__((aligned(8)));
+struct td { char c; aligned_int x; };
+_Alignas(32) char buffer[10];
+struct outer { char c; struct packed_s p; };
+struct packed_s ps; struct aligned_s as; struct both_s bs;
+struct members ms; struct td t; struct outer o;
+"#;
+    fs::write(format!("{}/layout.c", work_dir), c_code).expect("Failed to write test file");
+
+    let compile = Command::new("gcc")
+        .current_dir(work_dir)
+        .args(["-g", "-c", "layout.c", "-o", "layout.o"])
+        .output();
+    if compile.is_err() || !compile.as_ref().unwrap().status.success() {
+        eprintln!("gcc not available or compilation failed, skipping test");
+        let _ = fs::remove_dir_all(work_dir);
+        return;
+    }
+
+    let output_dir = format!("{}/output", work_dir);
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            &format!("{}/layout.o", work_dir),
+            "-o",
+            &output_dir,
+        ])
+        .output()
+        .expect("Failed to execute dwarf-c-reconstructor");
+    assert!(
+        output.status.success(),
+        "dwarf-c-reconstructor failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let content = fs::read_to_string(format!("{}{}/layout.c", output_dir, work_dir))
+        .expect("Failed to read generated file");
+    for expected in [
+        "extern _Atomic int counter; //1",
+        "extern int *_Atomic aptr; //2",
+        "struct __attribute__((packed)) packed_s { //3",
+        "struct __attribute__((aligned(16))) aligned_s { //4",
+        "struct __attribute__((packed, aligned(4))) both_s { //5",
+        "struct members { //6",
+        "_Alignas(16) int a; //6 @ offset 16",
+        "typedef int aligned_int __attribute__((aligned(8))); //7",
+        "    aligned_int x; //8 @ offset 8",
+        "extern _Alignas(32) char buffer[10]; //9",
+        "struct outer { //10",
+    ] {
+        assert!(
+            content.contains(expected),
+            "Missing `{}`:\n{}",
+            expected,
+            content
+        );
+    }
+
+    // The reconstructed types have the original layout
+    let checks = r#"
+#include <stddef.h>
+_Static_assert(sizeof(struct packed_s) == 7 && offsetof(struct packed_s, s) == 5, "");
+_Static_assert(_Alignof(struct aligned_s) == 16, "");
+_Static_assert(sizeof(struct both_s) == 8 && _Alignof(struct both_s) == 4, "");
+_Static_assert(offsetof(struct members, a) == 16, "");
+_Static_assert(offsetof(struct td, x) == 8, "");
+_Static_assert(sizeof(struct outer) == 8, "");
+"#;
+    fs::write(
+        format!("{}/recompiled.c", work_dir),
+        format!("{}{}", content, checks),
+    )
+    .expect("Failed to write test file");
+    let recompile = Command::new("gcc")
+        .current_dir(work_dir)
+        .args(["-std=c11", "-fsyntax-only", "recompiled.c"])
+        .output()
+        .expect("Failed to run gcc");
+    assert!(
+        recompile.status.success(),
+        "Reconstructed layout differs:\n{}",
+        String::from_utf8_lossy(&recompile.stderr)
+    );
+
+    // Cleanup
+    let _ = fs::remove_dir_all(work_dir);
+}
+
+#[test]
+fn test_bitfield_positions() {
+    // DWARF 2's MSB-relative bit offsets and DWARF 4's absolute ones give the
+    // same bitfield positions, with unnamed bitfields filling the gaps
+    let work_dir = "/tmp/test_bitfield_positions";
+    let _ = fs::remove_dir_all(work_dir);
+    fs::create_dir_all(work_dir).expect("Failed to create work dir");
+
+    let c_code = r#"struct bits {
+    unsigned a : 3;
+    unsigned b : 5;
+    unsigned : 4;
+    unsigned c : 6;
+    unsigned : 0;
+    unsigned d : 7;
+    char e;
+    unsigned long long f : 40;
+    unsigned long long g : 30;
+};
+struct __attribute__((packed)) straddle {
+    char c;
+    unsigned x : 12;
+    unsigned y : 30;
+};
+struct bits b;
+struct straddle s;
+"#;
+    fs::write(format!("{}/bits.c", work_dir), c_code).expect("Failed to write test file");
+
+    let mut contents = Vec::new();
+    for version in ["2", "5"] {
+        let object = format!("bits{}.o", version);
+        let compile = Command::new("gcc")
+            .current_dir(work_dir)
+            .args([
+                "-g",
+                &format!("-gdwarf-{}", version),
+                "-gstrict-dwarf",
+                "-c",
+                "bits.c",
+                "-o",
+                &object,
+            ])
+            .output();
+        if compile.is_err() || !compile.as_ref().unwrap().status.success() {
+            eprintln!("gcc not available or compilation failed, skipping test");
+            let _ = fs::remove_dir_all(work_dir);
+            return;
+        }
+
+        let output_dir = format!("{}/output{}", work_dir, version);
+        let output = Command::new("cargo")
+            .args([
+                "run",
+                "--",
+                &format!("{}/{}", work_dir, object),
+                "-o",
+                &output_dir,
+            ])
+            .output()
+            .expect("Failed to execute dwarf-c-reconstructor");
+        assert!(
+            output.status.success(),
+            "dwarf-c-reconstructor failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        contents.push(
+            fs::read_to_string(format!("{}{}/bits.c", output_dir, work_dir))
+                .expect("Failed to read generated file"),
+        );
+    }
+
+    assert_eq!(
+        contents[0], contents[1],
+        "DWARF 2 and DWARF 5 bitfields differ"
+    );
+    let content = &contents[0];
+    let expected = "    unsigned int a : 3; //2 @ bit offset 0
+    unsigned int b : 5; //3 @ bit offset 3
+    unsigned int : 4;
+    unsigned int c : 6; //5 @ bit offset 12
+    unsigned int : 0;
+    unsigned int d : 7; //7 @ bit offset 32
+    char e; //8 @ offset 5
+    long long unsigned int f : 40; //9 @ bit offset 64
+    long long unsigned int g : 30; //10 @ bit offset 128
+";
+    assert!(
+        content.contains(expected),
+        "Bitfields misplaced:\n{}",
+        content
+    );
+    assert!(
+        content.contains("struct __attribute__((packed)) straddle {"),
+        "Straddling bitfield should make the struct packed:\n{}",
+        content
+    );
+    assert!(
+        content.contains("unsigned int y : 30; //15 @ bit offset 20 [straddles storage unit]"),
+        "Straddling bitfield should be flagged:\n{}",
+        content
+    );
+
+    // Cleanup
+    let _ = fs::remove_dir_all(work_dir);
+}
+
+#[test]
+fn test_function_local_types() {
+    // Types defined inside a function body or block are emitted there, so
+    // the local variables using them refer to defined names
+    let work_dir = "/tmp/test_function_local_types";
+    let _ = fs::remove_dir_all(work_dir);
+    fs::create_dir_all(work_dir).expect("Failed to create work dir");
+
+    let c_code = r#"int process(int n)
+{
+    struct point { int x, y; };
+    typedef unsigned long ulong_t;
+    enum color { RED, GREEN = 5 };
+    struct point p = { n, n };
+    ulong_t total = p.x;
+    enum color c = GREEN;
+    if (n > 2) {
+        typedef struct { short lo, hi; } range_t;
+        range_t r = { 1, 2 };
+        return r.hi + c + (int)total;
+    }
+    return p.y;
+}
+int only_types(void)
+{
+    enum e { X = 3 };
+    return X;
+}
+int block_only(int n)
+{
+    struct pair { int a, b; };
+    if (n) {
+        struct pair p = { n, n };
+        return p.a + p.b;
+    }
+    return 0;
+}
+int same_line(int n)
+{
+    struct local { int a; } l;
+    enum { OFF, ON } state = ON;
+    l.a = n;
+    if (n) {
+        enum { LOW = -1, HIGH = 1 } level = HIGH;
+        return level + l.a;
+    }
+    return state;
+}
+"#;
+    fs::write(format!("{}/local.c", work_dir), c_code).expect("Failed to write test file");
+
+    let compile = Command::new("gcc")
+        .current_dir(work_dir)
+        .args(["-g", "-O0", "-c", "local.c", "-o", "local.o"])
+        .output();
+    if compile.is_err() || !compile.as_ref().unwrap().status.success() {
+        eprintln!("gcc not available or compilation failed, skipping test");
+        let _ = fs::remove_dir_all(work_dir);
+        return;
+    }
+
+    let output_dir = format!("{}/output", work_dir);
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            &format!("{}/local.o", work_dir),
+            "-o",
+            &output_dir,
+        ])
+        .output()
+        .expect("Failed to execute dwarf-c-reconstructor");
+    assert!(
+        output.status.success(),
+        "dwarf-c-reconstructor failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let generated = format!("{}{}/local.c", output_dir, work_dir);
+    let content = fs::read_to_string(&generated).expect("Failed to read generated file");
+    let expected = "{
+    struct point { //3
+        int x, y; //3 @ offset 0
+    }; // sizeof: 8
+    typedef long unsigned int ulong_t; //4
+    enum color { //5
+        RED = 0, // 0x0
+        GREEN = 5, // 0x5
+    }; // sizeof: 4
+    struct point p; //6
+    ulong_t total; //7
+    enum color c; //8
+    {
+        typedef struct { //10
+            short int lo, hi; //10 @ offset 0
+        } range_t; //10 // sizeof: 4
+        range_t r; //11
+    }
+}";
+    assert!(
+        content.contains(expected),
+        "Local types should be defined in the body:\n{}",
+        content
+    );
+
+    // A body holding only local types, or local types and a single block,
+    // keeps them. A type defined in a variable's declaration comes before
+    // it, and anonymous enums are defined inline.
+    for expected in [
+        "int only_types(void) //16
+{
+    enum e { //18
+        X = 3, // 0x3
+    }; // sizeof: 4
+}",
+        "int block_only(int n) //21
+{
+    struct pair { //23
+        int a, b; //23 @ offset 0
+    }; // sizeof: 8
+    {
+        struct pair p; //25
+    }
+}",
+        "int same_line(int n) //30
+{
+    struct local { //32
+        int a; //32 @ offset 0
+    }; // sizeof: 4
+    struct local l; //32
+    enum { OFF = 0, ON = 1 } state; //33
+    {
+        enum { LOW = -1, HIGH = 1 } level; //36
+    }
+}",
+    ] {
+        assert!(
+            content.contains(expected),
+            "Expected:\n{}\nin:\n{}",
+            expected,
+            content
+        );
+    }
+
+    // The reconstructed body compiles
+    let recompile = Command::new("gcc")
+        .args(["-fsyntax-only", "-x", "c", &generated])
+        .output()
+        .expect("Failed to run gcc");
+    assert!(
+        recompile.status.success(),
+        "Reconstructed function doesn't compile:\n{}",
+        String::from_utf8_lossy(&recompile.stderr)
+    );
+
+    // Cleanup
+    let _ = fs::remove_dir_all(work_dir);
+}
+
+#[test]
+fn test_extern_function_prototypes() {
+    // Functions only declared in a unit are kept as prototypes in the file
+    // declaring them, once across units
+    let work_dir = "/tmp/test_extern_function_prototypes";
+    let _ = fs::remove_dir_all(work_dir);
+    fs::create_dir_all(work_dir).expect("Failed to create work dir");
+
+    let api_h = r#"int api_open(const char *path, int flags);
+void api_log(const char *fmt, ...);
+"#;
+    let a_c = r#"#include <stdio.h>
+#include "api.h"
+int helper(int);
+int main(void)
+{
+    int fd = api_open("x", 1);
+    api_log("%d", fd);
+    printf("%d\n", helper(fd));
+    return 0;
+}
+"#;
+    let b_c = r#"#include "api.h"
+int helper(int v) { api_log("h"); return api_open("y", v); }
+"#;
+    fs::write(format!("{}/api.h", work_dir), api_h).expect("Failed to write test file");
+    fs::write(format!("{}/a.c", work_dir), a_c).expect("Failed to write test file");
+    fs::write(format!("{}/b.c", work_dir), b_c).expect("Failed to write test file");
+
+    for source in ["a.c", "b.c"] {
+        let compile = Command::new("gcc")
+            .current_dir(work_dir)
+            .args(["-g", "-c", &format!("{}/{}", work_dir, source)])
+            .output();
+        if compile.is_err() || !compile.as_ref().unwrap().status.success() {
+            eprintln!("gcc not available or compilation failed, skipping test");
+            let _ = fs::remove_dir_all(work_dir);
+            return;
+        }
+    }
+
+    let output_dir = format!("{}/output", work_dir);
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            &format!("{}/a.o", work_dir),
+            &format!("{}/b.o", work_dir),
+            "-o",
+            &output_dir,
+        ])
+        .output()
+        .expect("Failed to execute dwarf-c-reconstructor");
+    assert!(
+        output.status.success(),
+        "dwarf-c-reconstructor failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let read = |path: &str| {
+        fs::read_to_string(format!("{}{}/{}", output_dir, work_dir, path))
+            .unwrap_or_else(|_| panic!("{} should be generated", path))
+    };
+
+    // Both units use the API; its prototypes are in its header once
+    let api = read("api.h");
+    for prototype in [
+        "int api_open(const char*, int); //1",
+        "void api_log(const char*, ...); //2",
+    ] {
+        assert_eq!(
+            api.matches(prototype).count(),
+            1,
+            "Expected `{}` once in api.h:\n{}",
+            prototype,
+            api
+        );
+    }
+
+    // A prototype in the unit's own file stays there, next to the function
+    // using it; the unit defining the function has no prototype
+    let a = read("a.c");
+    assert!(
+        a.contains("int helper(int); //3"),
+        "Missing prototype in a.c:\n{}",
+        a
+    );
+    let b = read("b.c");
+    assert!(
+        b.contains("int helper(int v)") && !b.contains("int helper(int);"),
+        "b.c should only define helper:\n{}",
+        b
+    );
+
+    // Library functions are declared in their headers
+    let stdio = fs::read_to_string(format!("{}/usr/include/stdio.h", output_dir));
+    if let Ok(stdio) = stdio {
+        assert!(
+            stdio.contains("int printf(const char*, ...);"),
+            "Missing printf prototype:\n{}",
+            stdio
+        );
+    }
+
+    // Cleanup
+    let _ = fs::remove_dir_all(work_dir);
+}
+
+#[test]
+fn test_static_data_members() {
+    // Static data members are declared `static` without an offset, whether
+    // DWARF 4 describes them as members or DWARF 5 as variables, and their
+    // definitions are named after their class
+    let work_dir = "/tmp/test_static_data_members";
+    let _ = fs::remove_dir_all(work_dir);
+    fs::create_dir_all(work_dir).expect("Failed to create work dir");
+
+    let header_code = r#"struct Counter {
+    int value;
+    static int instances;
+    static const int limit = 10;
+    static double ratio;
+    char tag;
+};
+namespace ns {
+class Box {
+public:
+    static Box *current;
+    int w;
+private:
+    static const char *names[3];
+};
+}
+"#;
+    let source_code = r#"#include "statics.h"
+int Counter::instances = 3;
+double Counter::ratio;
+ns::Box *ns::Box::current = nullptr;
+const char *ns::Box::names[3] = {"a", "b", "c"};
+int use(Counter &c) { return c.value + Counter::limit; }
+"#;
+    fs::write(format!("{}/statics.h", work_dir), header_code).expect("Failed to write test header");
+    fs::write(format!("{}/statics.cpp", work_dir), source_code).expect("Failed to write test file");
+
+    let mut outputs = Vec::new();
+    for version in ["4", "5"] {
+        let object = format!("{}/statics{}.o", work_dir, version);
+        let compile = Command::new("g++")
+            .current_dir(work_dir)
+            .args([
+                "-g",
+                &format!("-gdwarf-{}", version),
+                "-c",
+                &format!("{}/statics.cpp", work_dir),
+                "-o",
+                &object,
+            ])
+            .output();
+        if compile.is_err() || !compile.as_ref().unwrap().status.success() {
+            eprintln!("g++ not available or compilation failed, skipping test");
+            let _ = fs::remove_dir_all(work_dir);
+            return;
+        }
+
+        let output_dir = format!("{}/output{}", work_dir, version);
+        let output = Command::new("cargo")
+            .args(["run", "--", &object, "-o", &output_dir])
+            .output()
+            .expect("Failed to execute dwarf-c-reconstructor");
+        assert!(
+            output.status.success(),
+            "dwarf-c-reconstructor failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let read = |name: &str| {
+            fs::read_to_string(format!("{}{}/{}", output_dir, work_dir, name))
+                .unwrap_or_else(|_| panic!("{} should be generated", name))
+        };
+        outputs.push((read("statics.h"), read("statics.cpp")));
+    }
+
+    let header = &outputs[0].0;
+    assert_eq!(
+        &outputs[1].0, header,
+        "DWARF 4 and 5 should give the same declarations"
+    );
+
+    let expected_struct = "struct Counter { //1
+    int value; //2 @ offset 0
+    char tag; //6 @ offset 4
+    static int instances; //3
+    static const int limit = 10; //4
+    static double ratio; //5
+}; // sizeof: 8";
+    assert!(
+        header.contains(expected_struct),
+        "Expected:\n{}\nin header:\n{}",
+        expected_struct,
+        header
+    );
+    assert!(
+        header.contains("        int w; //12 @ offset 0\n        static Box *current; //11\n"),
+        "Static members should follow the others of their section:\n{}",
+        header
+    );
+
+    for (_, source) in &outputs {
+        for definition in [
+            "int Counter::instances; //2",
+            "double Counter::ratio; //3",
+            "const char *ns::Box::names[3]; //5",
+        ] {
+            assert!(
+                source.contains(definition),
+                "Missing `{}` in:\n{}",
+                definition,
+                source
+            );
+        }
+        assert!(
+            !source.contains("limit"),
+            "Only defined members get a definition:\n{}",
+            source
+        );
+    }
+
+    // Cleanup
+    let _ = fs::remove_dir_all(work_dir);
+}